@@ -63,8 +63,10 @@ pub fn exit(code: ExitCode) {
 }
 
 /// Prints the panic on the debug output in a consistent manner across loggers.
+///
+/// `thread_name` is the name of the thread that panicked, if known.
 #[doc(hidden)]
-pub fn print_panic(info: &core::panic::PanicInfo<'_>) {
+pub fn print_panic(info: &core::panic::PanicInfo<'_>, thread_name: Option<&str>) {
     // `location()`'s documentation currently states that it always returns `Some(_)`.
     // It is unclear what the panic formatting would be otherwise, because the std does not
     // currently handle the case where the location cannot be obtained.
@@ -77,8 +79,16 @@ pub fn print_panic(info: &core::panic::PanicInfo<'_>) {
     #[cfg(feature = "defmt")]
     let message = ariel_os_debug_log::defmt::Display2Format(&message);
 
-    // Mimics the `Display` implementation of `core::panic::PanicInfo`.
-    println!("panicked at {}:\n{}", location, message);
+    // Mimics the `Display` implementation of `core::panic::PanicInfo`, and the std panic
+    // message for named threads.
+    if let Some(thread_name) = thread_name {
+        println!(
+            "thread '{}' panicked at {}:\n{}",
+            thread_name, location, message
+        );
+    } else {
+        println!("panicked at {}:\n{}", location, message);
+    }
 }
 
 #[cfg(all(feature = "debug-console", feature = "rtt-target"))]
@@ -132,6 +132,15 @@ compile_error!(
 #[cfg(all(feature = "threading", feature = "executor-single-thread"))]
 compile_error!(r#""executor-single-thread" and "threading" are mutually exclusive!"#);
 
+// Without an executor, sleeping threads would never be woken up.
+#[cfg(all(
+    feature = "threading",
+    feature = "time",
+    feature = "executor-none",
+    not(any(feature = "executor-interrupt", feature = "executor-thread"))
+))]
+compile_error!(r#""executor-none" cannot drive the timeouts of "threading" with "time"!"#);
+
 #[cfg(feature = "executor-interrupt")]
 #[distributed_slice(ariel_os_rt::INIT_FUNCS)]
 pub(crate) fn init() {
@@ -181,12 +190,30 @@ fn init() {
         .run(|spawner| spawner.must_spawn(init_task(p)));
 }
 
+/// Handles sleeping threads and the timeouts of blocking thread operations.
+#[cfg(all(feature = "threading", feature = "time"))]
+#[embassy_executor::task]
+async fn thread_timer_task() -> ! {
+    ariel_os_threads::events::run_timer().await
+}
+
 #[embassy_executor::task]
 #[allow(clippy::too_many_lines)]
 async fn init_task(mut peripherals: hal::OptionalPeripherals) {
     let spawner = asynch::Spawner::for_current_executor().await;
     asynch::set_spawner(spawner.make_send());
 
+    // The thread executor would be delayed by busy threads, so the timer gets its own
+    // executor, polled by the scheduler.
+    #[cfg(all(feature = "time", feature = "executor-thread", not(context = "native")))]
+    thread_executor::timer::start().must_spawn(thread_timer_task());
+    #[cfg(all(
+        feature = "threading",
+        feature = "time",
+        not(all(feature = "executor-thread", not(context = "native")))
+    ))]
+    spawner.spawn(thread_timer_task()).unwrap();
+
     #[cfg(feature = "debug-uart")]
     debug_uart::init(&mut peripherals);
 
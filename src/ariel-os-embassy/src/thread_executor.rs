@@ -12,10 +12,20 @@ use embassy_executor::{Spawner, raw};
 // doesn't matter.
 const THREAD_FLAG_WAKEUP: ThreadFlags = 0x01;
 
+// Context of the timer executor, which is never a valid `ThreadId`.
+#[cfg(all(feature = "time", not(context = "native")))]
+const TIMER_EXECUTOR_CONTEXT: *mut () = usize::MAX as *mut ();
+
 // SAFETY: this name is required by embassy-executor and the function signature matches the
 // expected one.
 #[unsafe(no_mangle)]
 fn __pender(context: *mut ()) {
+    #[cfg(all(feature = "time", not(context = "native")))]
+    if context == TIMER_EXECUTOR_CONTEXT {
+        ariel_os_threads::events::pend_timer_executor();
+        return;
+    }
+
     // SAFETY: `context` is a `ThreadId` passed by `ThreadExecutor::new`.
     let thread_id = ThreadId::new(context as usize as u8);
 
@@ -77,3 +87,55 @@ impl Executor {
         }
     }
 }
+
+/// Executor for the thread timer task, polled by the scheduler instead of a thread.
+///
+/// This keeps busy threads with a higher priority than the executor thread from delaying
+/// the wakeups of sleeping threads, time slicing, and the watchdog.
+/// On native, threads run concurrently on host threads, so this is not needed.
+#[cfg(all(feature = "time", not(context = "native")))]
+pub(crate) mod timer {
+    use core::{cell::UnsafeCell, mem::MaybeUninit};
+
+    use embassy_executor::{Spawner, raw};
+    use portable_atomic::{AtomicBool, Ordering};
+
+    use super::TIMER_EXECUTOR_CONTEXT;
+
+    struct TimerExecutor {
+        started: AtomicBool,
+        executor: UnsafeCell<MaybeUninit<raw::Executor>>,
+    }
+
+    // SAFETY: the executor is only written once in `start()`, before it can be polled, and
+    // the scheduler never polls it concurrently.
+    unsafe impl Sync for TimerExecutor {}
+
+    static EXECUTOR: TimerExecutor = TimerExecutor {
+        started: AtomicBool::new(false),
+        executor: UnsafeCell::new(MaybeUninit::uninit()),
+    };
+
+    /// Starts the timer executor and returns a spawner for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timer executor has already been started.
+    pub(crate) fn start() -> Spawner {
+        assert!(
+            !EXECUTOR.started.swap(true, Ordering::AcqRel),
+            "the timer executor has already been started"
+        );
+        // SAFETY: this only happens once, before the executor is polled.
+        let executor =
+            unsafe { (*EXECUTOR.executor.get()).write(raw::Executor::new(TIMER_EXECUTOR_CONTEXT)) };
+        ariel_os_threads::events::set_timer_executor(poll);
+        executor.spawner()
+    }
+
+    fn poll() {
+        // SAFETY: the executor has been initialized before `poll()` has been set, and the
+        // scheduler never polls it concurrently or reentrantly.
+        unsafe { (*EXECUTOR.executor.get()).assume_init_ref().poll() };
+    }
+}
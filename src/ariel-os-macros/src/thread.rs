@@ -5,6 +5,7 @@
 /// # Parameters
 ///
 /// - `autostart`: (*mandatory*) autostart the thread.
+/// - `name`: (*optional*) the thread's name; defaults to the function name.
 /// - `stacksize`: (*optional*) the size of the stack allocated to the thread (in bytes).
 /// - `affinity`: (*optional*) an `ariel_os::thread::CoreAffinity` instance allowing to pin the
 ///   thread to specific cores.
@@ -23,6 +24,15 @@
 /// }
 /// ```
 ///
+/// This starts a thread named `"hello"`:
+///
+/// ```ignore
+/// #[ariel_os::thread(autostart, name = "hello")]
+/// fn print_hello_world() {
+///     println!("Hello world!");
+/// }
+/// ```
+///
 /// This starts a thread with a stack size of 1024 bytes and a priority of 2:
 ///
 /// ```ignore
@@ -74,6 +84,11 @@ pub fn thread(args: TokenStream, item: TokenStream) -> TokenStream {
     let fn_name = thread_function.sig.ident.clone();
     let trampoline_function_name = format_ident!("__{fn_name}_trampoline");
 
+    let name = attrs.name.take().unwrap_or_else(|| {
+        let fn_name = fn_name.to_string();
+        syn::parse_quote! { #fn_name }
+    });
+
     let Parameters {
         stack_size,
         priority,
@@ -90,7 +105,7 @@ pub fn thread(args: TokenStream, item: TokenStream) -> TokenStream {
             #fn_name()
         }
 
-        #thread_crate::autostart_thread!(#trampoline_function_name, stacksize = #stack_size, priority = #priority, affinity = #affinity);
+        #thread_crate::autostart_thread!(#trampoline_function_name, name = #name, stacksize = #stack_size, priority = #priority, affinity = #affinity);
     };
 
     TokenStream::from(expanded)
@@ -136,6 +151,7 @@ mod thread {
     #[derive(Default)]
     pub struct Attributes {
         pub autostart: bool,
+        pub name: Option<syn::Expr>,
         pub stack_size: Option<syn::Expr>,
         pub priority: Option<syn::Expr>,
         pub affinity: Option<syn::Expr>,
@@ -154,6 +170,11 @@ mod thread {
                 return Ok(());
             }
 
+            if meta.path.is_ident("name") {
+                self.name = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+
             if meta.path.is_ident("stacksize") {
                 self.stack_size = Some(meta.value()?.parse()?);
                 return Ok(());
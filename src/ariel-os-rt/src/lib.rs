@@ -133,7 +133,13 @@ mod isr_stack {
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo<'_>) -> ! {
     #[cfg(feature = "panic-printing")]
-    ariel_os_debug::print_panic(_info);
+    {
+        #[cfg(feature = "threading")]
+        let thread_name = ariel_os_threads::current_name();
+        #[cfg(not(feature = "threading"))]
+        let thread_name = None;
+        ariel_os_debug::print_panic(_info, thread_name);
+    }
 
     ariel_os_debug::exit(ariel_os_debug::ExitCode::FAILURE);
 
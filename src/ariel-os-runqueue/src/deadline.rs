@@ -0,0 +1,184 @@
+// Disable indexing lints for now
+#![allow(clippy::indexing_slicing)]
+
+use crate::{MAX_THREADS, ThreadId};
+
+/// Runqueue that orders threads by deadline, supporting `N_THREADS` total.
+///
+/// Used for earliest-deadline-first scheduling: the head is always the thread with the
+/// earliest deadline.
+/// Threads with the same deadline are kept in the order in which they were added.
+///
+/// Deadlines are opaque `u64` values, e.g., timer ticks; lower values are earlier.
+///
+/// Assumptions:
+/// - [`ThreadId`]s range from `0..N_THREADS`
+/// - `N_THREADS` is <= [`MAX_THREADS`] (as u8 is used to store them, but 0xFF is used as
+///   special value)
+///
+/// The current implementation is a sorted circular linked list, which needs an
+/// `[u8; N_THREADS]` array for the next indexes and an `[u64; N_THREADS]` array for the
+/// deadlines.
+/// Adding a thread is O(n) in the number of queued threads.
+#[derive(Debug, Clone, Copy)]
+pub struct DeadlineRunQueue<const N_THREADS: usize> {
+    tail: u8,
+    next_idxs: [u8; N_THREADS],
+    deadlines: [u64; N_THREADS],
+}
+
+impl<const N_THREADS: usize> Default for DeadlineRunQueue<N_THREADS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N_THREADS: usize> DeadlineRunQueue<N_THREADS> {
+    const SENTINEL: u8 = 0xFF;
+
+    /// Returns a new, empty [`DeadlineRunQueue`].
+    #[must_use]
+    pub const fn new() -> Self {
+        const {
+            assert!(N_THREADS <= MAX_THREADS, "too many threads");
+        }
+        Self {
+            tail: Self::SENTINEL,
+            next_idxs: [Self::SENTINEL; N_THREADS],
+            deadlines: [0; N_THREADS],
+        }
+    }
+
+    /// Adds thread with tid `n` with the given deadline.
+    ///
+    /// It is queued after all threads with an earlier or the same deadline.
+    /// Does nothing if the thread is queued already; use [`Self::del()`] first to change
+    /// its deadline.
+    pub fn add(&mut self, n: ThreadId, deadline: u64) {
+        debug_assert!(usize::from(n) < N_THREADS);
+        let n = usize::from(n) as u8;
+        if self.next_idxs[n as usize] != Self::SENTINEL {
+            return;
+        }
+        self.deadlines[n as usize] = deadline;
+
+        if self.tail == Self::SENTINEL {
+            // Queue is empty, link both tail and n.next to n.
+            self.tail = n;
+            self.next_idxs[n as usize] = n;
+            return;
+        }
+
+        // Find the first thread with a later deadline, starting at the head.
+        let mut prev = self.tail;
+        let mut curr = self.next_idxs[prev as usize];
+        while self.deadlines[curr as usize] <= deadline {
+            if curr == self.tail {
+                // No thread has a later deadline, so n becomes the new tail.
+                self.next_idxs[n as usize] = self.next_idxs[curr as usize];
+                self.next_idxs[curr as usize] = n;
+                self.tail = n;
+                return;
+            }
+            prev = curr;
+            curr = self.next_idxs[curr as usize];
+        }
+        // Insert n between prev and curr; if curr is the head, n becomes the new head.
+        self.next_idxs[n as usize] = curr;
+        self.next_idxs[prev as usize] = n;
+    }
+
+    /// Removes thread with tid `n`.
+    ///
+    /// Does nothing if the thread isn't queued.
+    pub fn del(&mut self, n: ThreadId) {
+        let n = usize::from(n) as u8;
+        if self.next_idxs[n as usize] == Self::SENTINEL {
+            return;
+        }
+        // Find previous thread in circular list.
+        let mut prev = n;
+        while self.next_idxs[prev as usize] != n {
+            prev = self.next_idxs[prev as usize];
+        }
+        if prev == n {
+            // Queue is empty now.
+            self.tail = Self::SENTINEL;
+        } else {
+            if self.tail == n {
+                self.tail = prev;
+            }
+            self.next_idxs[prev as usize] = self.next_idxs[n as usize];
+        }
+        self.next_idxs[n as usize] = Self::SENTINEL;
+    }
+
+    /// Returns the thread with the earliest deadline without removing it.
+    #[must_use]
+    pub fn peek_head(&self) -> Option<ThreadId> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(ThreadId::new(self.next_idxs[self.tail as usize]))
+        }
+    }
+
+    /// Removes and returns the thread with the earliest deadline.
+    pub fn pop_head(&mut self) -> Option<ThreadId> {
+        let head = self.peek_head()?;
+        self.del(head);
+        Some(head)
+    }
+
+    /// Returns the deadline of thread `n`, if it is queued.
+    #[must_use]
+    pub fn deadline(&self, n: ThreadId) -> Option<u64> {
+        let n = usize::from(n);
+        (self.next_idxs[n] != Self::SENTINEL).then_some(self.deadlines[n])
+    }
+
+    /// Checks if the queue is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tail == Self::SENTINEL
+    }
+
+    /// Returns the first thread in deadline order that fulfills the predicate.
+    pub fn get_next_filter<F: FnMut(&ThreadId) -> bool>(&self, predicate: F) -> Option<ThreadId> {
+        self.iter().find(predicate)
+    }
+
+    /// Returns an iterator over the queued threads, in deadline order.
+    #[must_use]
+    pub fn iter(&self) -> DeadlineRunQueueIter<'_, N_THREADS> {
+        DeadlineRunQueueIter {
+            queue: self,
+            next: self.peek_head().map(|head| usize::from(head) as u8),
+        }
+    }
+}
+
+impl<'a, const N_THREADS: usize> IntoIterator for &'a DeadlineRunQueue<N_THREADS> {
+    type Item = ThreadId;
+    type IntoIter = DeadlineRunQueueIter<'a, N_THREADS>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over threads in a [`DeadlineRunQueue`], in deadline order.
+pub struct DeadlineRunQueueIter<'a, const N_THREADS: usize> {
+    queue: &'a DeadlineRunQueue<N_THREADS>,
+    // Next thread to be returned, `None` after the tail.
+    next: Option<u8>,
+}
+
+impl<const N_THREADS: usize> Iterator for DeadlineRunQueueIter<'_, N_THREADS> {
+    type Item = ThreadId;
+    fn next(&mut self) -> Option<Self::Item> {
+        let curr = self.next?;
+        self.next = (curr != self.queue.tail).then(|| self.queue.next_idxs[curr as usize]);
+        Some(ThreadId::new(curr))
+    }
+}
@@ -4,13 +4,26 @@
 #![deny(missing_docs)]
 #![expect(clippy::cast_possible_truncation)]
 
+mod deadline;
 mod runqueue;
-pub use runqueue::{RunQueue, RunqueueId, ThreadId};
+pub use deadline::{DeadlineRunQueue, DeadlineRunQueueIter};
+pub use runqueue::{MAX_QUEUES, MAX_THREADS, RunQueue, RunqueueId, ThreadId, bitcache_words};
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rq_size() {
+        // The bit cache of up to one word's worth of runqueues takes a single word.
+        assert_eq!(bitcache_words(16), 1);
+        assert_eq!(size_of::<RunQueue<16, 32>>(), size_of::<usize>() + 16 + 32);
+        assert_eq!(
+            bitcache_words(MAX_QUEUES),
+            MAX_QUEUES / usize::BITS as usize
+        );
+    }
+
     #[test]
     fn test_rq_basic() {
         let mut runqueue: RunQueue<8, 32> = RunQueue::new();
@@ -182,4 +195,153 @@ mod tests {
             Some(ThreadId::new(1))
         );
     }
+
+    #[test]
+    fn many_queues() {
+        let mut runqueue: RunQueue<
+            { MAX_QUEUES },
+            { MAX_THREADS },
+            { bitcache_words(MAX_QUEUES) },
+        > = RunQueue::new();
+
+        runqueue.add(ThreadId::new(0), RunqueueId::new(3));
+        runqueue.add(ThreadId::new(1), RunqueueId::new(64));
+        runqueue.add(ThreadId::new(2), RunqueueId::new(200));
+        runqueue.add(ThreadId::new(254), RunqueueId::new(255));
+
+        assert_eq!(
+            runqueue.get_next_with_rq(),
+            Some((ThreadId::new(254), RunqueueId::new(255)))
+        );
+
+        let mut iter = runqueue.iter_from(ThreadId::new(2), RunqueueId::new(200));
+        assert_eq!(iter.next(), Some(ThreadId::new(1)));
+        assert_eq!(iter.next(), Some(ThreadId::new(0)));
+        assert!(iter.next().is_none());
+
+        assert_eq!(runqueue.pop_next(), Some(ThreadId::new(254)));
+        runqueue.del(ThreadId::new(2));
+        assert_eq!(runqueue.pop_next(), Some(ThreadId::new(1)));
+        assert_eq!(runqueue.pop_next(), Some(ThreadId::new(0)));
+        assert_eq!(runqueue.pop_next(), None);
+    }
+
+    #[test]
+    fn test_drq_ordered() {
+        let mut runqueue: DeadlineRunQueue<32> = DeadlineRunQueue::new();
+        assert!(runqueue.is_empty());
+
+        runqueue.add(ThreadId::new(0), 30);
+        runqueue.add(ThreadId::new(1), 10);
+        runqueue.add(ThreadId::new(2), 40);
+        runqueue.add(ThreadId::new(3), 20);
+
+        assert_eq!(runqueue.peek_head(), Some(ThreadId::new(1)));
+        assert_eq!(runqueue.deadline(ThreadId::new(3)), Some(20));
+        assert_eq!(runqueue.deadline(ThreadId::new(4)), None);
+
+        assert_eq!(runqueue.pop_head(), Some(ThreadId::new(1)));
+        assert_eq!(runqueue.pop_head(), Some(ThreadId::new(3)));
+        assert_eq!(runqueue.pop_head(), Some(ThreadId::new(0)));
+        assert_eq!(runqueue.pop_head(), Some(ThreadId::new(2)));
+        assert_eq!(runqueue.pop_head(), None);
+        assert!(runqueue.is_empty());
+    }
+
+    #[test]
+    fn test_drq_same_deadline_fifo() {
+        let mut runqueue: DeadlineRunQueue<32> = DeadlineRunQueue::new();
+
+        runqueue.add(ThreadId::new(0), 10);
+        runqueue.add(ThreadId::new(1), 5);
+        runqueue.add(ThreadId::new(2), 10);
+        runqueue.add(ThreadId::new(3), 5);
+
+        assert_eq!(runqueue.pop_head(), Some(ThreadId::new(1)));
+        assert_eq!(runqueue.pop_head(), Some(ThreadId::new(3)));
+        assert_eq!(runqueue.pop_head(), Some(ThreadId::new(0)));
+        assert_eq!(runqueue.pop_head(), Some(ThreadId::new(2)));
+    }
+
+    #[test]
+    fn test_drq_add_twice() {
+        let mut runqueue: DeadlineRunQueue<32> = DeadlineRunQueue::new();
+
+        runqueue.add(ThreadId::new(0), 10);
+        runqueue.add(ThreadId::new(1), 20);
+        // Already queued, so the deadline doesn't change.
+        runqueue.add(ThreadId::new(1), 5);
+        assert_eq!(runqueue.peek_head(), Some(ThreadId::new(0)));
+
+        runqueue.del(ThreadId::new(1));
+        runqueue.add(ThreadId::new(1), 5);
+        assert_eq!(runqueue.peek_head(), Some(ThreadId::new(1)));
+    }
+
+    #[test]
+    fn test_drq_del() {
+        let mut runqueue: DeadlineRunQueue<32> = DeadlineRunQueue::new();
+
+        runqueue.add(ThreadId::new(0), 10);
+        runqueue.add(ThreadId::new(1), 20);
+        runqueue.add(ThreadId::new(2), 30);
+
+        // Delete thread that is neither head nor tail.
+        runqueue.del(ThreadId::new(1));
+        assert_eq!(
+            runqueue.iter().collect::<Vec<_>>(),
+            [ThreadId::new(0), ThreadId::new(2)]
+        );
+
+        // Delete tail, then add a thread that becomes the new tail.
+        runqueue.del(ThreadId::new(2));
+        runqueue.add(ThreadId::new(3), 40);
+        assert_eq!(
+            runqueue.iter().collect::<Vec<_>>(),
+            [ThreadId::new(0), ThreadId::new(3)]
+        );
+
+        // Delete invalid thread.
+        runqueue.del(ThreadId::new(4));
+        assert_eq!(runqueue.peek_head(), Some(ThreadId::new(0)));
+
+        // Delete head.
+        runqueue.del(ThreadId::new(0));
+        assert_eq!(runqueue.peek_head(), Some(ThreadId::new(3)));
+
+        // Delete last thread.
+        runqueue.del(ThreadId::new(3));
+        assert_eq!(runqueue.peek_head(), None);
+        assert!(runqueue.iter().next().is_none());
+    }
+
+    #[test]
+    fn test_drq_filter() {
+        let mut runqueue: DeadlineRunQueue<32> = DeadlineRunQueue::new();
+
+        runqueue.add(ThreadId::new(0), 30);
+        runqueue.add(ThreadId::new(1), 10);
+        runqueue.add(ThreadId::new(2), 20);
+
+        assert_eq!(runqueue.get_next_filter(|_| true), Some(ThreadId::new(1)));
+        assert_eq!(runqueue.get_next_filter(|_| false), None);
+        assert_eq!(
+            runqueue.get_next_filter(|t| usize::from(*t) % 2 == 0),
+            Some(ThreadId::new(2))
+        );
+    }
+
+    #[test]
+    fn test_drq_all() {
+        let mut runqueue: DeadlineRunQueue<{ MAX_THREADS }> = DeadlineRunQueue::new();
+
+        // Add in reverse deadline order.
+        for i in 0..MAX_THREADS {
+            runqueue.add(ThreadId::new(i as u8), (MAX_THREADS - i) as u64);
+        }
+        for i in (0..MAX_THREADS).rev() {
+            assert_eq!(runqueue.pop_head(), Some(ThreadId::new(i as u8)));
+        }
+        assert!(runqueue.is_empty());
+    }
 }
@@ -7,6 +7,20 @@ use self::clist::CList;
 
 const USIZE_BITS: usize = mem::size_of::<usize>() * 8;
 
+/// Maximum number of runqueues, as [`RunqueueId`]s are `u8`.
+pub const MAX_QUEUES: usize = 256;
+
+/// Maximum number of threads, as [`ThreadId`]s are `u8` and `0xFF` is used as
+/// special value.
+pub const MAX_THREADS: usize = 255;
+
+/// Returns the number of `usize` words the bit cache of a [`RunQueue`] with `n_queues`
+/// runqueues needs, which is its `N_WORDS` parameter.
+#[must_use]
+pub const fn bitcache_words(n_queues: usize) -> usize {
+    n_queues.div_ceil(USIZE_BITS)
+}
+
 /// Runqueue number.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -47,33 +61,47 @@ impl From<ThreadId> for usize {
 
 /// Runqueue for `N_QUEUES`, supporting `N_THREADS` total.
 ///
+/// `N_WORDS` must be [`bitcache_words(N_QUEUES)`](bitcache_words); the default of one word
+/// covers up to 32 runqueues on 32-bit platforms.
+///
 /// Assumptions:
 /// - runqueue numbers (corresponding priorities) are `0..N_QUEUES` (exclusive)
 /// - higher runqueue number ([`RunqueueId`]) means higher priority
-/// - runqueue numbers fit in usize bits (supporting max 32 priority levels)
+/// - `N_QUEUES` is <= [`MAX_QUEUES`] (as u8 is used to store runqueue numbers)
 /// - [`ThreadId`]s range from `0..N_THREADS`
-/// - `N_THREADS` is <255 (as u8 is used to store them, but 0xFF is used as
+/// - `N_THREADS` is <= [`MAX_THREADS`] (as u8 is used to store them, but 0xFF is used as
 ///   special value)
 ///
-/// The current implementation needs an usize for the bit cache,
+/// Both are checked at compile time.
+///
+/// The current implementation needs `N_WORDS` `usize`s for the bit cache,
 /// an `[u8; N_QUEUES]` array for the list tail indexes
 /// and an `[u8; N_THREADS]` for the list next indexes.
 #[derive(Default)]
-pub struct RunQueue<const N_QUEUES: usize, const N_THREADS: usize> {
+pub struct RunQueue<const N_QUEUES: usize, const N_THREADS: usize, const N_WORDS: usize = 1> {
     /// Bitcache that represents the currently used queues
     /// in `0..N_QUEUES`.
-    bitcache: usize,
+    bitcache: Bitcache<N_WORDS>,
     queues: clist::CList<N_QUEUES, N_THREADS>,
 }
 
-impl<const N_QUEUES: usize, const N_THREADS: usize> RunQueue<{ N_QUEUES }, { N_THREADS }> {
+impl<const N_QUEUES: usize, const N_THREADS: usize, const N_WORDS: usize>
+    RunQueue<{ N_QUEUES }, { N_THREADS }, { N_WORDS }>
+{
     /// Returns a new [`RunQueue`].
     #[must_use]
-    pub const fn new() -> RunQueue<{ N_QUEUES }, { N_THREADS }> {
-        // unfortunately we cannot assert!() on N_QUEUES and N_THREADS,
-        // as panics in const fn's are not (yet) implemented.
+    pub const fn new() -> RunQueue<{ N_QUEUES }, { N_THREADS }, { N_WORDS }> {
+        const {
+            assert!(N_QUEUES > 0, "at least one runqueue is required");
+            assert!(N_QUEUES <= MAX_QUEUES, "too many runqueues");
+            assert!(N_THREADS <= MAX_THREADS, "too many threads");
+            assert!(
+                N_WORDS == bitcache_words(N_QUEUES),
+                "`N_WORDS` must be `bitcache_words(N_QUEUES)`"
+            );
+        }
         RunQueue {
-            bitcache: 0,
+            bitcache: Bitcache::new(),
             queues: CList::new(),
         }
     }
@@ -82,7 +110,7 @@ impl<const N_QUEUES: usize, const N_THREADS: usize> RunQueue<{ N_QUEUES }, { N_T
     pub fn add(&mut self, n: ThreadId, rq: RunqueueId) {
         debug_assert!(usize::from(n) < N_THREADS);
         debug_assert!(usize::from(rq) < N_QUEUES);
-        self.bitcache |= 1 << rq.0;
+        self.bitcache.set(rq.0);
         self.queues.push(n.0, rq.0);
     }
 
@@ -104,14 +132,14 @@ impl<const N_QUEUES: usize, const N_THREADS: usize> RunQueue<{ N_QUEUES }, { N_T
         //
         assert_eq!(popped, Some(n.0));
         if self.queues.is_empty(rq.0) {
-            self.bitcache &= !(1 << rq.0);
+            self.bitcache.clear(rq.0);
         }
     }
 
     /// Removes thread with tid `n`.
     pub fn del(&mut self, n: ThreadId) {
         if let Some(empty_runqueue) = self.queues.del(n.0) {
-            self.bitcache &= !(1 << empty_runqueue);
+            self.bitcache.clear(empty_runqueue);
         }
     }
 
@@ -127,11 +155,7 @@ impl<const N_QUEUES: usize, const N_THREADS: usize> RunQueue<{ N_QUEUES }, { N_T
     /// Returns the tid that should run next and the runqueue it is in.
     #[must_use]
     pub fn get_next_with_rq(&self) -> Option<(ThreadId, RunqueueId)> {
-        let rq_ffs = ffs(self.bitcache);
-        if rq_ffs == 0 {
-            return None;
-        }
-        let rq = rq_ffs as u8 - 1;
+        let rq = self.bitcache.highest()?;
         self.queues
             .peek_head(rq)
             .map(|id| (ThreadId::new(id), RunqueueId::new(rq)))
@@ -154,14 +178,10 @@ impl<const N_QUEUES: usize, const N_THREADS: usize> RunQueue<{ N_QUEUES }, { N_T
     /// Pops the next runnable thread of
     /// the runqueue with the highest index.
     pub fn pop_next(&mut self) -> Option<ThreadId> {
-        let rq_ffs = ffs(self.bitcache);
-        if rq_ffs == 0 {
-            return None;
-        }
-        let rq = (rq_ffs - 1) as u8;
+        let rq = self.bitcache.highest()?;
         let head = self.queues.pop_head(rq).map(ThreadId::new);
         if self.queues.is_empty(rq) {
-            self.bitcache &= !(1 << rq);
+            self.bitcache.clear(rq);
         }
         head
     }
@@ -191,17 +211,74 @@ impl<const N_QUEUES: usize, const N_THREADS: usize> RunQueue<{ N_QUEUES }, { N_T
         &self,
         start: ThreadId,
         rq: RunqueueId,
-    ) -> RunQueueIter<'_, N_QUEUES, N_THREADS> {
+    ) -> RunQueueIter<'_, N_QUEUES, N_THREADS, N_WORDS> {
         RunQueueIter {
             prev: start.0,
             rq_head: self.queues.peek_head(rq.0),
             // Clear higher priority runqueues.
-            bitcache: self.bitcache % (1 << (rq.0 + 1)),
+            bitcache: self.bitcache.up_to(rq.0),
             queues: &self.queues,
         }
     }
 }
 
+/// Bitmap with one bit per runqueue, set if the runqueue is non-empty.
+#[derive(Clone, Copy, Debug)]
+struct Bitcache<const N_WORDS: usize> {
+    words: [usize; N_WORDS],
+}
+
+impl<const N_WORDS: usize> Default for Bitcache<N_WORDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N_WORDS: usize> Bitcache<N_WORDS> {
+    const fn new() -> Self {
+        Self {
+            words: [0; N_WORDS],
+        }
+    }
+
+    fn set(&mut self, rq: u8) {
+        self.words[rq as usize / USIZE_BITS] |= 1 << (rq as usize % USIZE_BITS);
+    }
+
+    fn clear(&mut self, rq: u8) {
+        self.words[rq as usize / USIZE_BITS] &= !(1 << (rq as usize % USIZE_BITS));
+    }
+
+    /// Returns the highest set bit, i.e., the highest priority non-empty runqueue.
+    fn highest(&self) -> Option<u8> {
+        let mut i = N_WORDS;
+        while i > 0 {
+            i -= 1;
+            let word = self.words[i];
+            if word != 0 {
+                return Some((i * USIZE_BITS + ffs(word) as usize - 1) as u8);
+            }
+        }
+        None
+    }
+
+    /// Returns a copy with only the bits of runqueues `0..=rq` set.
+    fn up_to(&self, rq: u8) -> Self {
+        let mut bitcache = *self;
+        let word = rq as usize / USIZE_BITS;
+        let bit = rq as usize % USIZE_BITS;
+        if bit + 1 < USIZE_BITS {
+            bitcache.words[word] &= (1 << (bit + 1)) - 1;
+        }
+        let mut i = word + 1;
+        while i < N_WORDS {
+            bitcache.words[i] = 0;
+            i += 1;
+        }
+        bitcache
+    }
+}
+
 #[inline]
 fn ffs(val: usize) -> u32 {
     USIZE_BITS as u32 - val.leading_zeros()
@@ -212,33 +289,33 @@ fn ffs(val: usize) -> u32 {
 /// It starts from the highest priority queue and continues switching to lower
 /// priority queues after circling through a queue once, until all queues
 /// that are included in this iterator have been iterated.
-pub struct RunQueueIter<'a, const N_QUEUES: usize, const N_THREADS: usize> {
+pub struct RunQueueIter<'a, const N_QUEUES: usize, const N_THREADS: usize, const N_WORDS: usize = 1>
+{
     queues: &'a clist::CList<N_QUEUES, N_THREADS>,
     // Predecessor in the circular runqueue list.
     prev: u8,
     // Head of the currently iterated runqueue.
     rq_head: Option<u8>,
     // Bitcache with the remaining queues that have to be iterated.
-    bitcache: usize,
+    bitcache: Bitcache<N_WORDS>,
 }
 
-impl<const N_QUEUES: usize, const N_THREADS: usize> Iterator
-    for RunQueueIter<'_, { N_QUEUES }, { N_THREADS }>
+impl<const N_QUEUES: usize, const N_THREADS: usize, const N_WORDS: usize> Iterator
+    for RunQueueIter<'_, { N_QUEUES }, { N_THREADS }, { N_WORDS }>
 {
     type Item = ThreadId;
     fn next(&mut self) -> Option<Self::Item> {
         let mut next = self.queues.peek_next(self.prev);
         if next == self.rq_head? {
             // Circled through whole queue, so switch to next one.
-            let rq = ffs(self.bitcache) as u8 - 1;
+            let rq = self.bitcache.highest()?;
             // Clear current runqueue from bitcache.
-            self.bitcache &= !(1 << rq);
+            self.bitcache.clear(rq);
             // Get head from remaining highest priority runqueue.
-            self.rq_head = if self.bitcache > 0 {
-                self.queues.peek_head(ffs(self.bitcache) as u8 - 1)
-            } else {
-                None
-            };
+            self.rq_head = self
+                .bitcache
+                .highest()
+                .and_then(|rq| self.queues.peek_head(rq));
             next = self.rq_head?;
         }
         self.prev = next;
@@ -266,8 +343,10 @@ mod clist {
 
     impl<const N_QUEUES: usize, const N_THREADS: usize> CList<N_QUEUES, N_THREADS> {
         pub const fn new() -> Self {
-            // TODO: ensure N fits in u8
-            // assert!(N<255); is not allowed in const because it could panic
+            const {
+                assert!(N_QUEUES <= super::MAX_QUEUES);
+                assert!(N_THREADS <= super::MAX_THREADS);
+            }
             CList {
                 tail: [Self::sentinel(); N_QUEUES],
                 next_idxs: [Self::sentinel(); N_THREADS],
@@ -0,0 +1,478 @@
+//! Provides threshold alarms and change notifications for sensor driver instances.
+//!
+//! A watch is registered on one [`ReadingChannel`] of a sensor driver instance with
+//! [`ALARMS.watch()`](Alarms::watch), along with a [`Condition`].
+//! When a reading fulfills the condition, an [`Alarm`] is published to the subscribers obtained
+//! with [`ALARMS.subscriber()`](Alarms::subscriber).
+//!
+//! Conditions are evaluated on every reading that is notified with [`notify_reading()`]:
+//!
+//! - readings obtained by a `Sampler`, with the `sampling` feature, and
+//! - readings reported by sensor drivers on their own, e.g., by push buttons on each edge, which
+//!   therefore do not require triggering measurements periodically.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use ariel_os::sensors::alarms::{ALARMS, Condition};
+//!
+//! // Alarm when the temperature reaches 30 °C (with a scaling of -2), and re-arm it once it falls
+//! // below 29 °C.
+//! let watch = ALARMS
+//!     .watch(thermometer, 0, Condition::Above { threshold: 3000, hysteresis: 100 })
+//!     .unwrap();
+//!
+//! let mut subscriber = ALARMS.subscriber().unwrap();
+//! loop {
+//!     let alarm = subscriber.next_message_pure().await;
+//!     if alarm.watch() == watch {
+//!         info!("Too hot: {}", alarm.sample().value());
+//!     }
+//! }
+//! ```
+//!
+//! [`ReadingChannel`]: crate::sensor::ReadingChannel
+//! [`notify_reading()`]: crate::sensor::notify_reading
+use core::cell::RefCell;
+
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    pubsub::{self, PubSubChannel, Subscriber},
+};
+
+use crate::{
+    Reading, Sensor,
+    sensor::{Sample, Samples},
+};
+
+/// Maximum number of watches.
+///
+/// This defaults to 8 and can be configured using the `CONFIG_SENSOR_ALARMS_WATCH_CAPACITY`
+/// environment variable.
+pub const WATCH_CAPACITY: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSOR_ALARMS_WATCH_CAPACITY",
+    8,
+    "maximum number of sensor alarm watches"
+);
+
+/// Maximum number of subscribers.
+///
+/// This defaults to 2 and can be configured using the `CONFIG_SENSOR_ALARMS_SUBSCRIBER_CAPACITY`
+/// environment variable.
+pub const SUBSCRIBER_CAPACITY: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSOR_ALARMS_SUBSCRIBER_CAPACITY",
+    2,
+    "maximum number of sensor alarm subscribers"
+);
+
+/// Number of alarms queued for each subscriber.
+const QUEUE_LEN: usize = 4;
+
+/// The global alarms instance.
+pub static ALARMS: Alarms = Alarms::new();
+
+/// Condition under which a watch triggers.
+///
+/// Values are compared to [`Sample::value()`], so they must be given with the same
+/// [scaling](crate::sensor::ReadingChannel::scaling).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Condition {
+    /// Triggers when the value rises to `threshold` or above.
+    ///
+    /// The watch then only triggers again after the value fell below
+    /// `threshold - hysteresis`.
+    Above {
+        /// Threshold value.
+        threshold: i32,
+        /// Hysteresis, avoiding repeated alarms while the value oscillates around the threshold.
+        hysteresis: u32,
+    },
+    /// Triggers when the value falls to `threshold` or below.
+    ///
+    /// The watch then only triggers again after the value rose above
+    /// `threshold + hysteresis`.
+    Below {
+        /// Threshold value.
+        threshold: i32,
+        /// Hysteresis, avoiding repeated alarms while the value oscillates around the threshold.
+        hysteresis: u32,
+    },
+    /// Triggers when the value differs by at least `delta` from the value it last triggered on.
+    ///
+    /// The first reading always triggers, reporting the initial value.
+    /// With a `delta` of `1`, this triggers on every change, e.g., on each edge of a push button.
+    Delta {
+        /// Minimum difference.
+        delta: u32,
+    },
+}
+
+/// Identifies a watch, see [`Alarms::watch()`].
+///
+/// Slots of watches that have been removed are reused.
+/// A [`WatchId`] additionally carries the generation of its slot, so that it never refers to
+/// a watch registered later in the same slot.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WatchId {
+    index: u8,
+    generation: u16,
+}
+
+/// Notification of a triggered watch.
+#[derive(Clone)]
+pub struct Alarm {
+    watch: WatchId,
+    sensor: &'static dyn Sensor,
+    sample: Sample,
+}
+
+impl Alarm {
+    /// Returns the watch that triggered.
+    #[must_use]
+    pub fn watch(&self) -> WatchId {
+        self.watch
+    }
+
+    /// Returns the sensor driver instance the watch is on.
+    #[must_use]
+    pub fn sensor(&self) -> &'static dyn Sensor {
+        self.sensor
+    }
+
+    /// Returns the sample the watch triggered on.
+    #[must_use]
+    pub fn sample(&self) -> Sample {
+        self.sample
+    }
+}
+
+/// Slot holding a [`Watch`].
+struct WatchSlot {
+    /// Generation of the most recently registered watch in this slot.
+    generation: u16,
+    watch: Option<Watch>,
+}
+
+impl WatchSlot {
+    const fn new() -> Self {
+        Self {
+            generation: 0,
+            watch: None,
+        }
+    }
+}
+
+/// A watch on a channel of a sensor driver instance.
+struct Watch {
+    sensor: &'static dyn Sensor,
+    channel: usize,
+    condition: Condition,
+    state: WatchState,
+}
+
+/// Evaluation state of a [`Watch`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum WatchState {
+    /// Threshold conditions: whether the watch may trigger.
+    Armed(bool),
+    /// Delta conditions: the value the watch last triggered on.
+    Reference(Option<i32>),
+}
+
+impl WatchState {
+    fn new(condition: Condition) -> Self {
+        match condition {
+            Condition::Above { .. } | Condition::Below { .. } => Self::Armed(true),
+            Condition::Delta { .. } => Self::Reference(None),
+        }
+    }
+
+    /// Updates the state with a new value, and returns whether the watch triggers.
+    fn update(&mut self, condition: Condition, sample_value: i32) -> bool {
+        let value = i64::from(sample_value);
+        match (condition, self) {
+            (
+                Condition::Above {
+                    threshold,
+                    hysteresis,
+                },
+                Self::Armed(armed),
+            ) => {
+                let threshold = i64::from(threshold);
+                if *armed && value >= threshold {
+                    *armed = false;
+                    true
+                } else {
+                    if value < threshold - i64::from(hysteresis) {
+                        *armed = true;
+                    }
+                    false
+                }
+            }
+            (
+                Condition::Below {
+                    threshold,
+                    hysteresis,
+                },
+                Self::Armed(armed),
+            ) => {
+                let threshold = i64::from(threshold);
+                if *armed && value <= threshold {
+                    *armed = false;
+                    true
+                } else {
+                    if value > threshold + i64::from(hysteresis) {
+                        *armed = true;
+                    }
+                    false
+                }
+            }
+            (Condition::Delta { delta }, Self::Reference(reference)) => {
+                let triggers = reference.is_none_or(|reference| {
+                    (value - i64::from(reference)).unsigned_abs() >= u64::from(delta)
+                });
+                if triggers {
+                    *reference = Some(sample_value);
+                }
+                triggers
+            }
+            // The state is always created for its condition.
+            _ => false,
+        }
+    }
+}
+
+/// Threshold alarms and change notifications.
+///
+/// This is exposed as [`ALARMS`].
+///
+/// See [the module level documentation](self) for more.
+pub struct Alarms {
+    watches: Mutex<CriticalSectionRawMutex, RefCell<[WatchSlot; WATCH_CAPACITY]>>,
+    alarms: PubSubChannel<CriticalSectionRawMutex, Alarm, QUEUE_LEN, SUBSCRIBER_CAPACITY, 1>,
+}
+
+impl Alarms {
+    // The constructor is private to make the alarms a singleton.
+    const fn new() -> Self {
+        Self {
+            watches: Mutex::new(RefCell::new([const { WatchSlot::new() }; WATCH_CAPACITY])),
+            alarms: PubSubChannel::new(),
+        }
+    }
+
+    /// Registers a watch on a [`ReadingChannel`] of `sensor`.
+    ///
+    /// `channel` is the index of the channel in [`Sensor::reading_channels()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AlarmsFullError`] if [`WATCH_CAPACITY`] watches are registered already.
+    ///
+    /// [`ReadingChannel`]: crate::sensor::ReadingChannel
+    pub fn watch(
+        &self,
+        sensor: &'static dyn Sensor,
+        channel: usize,
+        condition: Condition,
+    ) -> Result<WatchId, AlarmsFullError> {
+        self.watches.lock(|watches| {
+            let mut watches = watches.borrow_mut();
+            let (index, slot) = watches
+                .iter_mut()
+                .enumerate()
+                .find(|(_, slot)| slot.watch.is_none())
+                .ok_or(AlarmsFullError)?;
+            slot.generation = slot.generation.wrapping_add(1);
+            slot.watch = Some(Watch {
+                sensor,
+                channel,
+                condition,
+                state: WatchState::new(condition),
+            });
+            Ok(WatchId::new(index, slot.generation))
+        })
+    }
+
+    /// Removes a watch.
+    ///
+    /// Does nothing if the watch has been removed already.
+    /// Alarms of the watch that have already been published are still received.
+    pub fn unwatch(&self, watch: WatchId) {
+        self.watches.lock(|watches| {
+            if let Some(slot) = watches.borrow_mut().get_mut(usize::from(watch.index)) {
+                if slot.generation == watch.generation {
+                    slot.watch = None;
+                }
+            }
+        });
+    }
+
+    /// Returns a subscriber receiving every [`Alarm`].
+    ///
+    /// Subscribers that do not keep up miss the oldest alarms, see
+    /// [`WaitResult::Lagged`](pubsub::WaitResult::Lagged).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`pubsub::Error::MaximumSubscribersReached`] if [`SUBSCRIBER_CAPACITY`]
+    /// subscribers exist already.
+    pub fn subscriber(
+        &self,
+    ) -> Result<
+        Subscriber<'_, CriticalSectionRawMutex, Alarm, QUEUE_LEN, SUBSCRIBER_CAPACITY, 1>,
+        pubsub::Error,
+    > {
+        self.alarms.subscriber()
+    }
+
+    /// Evaluates the watches on `sensor` with a new reading, and publishes the resulting
+    /// alarms.
+    pub(crate) fn process(&self, sensor: &dyn Sensor, samples: Samples) {
+        let publisher = self.alarms.immediate_publisher();
+        self.watches.lock(|watches| {
+            let mut watches = watches.borrow_mut();
+            for (index, slot) in watches.iter_mut().enumerate() {
+                let Some(watch) = &mut slot.watch else {
+                    continue;
+                };
+                if !core::ptr::addr_eq(watch.sensor, sensor) {
+                    continue;
+                }
+                let Some(sample) = samples.samples().nth(watch.channel) else {
+                    continue;
+                };
+                if watch.state.update(watch.condition, sample.value()) {
+                    publisher.publish_immediate(Alarm {
+                        watch: WatchId::new(index, slot.generation),
+                        sensor: watch.sensor,
+                        sample,
+                    });
+                }
+            }
+        });
+    }
+}
+
+impl WatchId {
+    fn new(index: usize, generation: u16) -> Self {
+        Self {
+            // NOTE(no-panic): the capacity is checked to fit into a `u8`.
+            index: u8::try_from(index).unwrap_or(u8::MAX),
+            generation,
+        }
+    }
+}
+
+const _: () = assert!(
+    WATCH_CAPACITY <= u8::MAX as usize,
+    "`CONFIG_SENSOR_ALARMS_WATCH_CAPACITY` must fit into a `u8`"
+);
+
+/// The error type returned when no more watches can be registered, see [`Alarms::watch()`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AlarmsFullError;
+
+impl core::fmt::Display for AlarmsFullError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "no more sensor alarm watches can be registered")
+    }
+}
+
+impl core::error::Error for AlarmsFullError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Label, MeasurementUnit,
+        sensor::{Accuracy, notify_reading},
+        test_utils::TestSensor,
+    };
+
+    use super::*;
+
+    /// Push button reporting a reading on each edge on its own.
+    static BUTTON: TestSensor =
+        TestSensor::new(Some("button"), Label::Main, 0, MeasurementUnit::Bool);
+
+    /// Edge interrupt handler of [`BUTTON`].
+    fn on_edge(pressed: bool) {
+        let sample = Sample::new(i32::from(pressed), Accuracy::Unknown);
+        notify_reading(&BUTTON, Samples::V1([sample]));
+    }
+
+    #[test]
+    fn push_button_edges() {
+        let mut subscriber = ALARMS.subscriber().unwrap();
+        let edges = Condition::Delta { delta: 1 };
+        let removed = ALARMS.watch(&BUTTON, 0, edges).unwrap();
+        ALARMS.unwatch(removed);
+        let watch = ALARMS.watch(&BUTTON, 0, edges).unwrap();
+        // The slot is reused, but the identifier of the removed watch does not refer to the new
+        // one.
+        assert_ne!(watch, removed);
+        ALARMS.unwatch(removed);
+
+        for pressed in [false, true, true, false] {
+            on_edge(pressed);
+        }
+        for expected in [0, 1, 0] {
+            let alarm = subscriber.try_next_message_pure().unwrap();
+            assert_eq!(alarm.watch(), watch);
+            assert_eq!(alarm.sample().value(), expected);
+        }
+        assert!(subscriber.try_next_message_pure().is_none());
+
+        ALARMS.unwatch(watch);
+        on_edge(true);
+        assert!(subscriber.try_next_message_pure().is_none());
+    }
+
+    fn triggers(condition: Condition, values: &[i32]) -> [bool; 8] {
+        let mut state = WatchState::new(condition);
+        let mut triggers = [false; 8];
+        for (triggered, value) in triggers.iter_mut().zip(values) {
+            *triggered = state.update(condition, *value);
+        }
+        triggers
+    }
+
+    #[test]
+    fn threshold_with_hysteresis() {
+        let above = Condition::Above {
+            threshold: 100,
+            hysteresis: 10,
+        };
+        assert_eq!(
+            triggers(above, &[50, 100, 120, 95, 100, 89, 101, 0]),
+            [false, true, false, false, false, false, true, false],
+        );
+
+        let below = Condition::Below {
+            threshold: -100,
+            hysteresis: 10,
+        };
+        assert_eq!(
+            triggers(below, &[0, -100, -120, -95, -100, -89, -101, 0]),
+            [false, true, false, false, false, false, true, false],
+        );
+    }
+
+    #[test]
+    fn delta() {
+        let delta = Condition::Delta { delta: 5 };
+        assert_eq!(
+            triggers(delta, &[10, 14, 6, 5, 16, 16, 11, 12]),
+            [true, false, false, true, true, false, true, false],
+        );
+
+        let edges = Condition::Delta { delta: 1 };
+        assert_eq!(
+            triggers(edges, &[0, 1, 1, 0, 0, 1, 0, 0]),
+            [true, true, false, true, false, true, true, false],
+        );
+    }
+}
@@ -0,0 +1,496 @@
+//! Provides a CoAP [`Handler`] exposing registered sensor driver instances as resources.
+//!
+//! Each sensor driver instance is exposed at `<prefix>/<category>/<instance>`, where:
+//!
+//! - `<category>` is the first [`Category`] of the sensor driver (e.g., `temperature`,
+//!   `relative-humidity-temperature`), and
+//! - `<instance>` is the [`Sensor::label()`] of the sensor driver instance, falling back to its
+//!   [`Sensor::part_number()`], like the SenML base name (see [`senml`]).
+//!   Characters not allowed in SenML names and `/` are replaced with `_`, and names longer
+//!   than 40 characters are truncated.
+//!   If multiple sensor driver instances of a category end up with the same name, the ones
+//!   registered later are suffixed with `~2`, `~3`, etc.; as `~` is replaced in names, the
+//!   suffixed paths cannot collide with the name of another sensor driver instance.
+//!
+//! The paths of statically registered sensor driver instances are stable.
+//! The paths of sensor driver instances registered at runtime are not: when a sensor driver
+//! instance is unregistered, the suffixes of the ones with the same name registered after it
+//! shift down.
+//!
+//! The resources are reported in `/.well-known/core` with the `core.s` (sensor) interface, the
+//! category as resource type, and the SenML CBOR content format, which is the default.
+//!
+//! GET requests return the latest reading obtained by a [`Sampler`] as a SenML pack (see
+//! [`senml`]), in CBOR by default or in JSON if requested through the Accept
+//! option.
+//! The handler does not trigger measurements itself, as handlers cannot wait; sensor driver
+//! instances that are not sampled or have not been sampled yet respond with 5.03 (Service
+//! Unavailable).
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use ariel_os::sensors::{REGISTRY, coap::SensorsHandler, sampling::Sampler};
+//! use coap_handler_implementations::{HandlerBuilder, SimpleRendered};
+//!
+//! static SAMPLER: Sampler<4, 1, 1> = Sampler::new();
+//!
+//! #[ariel_os::task(autostart)]
+//! async fn coap_run() {
+//!     let handler = SensorsHandler::new(&SAMPLER, &["sensors"])
+//!         .at(&["hello"], SimpleRendered("Hello from Ariel OS"));
+//!
+//!     ariel_os::coap::coap_run(handler).await;
+//! }
+//! ```
+//!
+//! [`Sampler`]: crate::sampling::Sampler
+use coap_handler::{Attribute, Handler, Record, Reporting};
+use coap_message::{
+    Code as _, MessageOption as _, MinimalWritableMessage, MutableWritableMessage,
+    OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use coap_numbers::{code, option};
+
+use core::fmt::Write as _;
+
+use crate::{Category, REGISTRY, Sensor, registry::SensorsIter, sampling::Sampler, senml};
+
+/// Content format of `application/senml+json`.
+const CONTENT_FORMAT_SENML_JSON: u16 = 110;
+/// Content format of `application/senml+cbor`.
+const CONTENT_FORMAT_SENML_CBOR: u16 = 112;
+
+/// Size of the buffer the SenML pack of a reading is encoded into.
+const PAYLOAD_SIZE: usize = 512;
+
+/// Maximum number of characters of the name in the path segment of a sensor driver instance.
+const INSTANCE_NAME_LEN: usize = 40;
+/// Size of the path segment of a sensor driver instance, with room for a `~` and the decimal
+/// digits of a `usize` after the name.
+const INSTANCE_SEGMENT_SIZE: usize = INSTANCE_NAME_LEN + 21;
+
+/// CoAP [`Handler`] exposing registered sensor driver instances as resources.
+///
+/// Requests that are not for a sensor resource are responded to with 4.04 (Not Found), so
+/// other resources need to be added in front of this handler, e.g., using
+/// `coap_handler_implementations::HandlerBuilder::at()`.
+///
+/// See [the module level documentation](self) for more.
+pub struct SensorsHandler<const SENSORS: usize, const HISTORY: usize, const SUBSCRIBERS: usize> {
+    sampler: &'static Sampler<SENSORS, HISTORY, SUBSCRIBERS>,
+    prefix: &'static [&'static str],
+}
+
+impl<const SENSORS: usize, const HISTORY: usize, const SUBSCRIBERS: usize>
+    SensorsHandler<SENSORS, HISTORY, SUBSCRIBERS>
+{
+    /// Creates a new handler serving readings from `sampler`, with resources below `prefix`.
+    #[must_use]
+    pub fn new(
+        sampler: &'static Sampler<SENSORS, HISTORY, SUBSCRIBERS>,
+        prefix: &'static [&'static str],
+    ) -> Self {
+        Self { sampler, prefix }
+    }
+}
+
+/// Request data of [`SensorsHandler`].
+pub struct RequestData(Request);
+
+enum Request {
+    Reading {
+        sensor: &'static dyn Sensor,
+        format: Format,
+    },
+    /// Responds with an empty response with this code.
+    Status(u8),
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Cbor,
+    Json,
+}
+
+impl Format {
+    fn content_format(self) -> u16 {
+        match self {
+            Self::Cbor => CONTENT_FORMAT_SENML_CBOR,
+            Self::Json => CONTENT_FORMAT_SENML_JSON,
+        }
+    }
+}
+
+/// Position in the request path.
+enum PathState {
+    /// Number of segments of the prefix matched so far.
+    Prefix(usize),
+    /// Matched the prefix and this category segment.
+    Category(Category),
+    /// Matched the whole path of this sensor driver instance.
+    Sensor(&'static dyn Sensor),
+    NotFound,
+}
+
+impl<const SENSORS: usize, const HISTORY: usize, const SUBSCRIBERS: usize> Handler
+    for SensorsHandler<SENSORS, HISTORY, SUBSCRIBERS>
+{
+    type RequestData = RequestData;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = M::UnionError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let mut path = PathState::Prefix(0);
+        let mut accept = None;
+        request
+            .options()
+            .filter(|o| match o.number() {
+                option::URI_PATH => {
+                    path = next_path_state(&path, self.prefix, o.value());
+                    false
+                }
+                option::ACCEPT => {
+                    accept = Some(uint_value(o.value()));
+                    false
+                }
+                _ => true,
+            })
+            .ignore_elective_others()?;
+
+        let PathState::Sensor(sensor) = path else {
+            return Ok(RequestData(Request::Status(code::NOT_FOUND)));
+        };
+        if code::GET != request.code().into() {
+            return Ok(RequestData(Request::Status(code::METHOD_NOT_ALLOWED)));
+        }
+        let format = match accept {
+            None | Some(Some(CONTENT_FORMAT_SENML_CBOR)) => Format::Cbor,
+            Some(Some(CONTENT_FORMAT_SENML_JSON)) => Format::Json,
+            Some(_) => return Ok(RequestData(Request::Status(code::NOT_ACCEPTABLE))),
+        };
+        Ok(RequestData(Request::Reading { sensor, format }))
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        PAYLOAD_SIZE
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        let (sensor, format) = match request.0 {
+            Request::Reading { sensor, format } => (sensor, format),
+            Request::Status(status) => {
+                response.set_code(M::Code::new(status)?);
+                return Ok(());
+            }
+        };
+
+        let Some((_, samples)) = self.sampler.latest(sensor) else {
+            response.set_code(M::Code::new(code::SERVICE_UNAVAILABLE)?);
+            return Ok(());
+        };
+        let mut payload = [0; PAYLOAD_SIZE];
+        let readings = [(sensor, samples)];
+        let encoded = match format {
+            Format::Cbor => senml::encode_cbor("", &readings, &mut payload),
+            Format::Json => senml::encode_json("", &readings, &mut payload),
+        };
+        let Some(payload) = encoded.ok().and_then(|len| payload.get(..len)) else {
+            response.set_code(M::Code::new(code::INTERNAL_SERVER_ERROR)?);
+            return Ok(());
+        };
+
+        response.set_code(M::Code::new(code::CONTENT)?);
+        // Unsigned integer options are encoded without leading zero bytes.
+        let content_format = format.content_format().to_be_bytes();
+        let leading_zeros = content_format.iter().take_while(|b| **b == 0).count();
+        response.add_option(
+            M::OptionNumber::new(option::CONTENT_FORMAT)?,
+            content_format.get(leading_zeros..).unwrap_or_default(),
+        )?;
+        response.set_payload(payload)?;
+        Ok(())
+    }
+}
+
+/// Decodes the value of an unsigned integer option, if it fits into a `u16`.
+fn uint_value(value: &[u8]) -> Option<u16> {
+    (value.len() <= 2).then(|| {
+        value
+            .iter()
+            .fold(0, |uint, byte| (uint << 8) | u16::from(*byte))
+    })
+}
+
+/// Returns the position in the request path after the Uri-Path option `segment`.
+fn next_path_state(
+    state: &PathState,
+    prefix: &'static [&'static str],
+    segment: &[u8],
+) -> PathState {
+    match *state {
+        PathState::Prefix(matched) => match prefix.get(matched) {
+            Some(expected) if expected.as_bytes() == segment => PathState::Prefix(matched + 1),
+            Some(_) => PathState::NotFound,
+            None => CATEGORY_SEGMENTS
+                .iter()
+                .find(|(_, name)| name.as_bytes() == segment)
+                .map_or(PathState::NotFound, |(category, _)| {
+                    PathState::Category(*category)
+                }),
+        },
+        PathState::Category(category) => REGISTRY
+            .sensors()
+            .filter(|sensor| sensor.categories().first() == Some(&category))
+            .find(|sensor| instance_segment(*sensor).as_ref().as_bytes() == segment)
+            .map_or(PathState::NotFound, PathState::Sensor),
+        PathState::Sensor(_) | PathState::NotFound => PathState::NotFound,
+    }
+}
+
+/// Path segment of each [`Category`].
+const CATEGORY_SEGMENTS: &[(Category, &str)] = &[
+    (Category::Accelerometer, "accelerometer"),
+    (
+        Category::AccelerometerTemperature,
+        "accelerometer-temperature",
+    ),
+    (
+        Category::AccelerometerMagnetometerTemperature,
+        "accelerometer-magnetometer-temperature",
+    ),
+    (Category::Ammeter, "ammeter"),
+    (Category::Co2Gas, "co2-gas"),
+    (Category::Color, "color"),
+    (Category::Gyroscope, "gyroscope"),
+    (Category::RelativeHumidity, "relative-humidity"),
+    (
+        Category::RelativeHumidityTemperature,
+        "relative-humidity-temperature",
+    ),
+    (Category::Light, "light"),
+    (Category::Magnetometer, "magnetometer"),
+    (Category::Ph, "ph"),
+    (Category::Pressure, "pressure"),
+    (Category::PushButton, "push-button"),
+    (Category::Temperature, "temperature"),
+    (Category::Tvoc, "tvoc"),
+    (Category::Voltage, "voltage"),
+];
+
+/// Returns the name of a sensor driver instance, before replacing characters.
+fn instance_name(sensor: &dyn Sensor) -> &'static str {
+    sensor
+        .label()
+        .or(sensor.part_number())
+        .unwrap_or(senml::FALLBACK_NAME)
+}
+
+/// Returns the path segment of a sensor driver instance.
+///
+/// Instances of the same category whose names are the same are numbered in registration order.
+fn instance_segment(sensor: &dyn Sensor) -> PathElement {
+    let name = instance_name(sensor);
+    let segment = PathElement::instance(name, 1);
+    let category = sensor.categories().first();
+    let occurrence = 1 + REGISTRY
+        .sensors()
+        .take_while(|other| !core::ptr::addr_eq(*other, sensor))
+        .filter(|other| {
+            other.categories().first() == category
+                && PathElement::instance(instance_name(*other), 1).as_ref() == segment.as_ref()
+        })
+        .count();
+    if occurrence == 1 {
+        segment
+    } else {
+        PathElement::instance(name, occurrence)
+    }
+}
+
+/// Returns the path segment of the first category of `sensor`.
+fn category_segment(sensor: &dyn Sensor) -> Option<&'static str> {
+    let category = sensor.categories().first()?;
+    CATEGORY_SEGMENTS
+        .iter()
+        .find(|(c, _)| c == category)
+        .map(|(_, name)| *name)
+}
+
+impl<const SENSORS: usize, const HISTORY: usize, const SUBSCRIBERS: usize> Reporting
+    for SensorsHandler<SENSORS, HISTORY, SUBSCRIBERS>
+{
+    type Record<'res>
+        = SensorRecord
+    where
+        Self: 'res;
+    type Reporter<'res>
+        = SensorRecords
+    where
+        Self: 'res;
+
+    fn report(&self) -> Self::Reporter<'_> {
+        SensorRecords {
+            prefix: self.prefix,
+            sensors: REGISTRY.sensors_iter(),
+        }
+    }
+}
+
+/// Iterator over the resources of a [`SensorsHandler`].
+pub struct SensorRecords {
+    prefix: &'static [&'static str],
+    /// Remaining sensor driver instances of the [`REGISTRY`].
+    sensors: SensorsIter,
+}
+
+impl Iterator for SensorRecords {
+    type Item = SensorRecord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Sensor drivers without any category are not exposed.
+        self.sensors.find_map(|sensor| {
+            Some(SensorRecord {
+                prefix: self.prefix,
+                category: category_segment(sensor)?,
+                instance: instance_segment(sensor),
+            })
+        })
+    }
+}
+
+/// Resource of a sensor driver instance, as reported by [`SensorsHandler`].
+pub struct SensorRecord {
+    prefix: &'static [&'static str],
+    category: &'static str,
+    instance: PathElement,
+}
+
+impl Record for SensorRecord {
+    type PathElement = PathElement;
+    type PathElements = core::iter::Chain<
+        core::iter::Map<core::slice::Iter<'static, &'static str>, fn(&&'static str) -> PathElement>,
+        core::array::IntoIter<PathElement, 2>,
+    >;
+    type Attributes = core::array::IntoIter<Attribute, 3>;
+
+    fn path(&self) -> Self::PathElements {
+        let prefix: fn(&&'static str) -> PathElement =
+            |segment| PathElement(Segment::Static(segment));
+        self.prefix.iter().map(prefix).chain([
+            PathElement(Segment::Static(self.category)),
+            self.instance.clone(),
+        ])
+    }
+
+    fn rel(&self) -> Option<&str> {
+        None
+    }
+
+    fn attributes(&self) -> Self::Attributes {
+        [
+            Attribute::Interface("core.s"),
+            Attribute::ResourceType(self.category),
+            Attribute::Ct(CONTENT_FORMAT_SENML_CBOR),
+        ]
+        .into_iter()
+    }
+}
+
+/// Path segment of a [`SensorRecord`].
+#[derive(Clone)]
+pub struct PathElement(Segment);
+
+#[derive(Clone)]
+enum Segment {
+    Static(&'static str),
+    /// Path segment of a sensor driver instance, see [`instance_segment()`].
+    Instance {
+        bytes: [u8; INSTANCE_SEGMENT_SIZE],
+        len: usize,
+    },
+}
+
+impl PathElement {
+    /// Returns the path segment of the `occurrence`-th sensor driver instance named `name` in
+    /// its category.
+    fn instance(name: &str, occurrence: usize) -> Self {
+        let mut segment = Self(Segment::Instance {
+            bytes: [0; INSTANCE_SEGMENT_SIZE],
+            len: 0,
+        });
+        for c in name.chars().take(INSTANCE_NAME_LEN) {
+            segment.push(match senml::name_char(c) {
+                // Path segments are separated by `/` in link-format.
+                b'/' => b'_',
+                c => c,
+            });
+        }
+        if occurrence > 1 {
+            // `~` is not among the characters of names, see `senml::name_char()`.
+            let _ = write!(segment, "~{occurrence}");
+        }
+        segment
+    }
+
+    /// Appends a byte to an instance segment, if there is room left.
+    fn push(&mut self, byte: u8) {
+        if let Segment::Instance { bytes, len } = &mut self.0 {
+            if let Some(slot) = bytes.get_mut(*len) {
+                *slot = byte;
+                *len += 1;
+            }
+        }
+    }
+}
+
+impl core::fmt::Write for PathElement {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        s.bytes().for_each(|byte| self.push(byte));
+        Ok(())
+    }
+}
+
+impl AsRef<str> for PathElement {
+    fn as_ref(&self) -> &str {
+        match &self.0 {
+            Segment::Static(segment) => segment,
+            // Instance segments only consist of ASCII characters.
+            Segment::Instance { bytes, len } => bytes
+                .get(..*len)
+                .and_then(|bytes| core::str::from_utf8(bytes).ok())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_segments() {
+        for (name, occurrence, expected) in [
+            ("indoor", 1, "indoor"),
+            ("indoor temp/1", 1, "indoor_temp_1"),
+            ("DS18B20", 2, "DS18B20~2"),
+            ("indoor~2", 1, "indoor_2"),
+            ("é", 1, "_"),
+        ] {
+            assert_eq!(PathElement::instance(name, occurrence).as_ref(), expected);
+        }
+
+        let long = core::str::from_utf8(&[b'x'; 2 * INSTANCE_NAME_LEN]).unwrap();
+        let segment = PathElement::instance(long, usize::MAX);
+        assert!(segment.as_ref().ends_with("x~18446744073709551615"));
+        assert_eq!(segment.as_ref().len(), INSTANCE_SEGMENT_SIZE);
+    }
+}
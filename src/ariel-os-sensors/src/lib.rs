@@ -20,6 +20,8 @@
 //!
 //! Registered sensor driver instances can be accessed using
 //! [`REGISTRY::sensors()`](registry::Registry::sensors).
+//! Besides statically registered ones, sensor driver instances can be registered at runtime
+//! using [`REGISTRY::register()`](registry::Registry::register).
 //! Sensor drivers implement the [`Sensor`] trait, which allows to trigger measurements and obtain
 //! the resulting readings.
 //!
@@ -43,6 +45,23 @@
 //! [`ReadingChannel`](sensor::ReadingChannel), for each [`Sample`](sample::Sample) returned.
 //! See [`Sample`](sample::Sample) for more details.
 //!
+//! # Periodic sampling
+//!
+//! With the `sampling` feature, the `sampling` module provides a service that takes care of
+//! periodically triggering measurements and obtaining readings, and keeps a history of them.
+//!
+//! # Alarms
+//!
+//! With the `alarms` feature, the `alarms` module allows to watch reading channels for thresholds
+//! being crossed or values changing, and to be notified when that happens.
+//!
+//! # Encoding readings
+//!
+//! With the `senml` feature, the `senml` module provides SenML encoding of readings, in both
+//! CBOR and JSON.
+//! With the `coap` feature, the `coap` module provides a CoAP handler exposing sensor driver
+//! instances as resources returning such encoded readings.
+//!
 //! # For implementors
 //!
 //! Sensor drivers must implement the [`Sensor`] trait.
@@ -53,12 +72,22 @@
 #![deny(clippy::pedantic)]
 #![deny(missing_docs)]
 
+#[cfg(feature = "alarms")]
+pub mod alarms;
 mod category;
+#[cfg(feature = "coap")]
+pub mod coap;
 mod label;
 mod measurement_unit;
 pub mod registry;
 mod sample;
+#[cfg(feature = "sampling")]
+pub mod sampling;
+#[cfg(feature = "senml")]
+pub mod senml;
 pub mod sensor;
+#[cfg(test)]
+mod test_utils;
 
 pub use category::Category;
 pub use label::Label;
@@ -1,8 +1,25 @@
 //! Provides a sensor driver instance registry, allowing to register sensor driver instances and
 //! access them in a centralized location.
+//!
+//! Sensor driver instances are registered either statically in [`SENSOR_REFS`], or at runtime
+//! using [`Registry::register()`], e.g., after detecting which sensor devices are attached.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
 
 use crate::Sensor;
 
+/// Maximum number of sensor driver instances registered at runtime.
+///
+/// This defaults to 4 and can be configured using the `CONFIG_SENSOR_REGISTRY_DYNAMIC_CAPACITY`
+/// environment variable.
+pub const DYNAMIC_CAPACITY: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSOR_REGISTRY_DYNAMIC_CAPACITY",
+    4,
+    "maximum number of sensor driver instances registered at runtime"
+);
+
 /// Stores references to registered sensor driver instances.
 ///
 /// To register a sensor driver instance, insert a `&'static` into this [distributed
@@ -22,22 +39,197 @@ pub static REGISTRY: Registry = Registry::new();
 ///
 /// This is exposed as [`REGISTRY`].
 pub struct Registry {
-    // Prevents instantiation from outside this module.
-    _private: (),
+    /// Sensor driver instances registered at runtime, in registration order.
+    dynamic: Mutex<CriticalSectionRawMutex, RefCell<DynamicSensors>>,
+}
+
+/// Sensor driver instances registered at runtime.
+///
+/// The first `len` entries are registered; they are kept contiguous.
+#[derive(Clone, Copy)]
+struct DynamicSensors {
+    sensors: [Option<&'static dyn Sensor>; DYNAMIC_CAPACITY],
+    len: usize,
 }
 
 impl Registry {
     // The constructor is private to make the registry a singleton.
     const fn new() -> Self {
-        Self { _private: () }
+        Self {
+            dynamic: Mutex::new(RefCell::new(DynamicSensors {
+                sensors: [None; DYNAMIC_CAPACITY],
+                len: 0,
+            })),
+        }
     }
 
     /// Returns an iterator over registered sensor driver instances.
+    ///
+    /// Statically registered sensor driver instances come first, followed by the ones registered
+    /// at runtime, in registration order.
+    /// Sensor driver instances registered or unregistered while iterating are not taken into
+    /// account.
     #[must_use]
     pub fn sensors(&self) -> impl ExactSizeIterator<Item = &'static dyn Sensor> {
-        // Returning an iterator instead of the distributed slice directly would allow us to chain
-        // another source of sensor driver instances in the future, if we decided to support
-        // dynamically-allocated sensor driver instances.
-        SENSOR_REFS.iter().copied()
+        self.sensors_iter()
+    }
+
+    /// Returns the iterator of [`Registry::sensors()`], as a type that can be stored.
+    pub(crate) fn sensors_iter(&self) -> SensorsIter {
+        SensorsIter {
+            statics: SENSOR_REFS.iter(),
+            dynamic: self.dynamic.lock(|dynamic| *dynamic.borrow()),
+            next_dynamic: 0,
+        }
+    }
+
+    /// Registers a sensor driver instance at runtime.
+    ///
+    /// Does nothing if the sensor driver instance is registered already.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RegistryFullError`] if [`DYNAMIC_CAPACITY`] sensor driver instances are
+    /// registered at runtime already.
+    pub fn register(&self, sensor: &'static dyn Sensor) -> Result<(), RegistryFullError> {
+        if SENSOR_REFS.iter().any(|s| core::ptr::addr_eq(*s, sensor)) {
+            return Ok(());
+        }
+        self.dynamic.lock(|dynamic| {
+            let mut dynamic = dynamic.borrow_mut();
+            if dynamic.position(sensor).is_some() {
+                return Ok(());
+            }
+            let len = dynamic.len;
+            let slot = dynamic.sensors.get_mut(len).ok_or(RegistryFullError)?;
+            *slot = Some(sensor);
+            dynamic.len += 1;
+            Ok(())
+        })
+    }
+
+    /// Unregisters a sensor driver instance registered with [`Registry::register()`].
+    ///
+    /// Returns whether the sensor driver instance was registered at runtime; statically
+    /// registered sensor driver instances cannot be unregistered.
+    ///
+    /// Users that obtained the sensor driver instance from the registry before, e.g., a
+    /// [`Sampler`](crate::sampling::Sampler), are not notified.
+    pub fn unregister(&self, sensor: &'static dyn Sensor) -> bool {
+        self.dynamic.lock(|dynamic| {
+            let mut dynamic = dynamic.borrow_mut();
+            let Some(index) = dynamic.position(sensor) else {
+                return false;
+            };
+            let len = dynamic.len;
+            // Keep the registered sensor driver instances contiguous and in order.
+            if let Some(registered) = dynamic.sensors.get_mut(index..len) {
+                registered.rotate_left(1);
+            }
+            if let Some(last) = dynamic.sensors.get_mut(len - 1) {
+                *last = None;
+            }
+            dynamic.len -= 1;
+            true
+        })
+    }
+}
+
+impl DynamicSensors {
+    fn position(&self, sensor: &'static dyn Sensor) -> Option<usize> {
+        self.sensors
+            .iter()
+            .position(|s| s.is_some_and(|s| core::ptr::addr_eq(s, sensor)))
+    }
+}
+
+/// Iterator over registered sensor driver instances, see [`Registry::sensors()`].
+pub(crate) struct SensorsIter {
+    statics: core::slice::Iter<'static, &'static dyn Sensor>,
+    /// Snapshot of the sensor driver instances registered at runtime.
+    dynamic: DynamicSensors,
+    /// Index of the next sensor driver instance in `dynamic`.
+    next_dynamic: usize,
+}
+
+impl Iterator for SensorsIter {
+    type Item = &'static dyn Sensor;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(sensor) = self.statics.next() {
+            return Some(*sensor);
+        }
+        let sensor = (*self.dynamic.sensors.get(self.next_dynamic)?)?;
+        self.next_dynamic += 1;
+        Some(sensor)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.statics.len() + self.dynamic.len - self.next_dynamic;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for SensorsIter {}
+
+/// The error type returned when no more sensor driver instances can be registered at runtime,
+/// see [`Registry::register()`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegistryFullError;
+
+impl core::fmt::Display for RegistryFullError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "no more sensor driver instances can be registered")
+    }
+}
+
+impl core::error::Error for RegistryFullError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Label, MeasurementUnit, test_utils::TestSensor};
+
+    use super::*;
+
+    static SENSORS: [TestSensor; DYNAMIC_CAPACITY + 1] =
+        [const { TestSensor::new(None, Label::Main, 0, MeasurementUnit::Volt) };
+            DYNAMIC_CAPACITY + 1];
+
+    fn is_registered_in_order(registry: &Registry, expected: &[&'static dyn Sensor]) -> bool {
+        let sensors = registry.sensors();
+        sensors.len() == expected.len()
+            && sensors
+                .zip(expected)
+                .all(|(sensor, expected)| core::ptr::addr_eq(sensor, *expected))
+    }
+
+    #[test]
+    fn register_and_unregister() {
+        let registry = Registry::new();
+        let [first, second, rest @ ..] = &SENSORS;
+        let (first, second): (&'static dyn Sensor, &'static dyn Sensor) = (first, second);
+
+        registry.register(first).unwrap();
+        registry.register(second).unwrap();
+        // Registering again does nothing.
+        registry.register(first).unwrap();
+        assert!(is_registered_in_order(&registry, &[first, second]));
+
+        assert!(registry.unregister(first));
+        assert!(!registry.unregister(first));
+        assert!(is_registered_in_order(&registry, &[second]));
+
+        registry.register(first).unwrap();
+        assert!(is_registered_in_order(&registry, &[second, first]));
+
+        for sensor in rest {
+            if registry.sensors().len() < DYNAMIC_CAPACITY {
+                registry.register(sensor).unwrap();
+            } else {
+                assert!(registry.register(sensor).is_err());
+            }
+        }
+        assert_eq!(registry.sensors().len(), DYNAMIC_CAPACITY);
     }
 }
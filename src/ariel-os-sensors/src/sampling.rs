@@ -0,0 +1,453 @@
+//! Provides a [`Sampler`] periodically obtaining readings from sensor driver instances.
+//!
+//! The sampler triggers measurements of the sensor driver instances it has been configured for
+//! with [`Sampler::set_interval()`], each at its own interval, and waits for the readings.
+//! Measurements that are due at the same time are triggered together, so that they happen
+//! concurrently.
+//!
+//! Readings are:
+//!
+//! - recorded in an in-RAM history per sensor driver instance, which can be accessed per
+//!   [`ReadingChannel`] with [`Sampler::history()`], and
+//! - published as [`SamplingEvent`]s to subscribers obtained with [`Sampler::subscriber()`],
+//!   along with errors.
+//!
+//! Sensor drivers that are not enabled (e.g., because they are sleeping) are skipped until they
+//! are enabled again.
+//! A reading that is not obtained within the interval of its sensor driver instance is given
+//! up on and published as [`SamplingError::Timeout`], so that a sensor driver that never
+//! provides its reading does not hold back the others.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use ariel_os::{sensors::{REGISTRY, sampling::Sampler}, time::Duration};
+//!
+//! // Up to 4 sensor driver instances, 8 readings of history each, 2 subscribers.
+//! static SAMPLER: Sampler<4, 8, 2> = Sampler::new();
+//!
+//! #[ariel_os::task(autostart)]
+//! async fn sampling() {
+//!     for sensor in REGISTRY.sensors() {
+//!         SAMPLER.set_interval(sensor, Some(Duration::from_secs(10))).unwrap();
+//!     }
+//!     SAMPLER.run().await
+//! }
+//! ```
+//!
+//! [`ReadingChannel`]: crate::sensor::ReadingChannel
+use core::cell::RefCell;
+
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    pubsub::{self, PubSubChannel, Subscriber},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant, TimeoutError, with_deadline};
+
+use crate::{
+    Reading, Sensor,
+    sensor::{ReadingError, Sample, Samples, State, TriggerMeasurementError},
+};
+
+/// Periodically obtains readings from up to `SENSORS` sensor driver instances, keeps the
+/// `HISTORY` most recent readings of each, and publishes them to up to `SUBSCRIBERS`
+/// subscribers.
+///
+/// See [the module level documentation](self) for more.
+pub struct Sampler<const SENSORS: usize, const HISTORY: usize, const SUBSCRIBERS: usize> {
+    entries: Mutex<CriticalSectionRawMutex, RefCell<[Option<Entry<HISTORY>>; SENSORS]>>,
+    events: PubSubChannel<CriticalSectionRawMutex, SamplingEvent, SENSORS, SUBSCRIBERS, 1>,
+    /// Signaled when the configuration changed, so that [`Sampler::run()`] reschedules.
+    changed: Signal<CriticalSectionRawMutex, ()>,
+}
+
+/// A sensor driver instance sampled by a [`Sampler`].
+struct Entry<const HISTORY: usize> {
+    sensor: &'static dyn Sensor,
+    interval: Duration,
+    next_due: Instant,
+    history: History<(Instant, Samples), HISTORY>,
+}
+
+impl<const SENSORS: usize, const HISTORY: usize, const SUBSCRIBERS: usize> Default
+    for Sampler<SENSORS, HISTORY, SUBSCRIBERS>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const SENSORS: usize, const HISTORY: usize, const SUBSCRIBERS: usize>
+    Sampler<SENSORS, HISTORY, SUBSCRIBERS>
+{
+    /// Creates a new [`Sampler`], which does not sample any sensor driver instance yet.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            entries: Mutex::new(RefCell::new([const { None }; SENSORS])),
+            events: PubSubChannel::new(),
+            changed: Signal::new(),
+        }
+    }
+
+    /// Sets the interval at which `sensor` is sampled, starting right away.
+    ///
+    /// With `None`, the sensor driver instance is not sampled anymore and its history is
+    /// dropped.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`SetIntervalError::ZeroInterval`] if `interval` is zero.
+    /// - Returns [`SetIntervalError::SamplerFull`] if `SENSORS` sensor driver instances are
+    ///   sampled already.
+    pub fn set_interval(
+        &self,
+        sensor: &'static dyn Sensor,
+        interval: Option<Duration>,
+    ) -> Result<(), SetIntervalError> {
+        if interval == Some(Duration::from_ticks(0)) {
+            return Err(SetIntervalError::ZeroInterval);
+        }
+        let now = Instant::now();
+        self.entries.lock(|entries| {
+            let mut entries = entries.borrow_mut();
+            let slot = if entries.iter().any(|entry| entry.is_for(sensor)) {
+                entries.iter_mut().find(|entry| entry.is_for(sensor))
+            } else if interval.is_some() {
+                entries.iter_mut().find(|entry| entry.is_none())
+            } else {
+                return Ok(());
+            };
+            let slot = slot.ok_or(SetIntervalError::SamplerFull)?;
+            *slot = interval.map(|interval| Entry {
+                sensor,
+                interval,
+                next_due: now,
+                history: slot.take().map_or_else(History::new, |entry| entry.history),
+            });
+            Ok(())
+        })?;
+        self.changed.signal(());
+        Ok(())
+    }
+
+    /// Returns a subscriber receiving a [`SamplingEvent`] for every reading and error.
+    ///
+    /// Subscribers that do not keep up miss the oldest events, see
+    /// [`WaitResult::Lagged`](pubsub::WaitResult::Lagged).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`pubsub::Error::MaximumSubscribersReached`] if `SUBSCRIBERS` subscribers exist
+    /// already.
+    pub fn subscriber(
+        &self,
+    ) -> Result<
+        Subscriber<'_, CriticalSectionRawMutex, SamplingEvent, SENSORS, SUBSCRIBERS, 1>,
+        pubsub::Error,
+    > {
+        self.events.subscriber()
+    }
+
+    /// Returns the most recent reading of `sensor`, along with the time it was obtained at.
+    ///
+    /// Returns `None` if `sensor` is not sampled or no reading has been obtained yet.
+    #[must_use]
+    pub fn latest(&self, sensor: &'static dyn Sensor) -> Option<(Instant, Samples)> {
+        self.entries.lock(|entries| {
+            entries
+                .borrow()
+                .iter()
+                .flatten()
+                .find(|entry| entry.is_for(sensor))
+                .and_then(|entry| entry.history.iter().last())
+        })
+    }
+
+    /// Calls `f` with the recorded samples of one [`ReadingChannel`] of `sensor`, oldest first,
+    /// along with the time they were obtained at.
+    ///
+    /// `channel` is the index of the channel in [`Sensor::reading_channels()`].
+    ///
+    /// The samples are copied out of the history first, so `f` does not run inside the
+    /// critical section protecting it.
+    ///
+    /// Returns `None` if `sensor` is not sampled.
+    ///
+    /// [`ReadingChannel`]: crate::sensor::ReadingChannel
+    pub fn history<R>(
+        &self,
+        sensor: &'static dyn Sensor,
+        channel: usize,
+        f: impl FnOnce(&mut dyn Iterator<Item = (Instant, Sample)>) -> R,
+    ) -> Option<R> {
+        let mut samples = [None; HISTORY];
+        self.entries.lock(|entries| {
+            let entries = entries.borrow();
+            let entry = entries
+                .iter()
+                .flatten()
+                .find(|entry| entry.is_for(sensor))?;
+            let history = entry.history.iter().filter_map(|(timestamp, samples)| {
+                samples
+                    .samples()
+                    .nth(channel)
+                    .map(|sample| (timestamp, sample))
+            });
+            for (slot, sample) in samples.iter_mut().zip(history) {
+                *slot = Some(sample);
+            }
+            Some(())
+        })?;
+        Some(f(&mut samples.into_iter().flatten()))
+    }
+
+    /// Runs the sampler.
+    ///
+    /// This must be spawned once, e.g., as a task.
+    pub async fn run(&self) -> ! {
+        loop {
+            let now = Instant::now();
+            let mut due: [Option<(&'static dyn Sensor, Instant)>; SENSORS] = [None; SENSORS];
+            let mut next_due = Instant::MAX;
+            self.entries.lock(|entries| {
+                let mut entries = entries.borrow_mut();
+                for (entry, due) in entries.iter_mut().flatten().zip(&mut due) {
+                    if entry.next_due <= now {
+                        let deadline = now.checked_add(entry.interval).unwrap_or(Instant::MAX);
+                        *due = Some((entry.sensor, deadline));
+                        entry.next_due += entry.interval;
+                        if entry.next_due <= now {
+                            // The sampler fell behind, skip the missed measurements.
+                            entry.next_due = now + entry.interval;
+                        }
+                    }
+                    next_due = next_due.min(entry.next_due);
+                }
+            });
+
+            // Trigger all measurements first, so that they happen concurrently.
+            for slot in &mut due {
+                let Some((sensor, _)) = *slot else {
+                    continue;
+                };
+                if !matches!(sensor.state(), State::Enabled | State::Measuring) {
+                    *slot = None;
+                    continue;
+                }
+                if let Err(err) = sensor.trigger_measurement() {
+                    *slot = None;
+                    self.publish(sensor, Err(err.into()));
+                }
+            }
+
+            for (sensor, deadline) in due.into_iter().flatten() {
+                let result = match with_deadline(deadline, sensor.wait_for_reading()).await {
+                    Ok(result) => result.map_err(SamplingError::from),
+                    Err(TimeoutError) => Err(SamplingError::Timeout),
+                };
+                if let Ok(samples) = result {
+                    let timestamp = Instant::now();
+                    self.entries.lock(|entries| {
+                        let mut entries = entries.borrow_mut();
+                        // The sensor driver instance might not be sampled anymore.
+                        if let Some(entry) = entries
+                            .iter_mut()
+                            .flatten()
+                            .find(|entry| entry.is_for(sensor))
+                        {
+                            entry.history.push((timestamp, samples));
+                        }
+                    });
+                    crate::sensor::notify_reading(sensor, samples);
+                }
+                self.publish(sensor, result);
+            }
+
+            // Wait for the next measurement, unless the configuration changes.
+            let _ = with_deadline(next_due, self.changed.wait()).await;
+        }
+    }
+
+    fn publish(&self, sensor: &'static dyn Sensor, result: Result<Samples, SamplingError>) {
+        self.events
+            .immediate_publisher()
+            .publish_immediate(SamplingEvent {
+                sensor,
+                timestamp: Instant::now(),
+                result,
+            });
+    }
+}
+
+impl<const HISTORY: usize> Entry<HISTORY> {
+    fn is_for(&self, sensor: &'static dyn Sensor) -> bool {
+        core::ptr::addr_eq(self.sensor, sensor)
+    }
+}
+
+trait IsFor {
+    fn is_for(&self, sensor: &'static dyn Sensor) -> bool;
+}
+
+impl<const HISTORY: usize> IsFor for Option<Entry<HISTORY>> {
+    fn is_for(&self, sensor: &'static dyn Sensor) -> bool {
+        self.as_ref().is_some_and(|entry| entry.is_for(sensor))
+    }
+}
+
+/// Reading or error obtained by a [`Sampler`], see [`Sampler::subscriber()`].
+#[derive(Clone)]
+pub struct SamplingEvent {
+    sensor: &'static dyn Sensor,
+    timestamp: Instant,
+    result: Result<Samples, SamplingError>,
+}
+
+impl SamplingEvent {
+    /// Returns the sensor driver instance the reading was obtained from.
+    #[must_use]
+    pub fn sensor(&self) -> &'static dyn Sensor {
+        self.sensor
+    }
+
+    /// Returns the time the reading or error was obtained at.
+    #[must_use]
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
+
+    /// Returns the reading.
+    ///
+    /// Interpretation of the reading requires data from [`Sensor::reading_channels()`] as well.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that happened while triggering the measurement or waiting for the
+    /// reading.
+    pub fn result(&self) -> Result<Samples, SamplingError> {
+        self.result
+    }
+}
+
+/// Represents errors happening when sampling a sensor driver instance.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SamplingError {
+    /// The sensor driver is not enabled (e.g., it may be disabled or sleeping).
+    NonEnabled,
+    /// Cannot access the sensor device (e.g., because of a bus error).
+    SensorAccess,
+    /// No measurement has been triggered before waiting for a reading.
+    NotMeasuring,
+    /// The reading was not obtained within the sampling interval.
+    Timeout,
+}
+
+impl From<TriggerMeasurementError> for SamplingError {
+    fn from(err: TriggerMeasurementError) -> Self {
+        match err {
+            TriggerMeasurementError::NonEnabled => Self::NonEnabled,
+        }
+    }
+}
+
+impl From<ReadingError> for SamplingError {
+    fn from(err: ReadingError) -> Self {
+        match err {
+            ReadingError::NonEnabled => Self::NonEnabled,
+            ReadingError::SensorAccess => Self::SensorAccess,
+            ReadingError::NotMeasuring => Self::NotMeasuring,
+        }
+    }
+}
+
+impl core::fmt::Display for SamplingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NonEnabled => write!(f, "sensor driver is not enabled"),
+            Self::SensorAccess => write!(f, "sensor device could not be accessed"),
+            Self::NotMeasuring => write!(f, "no measurement has been triggered"),
+            Self::Timeout => write!(f, "reading was not obtained within the sampling interval"),
+        }
+    }
+}
+
+impl core::error::Error for SamplingError {}
+
+/// Represents errors happening when setting the sampling interval of a sensor driver instance,
+/// see [`Sampler::set_interval()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SetIntervalError {
+    /// The interval is zero.
+    ZeroInterval,
+    /// The sampler cannot sample another sensor driver instance.
+    SamplerFull,
+}
+
+impl core::fmt::Display for SetIntervalError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ZeroInterval => write!(f, "sampling interval must not be zero"),
+            Self::SamplerFull => write!(f, "sampler cannot sample more sensor driver instances"),
+        }
+    }
+}
+
+impl core::error::Error for SetIntervalError {}
+
+/// Ring buffer keeping the `N` most recently pushed values.
+struct History<T, const N: usize> {
+    values: [Option<T>; N],
+    /// Index the next value is written to.
+    next: usize,
+}
+
+impl<T: Copy, const N: usize> History<T, N> {
+    const fn new() -> Self {
+        Self {
+            values: [None; N],
+            next: 0,
+        }
+    }
+
+    /// Pushes a value, dropping the oldest one if the history is full.
+    fn push(&mut self, value: T) {
+        // Nothing is kept if `N` is zero.
+        if let Some(slot) = self.values.get_mut(self.next) {
+            *slot = Some(value);
+            self.next = (self.next + 1) % N;
+        }
+    }
+
+    /// Returns an iterator over the values, oldest first.
+    fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        let (newer, older) = self.values.split_at(self.next);
+        older.iter().chain(newer).flatten().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_ring_buffer() {
+        let mut history: History<u32, 3> = History::new();
+        assert_eq!(history.iter().next(), None);
+
+        history.push(1);
+        history.push(2);
+        assert!(history.iter().eq([1, 2]));
+
+        history.push(3);
+        history.push(4);
+        assert!(history.iter().eq([2, 3, 4]));
+
+        let mut empty: History<u32, 0> = History::new();
+        empty.push(1);
+        assert_eq!(empty.iter().next(), None);
+    }
+}
@@ -0,0 +1,582 @@
+//! Provides [SenML (RFC 8428)](https://www.rfc-editor.org/rfc/rfc8428) encoding of sensor
+//! readings, in both its CBOR and JSON representations.
+//!
+//! A SenML pack is encoded for one or multiple readings, each along with the sensor driver
+//! instance it was obtained from.
+//! Each [`Sample`] of a reading is encoded as a record:
+//!
+//! - The name of the records of a reading is based on the [`Sensor::label()`] of the sensor
+//!   driver instance, falling back to its [`Sensor::part_number()`], prefixed with a
+//!   caller-provided prefix (e.g., a device URN ending with a `:`).
+//!   It is suffixed with `/` and the [`Label`] of the [`ReadingChannel`], except for
+//!   [`Label::Main`].
+//!   Characters not allowed in SenML names are replaced with `_`.
+//!   Names that would be empty or not start with a letter or digit, as required by RFC 8428,
+//!   Section 4.5.1, are prefixed with `sensor`.
+//! - The [`MeasurementUnit`] is mapped to the corresponding SenML unit.
+//!   Samples whose unit is not a SenML unit are converted exactly into one, e.g., grams are
+//!   converted into kilograms.
+//! - Values are encoded exactly, taking the [scaling](ReadingChannel::scaling) into account:
+//!   decimal numbers are used in JSON and decimal fractions in CBOR.
+//!   [`MeasurementUnit::Bool`] samples are encoded as boolean values.
+//!
+//! The accuracy of samples is not encoded.
+//!
+//! # Examples
+//!
+//! A reading of a temperature & humidity sensor labeled `indoor` is encoded as follows in JSON,
+//! using `urn:dev:mac:0024befffe804ff1:` as prefix:
+//!
+//! ```json
+//! [
+//!   {"bn":"urn:dev:mac:0024befffe804ff1:indoor","n":"/temperature","u":"Cel","v":22.25},
+//!   {"n":"/relative-humidity","u":"%RH","v":45.5}
+//! ]
+//! ```
+#![allow(
+    clippy::missing_errors_doc,
+    reason = "private helpers only fail when the output buffer is full"
+)]
+
+use core::fmt::Write as _;
+
+use crate::{
+    Label, MeasurementUnit, Reading, Sensor,
+    sensor::{ReadingChannel, Sample, Samples},
+};
+
+/// SenML labels of the CBOR representation, see RFC 8428, Section 6.
+mod cbor_label {
+    pub const BASE_NAME: i64 = -2;
+    pub const NAME: i64 = 0;
+    pub const UNIT: i64 = 1;
+    pub const VALUE: i64 = 2;
+    pub const BOOLEAN_VALUE: i64 = 4;
+}
+
+/// CBOR tag of decimal fractions, see RFC 8949, Section 3.4.4.
+const CBOR_TAG_DECIMAL_FRACTION: u64 = 4;
+
+/// Encodes `readings` as a SenML pack in the CBOR representation into `buf`.
+///
+/// Returns the number of bytes written.
+///
+/// See [the module level documentation](self) for more.
+///
+/// # Errors
+///
+/// Returns [`BufferTooSmallError`] if `buf` is too small to hold the encoded pack.
+pub fn encode_cbor(
+    prefix: &str,
+    readings: &[(&dyn Sensor, Samples)],
+    buf: &mut [u8],
+) -> Result<usize, BufferTooSmallError> {
+    let mut out = Buffer::new(buf);
+
+    let record_count = readings
+        .iter()
+        .map(|(sensor, samples)| record_count(*sensor, *samples))
+        .sum::<usize>();
+    cbor_head(&mut out, 4, record_count as u64)?;
+
+    for (sensor, samples) in readings {
+        let base_name = BaseName::new(prefix, *sensor);
+        let channels = sensor.reading_channels();
+        for (i, (channel, sample)) in channels.iter().zip(samples.samples()).enumerate() {
+            let name = channel_name(channel.label());
+            let value = Value::new(channel, sample);
+
+            let is_first = i == 0;
+            let entry_count = 1
+                + usize::from(is_first)
+                + usize::from(name.is_some())
+                + usize::from(value.unit.is_some());
+            cbor_head(&mut out, 5, entry_count as u64)?;
+
+            if is_first {
+                cbor_int(&mut out, cbor_label::BASE_NAME)?;
+                cbor_head(&mut out, 3, base_name.len() as u64)?;
+                base_name.write(&mut out)?;
+            }
+            if let Some(name) = name {
+                cbor_int(&mut out, cbor_label::NAME)?;
+                cbor_str(&mut out, name)?;
+            }
+            if let Some(unit) = value.unit {
+                cbor_int(&mut out, cbor_label::UNIT)?;
+                cbor_str(&mut out, unit)?;
+            }
+            match value.number {
+                Number::Bool(value) => {
+                    cbor_int(&mut out, cbor_label::BOOLEAN_VALUE)?;
+                    out.write(&[if value { 0xf5 } else { 0xf4 }])?;
+                }
+                Number::Decimal { mantissa, exponent } => {
+                    cbor_int(&mut out, cbor_label::VALUE)?;
+                    if exponent == 0 {
+                        cbor_int(&mut out, mantissa)?;
+                    } else {
+                        cbor_head(&mut out, 6, CBOR_TAG_DECIMAL_FRACTION)?;
+                        cbor_head(&mut out, 4, 2)?;
+                        cbor_int(&mut out, i64::from(exponent))?;
+                        cbor_int(&mut out, mantissa)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(out.len)
+}
+
+/// Encodes `readings` as a SenML pack in the JSON representation into `buf`.
+///
+/// Returns the number of bytes written.
+///
+/// See [the module level documentation](self) for more.
+///
+/// # Errors
+///
+/// Returns [`BufferTooSmallError`] if `buf` is too small to hold the encoded pack.
+pub fn encode_json(
+    prefix: &str,
+    readings: &[(&dyn Sensor, Samples)],
+    buf: &mut [u8],
+) -> Result<usize, BufferTooSmallError> {
+    let mut out = Buffer::new(buf);
+
+    out.write(b"[")?;
+    let mut is_first_record = true;
+    for (sensor, samples) in readings {
+        let base_name = BaseName::new(prefix, *sensor);
+        let channels = sensor.reading_channels();
+        for (i, (channel, sample)) in channels.iter().zip(samples.samples()).enumerate() {
+            if !is_first_record {
+                out.write(b",")?;
+            }
+            is_first_record = false;
+
+            let value = Value::new(channel, sample);
+            // Separates the members of the record.
+            let mut separator = "";
+
+            out.write(b"{")?;
+            if i == 0 {
+                out.write(b"\"bn\":\"")?;
+                base_name.write(&mut out)?;
+                out.write(b"\"")?;
+                separator = ",";
+            }
+            // Names and units do not need escaping.
+            if let Some(name) = channel_name(channel.label()) {
+                write!(out, "{separator}\"n\":\"{name}\"")?;
+                separator = ",";
+            }
+            if let Some(unit) = value.unit {
+                write!(out, "{separator}\"u\":\"{unit}\"")?;
+                separator = ",";
+            }
+            match value.number {
+                Number::Bool(value) => write!(out, "{separator}\"vb\":{value}")?,
+                Number::Decimal { mantissa, exponent } => {
+                    write!(out, "{separator}\"v\":")?;
+                    json_decimal(&mut out, mantissa, exponent)?;
+                }
+            }
+            out.write(b"}")?;
+        }
+    }
+    out.write(b"]")?;
+
+    Ok(out.len)
+}
+
+/// Returns the number of records of a reading.
+fn record_count(sensor: &dyn Sensor, samples: Samples) -> usize {
+    sensor
+        .reading_channels()
+        .iter()
+        .len()
+        .min(samples.samples().len())
+}
+
+/// Prefix of base names that would otherwise be empty or not start with a letter or digit.
+pub(crate) const FALLBACK_NAME: &str = "sensor";
+
+/// Base name of the records of a reading.
+struct BaseName<'a> {
+    prefix: &'a str,
+    instance: &'a str,
+}
+
+impl<'a> BaseName<'a> {
+    fn new(prefix: &'a str, sensor: &dyn Sensor) -> Self {
+        Self {
+            prefix,
+            instance: sensor.label().or(sensor.part_number()).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the characters of the base name, with characters not allowed in SenML names
+    /// replaced, and prefixed with [`FALLBACK_NAME`] if needed.
+    fn chars(&self) -> impl Iterator<Item = u8> + '_ {
+        let name = self.prefix.chars().chain(self.instance.chars());
+        let is_valid = name
+            .clone()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric());
+        let fallback = if is_valid { "" } else { FALLBACK_NAME };
+        fallback.bytes().chain(name.map(name_char))
+    }
+
+    /// Returns the length in bytes of the base name.
+    fn len(&self) -> usize {
+        self.chars().count()
+    }
+
+    fn write(&self, out: &mut Buffer<'_>) -> Result<(), BufferTooSmallError> {
+        self.chars().try_for_each(|c| out.write(&[c]))
+    }
+}
+
+/// Returns `c` if it is allowed in SenML names, `_` otherwise.
+pub(crate) fn name_char(c: char) -> u8 {
+    if c.is_ascii_alphanumeric() || matches!(c, '-' | ':' | '.' | '/' | '_') {
+        c as u8
+    } else {
+        b'_'
+    }
+}
+
+/// Returns the name of the records of a [`ReadingChannel`], which is appended to the base name.
+fn channel_name(label: Label) -> Option<&'static str> {
+    match label {
+        Label::Main => None,
+        Label::RelativeHumidity => Some("/relative-humidity"),
+        Label::Temperature => Some("/temperature"),
+        Label::X => Some("/x"),
+        Label::Y => Some("/y"),
+        Label::Z => Some("/z"),
+    }
+}
+
+/// Value of a record, along with its SenML unit.
+struct Value {
+    unit: Option<&'static str>,
+    number: Number,
+}
+
+enum Number {
+    Bool(bool),
+    /// Exact value of `mantissa · 10^exponent`.
+    Decimal {
+        mantissa: i64,
+        exponent: i32,
+    },
+}
+
+impl Value {
+    fn new(channel: ReadingChannel, sample: Sample) -> Self {
+        let value = i64::from(sample.value());
+        let exponent = i32::from(channel.scaling());
+
+        // Converts the value with `factor · 10^exponent_offset` into `unit`.
+        let converted = |unit, factor: i64, exponent_offset: i32| Self {
+            unit: Some(unit),
+            number: Number::Decimal {
+                mantissa: value * factor,
+                exponent: exponent + exponent_offset,
+            },
+        };
+        let unit = match channel.unit() {
+            MeasurementUnit::Bool => {
+                return Self {
+                    unit: None,
+                    number: Number::Bool(value != 0),
+                };
+            }
+            // Standard gravity is exactly 9.80665 m/s².
+            MeasurementUnit::AccelG => return converted("m/s2", 980_665, -5),
+            MeasurementUnit::Gram => return converted("kg", 1, -3),
+            MeasurementUnit::Percent => return converted("/", 1, -2),
+            MeasurementUnit::Ampere => "A",
+            MeasurementUnit::Becquerel => "Bq",
+            MeasurementUnit::Candela => "cd",
+            MeasurementUnit::Celsius => "Cel",
+            MeasurementUnit::Coulomb => "C",
+            MeasurementUnit::Decibel => "dB",
+            MeasurementUnit::Farad => "F",
+            MeasurementUnit::Gray => "Gy",
+            MeasurementUnit::Henry => "H",
+            MeasurementUnit::Hertz => "Hz",
+            MeasurementUnit::Joule => "J",
+            MeasurementUnit::Katal => "kat",
+            MeasurementUnit::Kelvin => "K",
+            MeasurementUnit::Lumen => "lm",
+            MeasurementUnit::Lux => "lx",
+            MeasurementUnit::Meter => "m",
+            MeasurementUnit::Mole => "mol",
+            MeasurementUnit::Newton => "N",
+            MeasurementUnit::Ohm => "Ohm",
+            MeasurementUnit::Pascal => "Pa",
+            MeasurementUnit::PercentageRelativeHumidity => "%RH",
+            MeasurementUnit::Radian => "rad",
+            MeasurementUnit::Second => "s",
+            MeasurementUnit::Siemens => "S",
+            MeasurementUnit::Sievert => "Sv",
+            MeasurementUnit::Steradian => "sr",
+            MeasurementUnit::Tesla => "T",
+            MeasurementUnit::Volt => "V",
+            MeasurementUnit::Watt => "W",
+            MeasurementUnit::Weber => "Wb",
+        };
+        converted(unit, 1, 0)
+    }
+}
+
+/// Writes `mantissa · 10^exponent` as a JSON number, without loss of precision.
+fn json_decimal(
+    out: &mut Buffer<'_>,
+    mantissa: i64,
+    exponent: i32,
+) -> Result<(), BufferTooSmallError> {
+    if exponent >= 0 {
+        if exponent == 0 {
+            write!(out, "{mantissa}")?;
+        } else {
+            write!(out, "{mantissa}e{exponent}")?;
+        }
+        return Ok(());
+    }
+
+    if mantissa < 0 {
+        out.write(b"-")?;
+    }
+    // Large enough for the digits of any `u64`.
+    let mut digits = [0u8; 20];
+    let mut digits_len = 0;
+    let mut abs = mantissa.unsigned_abs();
+    for digit in digits.iter_mut().rev() {
+        *digit = b'0' + (abs % 10) as u8;
+        digits_len += 1;
+        abs /= 10;
+        if abs == 0 {
+            break;
+        }
+    }
+    let digits = digits.get(digits.len() - digits_len..).unwrap_or_default();
+
+    let fraction_len = exponent.unsigned_abs() as usize;
+    if let Some(integer_len) = digits
+        .len()
+        .checked_sub(fraction_len)
+        .filter(|len| *len > 0)
+    {
+        let (integer, fraction) = digits.split_at(integer_len);
+        out.write(integer)?;
+        out.write(b".")?;
+        out.write(fraction)?;
+    } else {
+        out.write(b"0.")?;
+        for _ in digits.len()..fraction_len {
+            out.write(b"0")?;
+        }
+        out.write(digits)?;
+    }
+    Ok(())
+}
+
+/// Writes the head of a CBOR data item with the given major type and argument.
+fn cbor_head(out: &mut Buffer<'_>, major: u8, argument: u64) -> Result<(), BufferTooSmallError> {
+    let major = major << 5;
+    if let Ok(argument) = u8::try_from(argument) {
+        if argument < 24 {
+            out.write(&[major | argument])
+        } else {
+            out.write(&[major | 0x18, argument])
+        }
+    } else if let Ok(argument) = u16::try_from(argument) {
+        out.write(&[major | 0x19])?;
+        out.write(&argument.to_be_bytes())
+    } else if let Ok(argument) = u32::try_from(argument) {
+        out.write(&[major | 0x1a])?;
+        out.write(&argument.to_be_bytes())
+    } else {
+        out.write(&[major | 0x1b])?;
+        out.write(&argument.to_be_bytes())
+    }
+}
+
+/// Writes a CBOR integer.
+fn cbor_int(out: &mut Buffer<'_>, value: i64) -> Result<(), BufferTooSmallError> {
+    if value >= 0 {
+        cbor_head(out, 0, value.unsigned_abs())
+    } else {
+        // Negative integers are encoded as `-1 - value`.
+        cbor_head(out, 1, (-1 - value).unsigned_abs())
+    }
+}
+
+/// Writes a CBOR text string.
+fn cbor_str(out: &mut Buffer<'_>, value: &str) -> Result<(), BufferTooSmallError> {
+    cbor_head(out, 3, value.len() as u64)?;
+    out.write(value.as_bytes())
+}
+
+/// Output buffer.
+struct Buffer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Buffer<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), BufferTooSmallError> {
+        let end = self.len + bytes.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(BufferTooSmallError)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl core::fmt::Write for Buffer<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
+}
+
+/// The error type returned when the buffer is too small to hold the encoded SenML pack.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BufferTooSmallError;
+
+impl From<core::fmt::Error> for BufferTooSmallError {
+    fn from(_: core::fmt::Error) -> Self {
+        // Writing to a `Buffer` only fails when it is full.
+        Self
+    }
+}
+
+impl core::fmt::Display for BufferTooSmallError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "buffer is too small to hold the SenML pack")
+    }
+}
+
+impl core::error::Error for BufferTooSmallError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{sensor::Accuracy, test_utils::TestSensor};
+
+    use super::*;
+
+    fn samples(value: i32) -> Samples {
+        Samples::V1([Sample::new(value, Accuracy::Unknown)])
+    }
+
+    fn thermometer() -> TestSensor {
+        TestSensor::new(
+            Some("indoor temp"),
+            Label::Temperature,
+            -2,
+            MeasurementUnit::Celsius,
+        )
+    }
+
+    fn button() -> TestSensor {
+        TestSensor::new(None, Label::Main, 0, MeasurementUnit::Bool)
+    }
+
+    fn json_number(mantissa: i64, exponent: i32) -> ([u8; 32], usize) {
+        let mut buf = [0; 32];
+        let mut out = Buffer::new(&mut buf);
+        json_decimal(&mut out, mantissa, exponent).unwrap();
+        let len = out.len;
+        (buf, len)
+    }
+
+    #[test]
+    fn json_decimal_is_exact() {
+        for (mantissa, exponent, expected) in [
+            (2225, -2, "22.25"),
+            (-2225, -2, "-22.25"),
+            (5, -2, "0.05"),
+            (-5, -3, "-0.005"),
+            (42, 0, "42"),
+            (42, 3, "42e3"),
+            (0, -1, "0.0"),
+        ] {
+            let (buf, len) = json_number(mantissa, exponent);
+            assert_eq!(buf.get(..len), Some(expected.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn base_name_starts_alphanumeric() {
+        for (prefix, instance, expected) in [
+            ("urn:dev:ow:1:", "indoor temp", "urn:dev:ow:1:indoor_temp"),
+            ("", "outdoor", "outdoor"),
+            ("", "", "sensor"),
+            ("", "_x", "sensor_x"),
+            ("", "é", "sensor_"),
+            (":", "", "sensor:"),
+        ] {
+            let base_name = BaseName { prefix, instance };
+            assert!(base_name.chars().eq(expected.bytes()));
+            assert_eq!(base_name.len(), expected.len());
+        }
+    }
+
+    #[test]
+    fn encode_json_batch() {
+        let (thermometer, button) = (thermometer(), button());
+        let readings: [(&dyn Sensor, Samples); 2] =
+            [(&thermometer, samples(2225)), (&button, samples(1))];
+        let mut buf = [0; 256];
+        let len = encode_json("urn:dev:ow:10e2073a0108006:", &readings, &mut buf).unwrap();
+        assert_eq!(
+            buf.get(..len)
+                .map(|json| core::str::from_utf8(json).unwrap()),
+            Some(concat!(
+                r#"[{"bn":"urn:dev:ow:10e2073a0108006:indoor_temp","n":"/temperature","u":"Cel","v":22.25},"#,
+                r#"{"bn":"urn:dev:ow:10e2073a0108006:TEST01","vb":true}]"#,
+            )),
+        );
+
+        let mut short = [0; 256];
+        let short = short.get_mut(..len - 1).unwrap();
+        assert!(encode_json("urn:dev:ow:10e2073a0108006:", &readings, short).is_err());
+    }
+
+    #[test]
+    fn encode_cbor_batch() {
+        let (thermometer, button) = (thermometer(), button());
+        let readings: [(&dyn Sensor, Samples); 2] =
+            [(&thermometer, samples(2225)), (&button, samples(0))];
+        let mut buf = [0; 64];
+        let len = encode_cbor("a:", &readings, &mut buf).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            0x82, // array(2)
+            0xa4, // map(4)
+            0x21, 0x6d, b'a', b':', b'i', b'n', b'd', b'o', b'o', b'r', b'_', b't', b'e', b'm', b'p',
+            0x00, 0x6c, b'/', b't', b'e', b'm', b'p', b'e', b'r', b'a', b't', b'u', b'r', b'e',
+            0x01, 0x63, b'C', b'e', b'l',
+            0x02, 0xc4, 0x82, 0x21, 0x19, 0x08, 0xb1, // 4([-2, 2225])
+            0xa2, // map(2)
+            0x21, 0x68, b'a', b':', b'T', b'E', b'S', b'T', b'0', b'1',
+            0x04, 0xf4,
+        ];
+        assert_eq!(buf.get(..len), Some(expected.as_slice()));
+
+        let short = buf.get_mut(..len - 1).unwrap();
+        assert!(encode_cbor("a:", &readings, short).is_err());
+    }
+}
@@ -138,6 +138,25 @@ impl Future for ReadingWaiter {
     }
 }
 
+/// Notifies of a new reading of a sensor driver instance.
+///
+/// With the `alarms` feature, this evaluates the alarm watches on `sensor`; it does nothing
+/// otherwise.
+/// Readings obtained by a `Sampler`, with the `sampling` feature, are notified by the sampler
+/// itself.
+///
+/// # For implementors
+///
+/// Sensor drivers detecting changes on their own, e.g., push buttons using edge interrupts, should
+/// call this with a reading on each change, so that alarms trigger immediately instead of requiring
+/// measurements to be triggered periodically.
+pub fn notify_reading(sensor: &dyn Sensor, samples: Samples) {
+    #[cfg(feature = "alarms")]
+    crate::alarms::ALARMS.process(sensor, samples);
+    #[cfg(not(feature = "alarms"))]
+    let _ = (sensor, samples);
+}
+
 /// Mode of a sensor driver.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -0,0 +1,71 @@
+//! Provides helpers for unit tests.
+use crate::{
+    Category, Label, MeasurementUnit, Sensor,
+    sensor::{
+        Mode, ReadingChannel, ReadingChannels, ReadingError, ReadingWaiter, SetModeError, State,
+        TriggerMeasurementError,
+    },
+};
+
+/// Sensor driver returning a single sample, which cannot obtain readings.
+pub(crate) struct TestSensor {
+    label: Option<&'static str>,
+    channel: (Label, i8, MeasurementUnit),
+}
+
+impl TestSensor {
+    pub(crate) const fn new(
+        label: Option<&'static str>,
+        channel_label: Label,
+        scaling: i8,
+        unit: MeasurementUnit,
+    ) -> Self {
+        Self {
+            label,
+            channel: (channel_label, scaling, unit),
+        }
+    }
+}
+
+impl Sensor for TestSensor {
+    fn trigger_measurement(&self) -> Result<(), TriggerMeasurementError> {
+        Ok(())
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        ReadingWaiter::Err(ReadingError::NotMeasuring)
+    }
+
+    fn reading_channels(&self) -> ReadingChannels {
+        let (label, scaling, unit) = self.channel;
+        ReadingChannels::V1([ReadingChannel::new(label, scaling, unit)])
+    }
+
+    fn set_mode(&self, _mode: Mode) -> Result<State, SetModeError> {
+        Ok(State::Enabled)
+    }
+
+    fn state(&self) -> State {
+        State::Enabled
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[]
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        Some("TEST01")
+    }
+
+    fn version(&self) -> u8 {
+        0
+    }
+}
@@ -291,7 +291,14 @@ global_asm!(
 ///
 /// - must not be called manually (only by PendSV)
 unsafe extern "C" fn sched() -> u64 {
+    #[cfg(feature = "trace")]
+    crate::trace::isr_enter(SystemHandler::PendSV as u16);
+
     let (current_high_regs, next_high_regs) = loop {
+        // The timer might wake up threads, so it runs before picking the next one.
+        #[cfg(feature = "time")]
+        crate::time::poll_timer_executor();
+
         if let Some(res) = critical_section::with(|cs| {
             let scheduler = unsafe { &mut *SCHEDULER.as_ptr(cs) };
 
@@ -306,6 +313,8 @@ unsafe extern "C" fn sched() -> u64 {
 
                     #[cfg(not(feature = "multi-core"))]
                     {
+                        #[cfg(feature = "cpu-time")]
+                        scheduler.account_cpu_time(None);
                         Cpu::wfi();
                         // this fence seems necessary, see #310.
                         core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
@@ -314,11 +323,16 @@ unsafe extern "C" fn sched() -> u64 {
                 }
             };
 
+            #[cfg(feature = "cpu-time")]
+            scheduler.account_cpu_time(Some(next_tid));
+
             // `current_high_regs` will be null if there is no current thread.
             // This is only the case once, when the very first thread starts running.
             // The returned `r1` therefore will be null, and saving/ restoring
             // the context is skipped.
             let mut current_high_regs = core::ptr::null();
+            #[cfg(feature = "trace")]
+            let previous_tid = scheduler.current_tid();
             if let Some(current_tid_ref) = scheduler.current_tid_mut() {
                 if next_tid == *current_tid_ref {
                     return Some((0, 0));
@@ -326,12 +340,23 @@ unsafe extern "C" fn sched() -> u64 {
                 let current_tid = *current_tid_ref;
                 *current_tid_ref = next_tid;
                 let current = scheduler.get_unchecked_mut(current_tid);
-                current.data.sp = cortex_m::register::psp::read() as usize;
+                let sp = cortex_m::register::psp::read() as usize;
+                current.data.sp = sp;
                 current_high_regs = current.data.high_regs.as_ptr();
+                #[cfg(feature = "stack-overflow-check")]
+                scheduler.check_stack(current_tid, sp);
+                #[cfg(feature = "alloc")]
+                scheduler.release_stacks(current_tid);
             } else {
                 *scheduler.current_tid_mut() = Some(next_tid);
             }
 
+            #[cfg(feature = "trace")]
+            crate::trace::record(crate::trace::TraceEvent::Switch {
+                from: previous_tid,
+                to: Some(next_tid),
+            });
+
             let next = scheduler.get_unchecked(next_tid);
             // SAFETY: changing the PSP as part of context switch
             unsafe { cortex_m::register::psp::write(next.data.sp as u32) };
@@ -350,6 +375,12 @@ unsafe extern "C" fn sched() -> u64 {
         }
     };
 
+    #[cfg(feature = "time-slicing")]
+    crate::time_slice::start_tick_if_needed();
+
+    #[cfg(feature = "trace")]
+    crate::trace::isr_exit();
+
     // The caller (`PendSV`) expects these two pointers in r0 and r1:
     // r0 = &current.data.high_regs (or 0)
     // r1 = &next.data.high_regs
@@ -1,4 +1,4 @@
-use crate::Thread;
+use crate::{SCHEDULER, Thread};
 
 /// Arch-specific implementations for the scheduler.
 pub trait Arch {
@@ -23,6 +23,22 @@ pub trait Arch {
     /// Setup and initiate the first context switch.
     fn start_threading();
 
+    /// Ends the current thread with exit value `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    fn exit(value: usize) -> ! {
+        SCHEDULER.with_mut(|mut scheduler| {
+            let thread_id = scheduler
+                .current_tid()
+                .expect("Function should be called inside a thread context.");
+            scheduler.exit(thread_id, value);
+        });
+
+        unreachable!();
+    }
+
     /// Prompts the CPU to enter deep sleep until an interrupt occurs.
     #[allow(dead_code, reason = "used in scheduler implementation")]
     fn wfi();
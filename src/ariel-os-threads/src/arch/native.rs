@@ -11,6 +11,9 @@ mod critical_section;
 static THREAD_RUNNABLE: [AtomicU32; crate::THREAD_COUNT] =
     [const { AtomicU32::new(0) }; crate::THREAD_COUNT];
 
+/// Unwinding payload used to end a thread through [`crate::exit()`].
+struct ThreadExit(usize);
+
 #[derive(Debug)]
 pub struct ThreadData {
     thread: Option<std::thread::Thread>,
@@ -66,13 +69,21 @@ impl Arch for Cpu {
                 }
             });
 
-            if res.is_err() {
-                ariel_os_debug::log::error!("thread {:?} panicked, aborting.", thread_id);
-                std::process::abort();
-            }
+            // `exit()` unwinds with a `ThreadExit` payload, anything else is an actual panic.
+            let value = match res {
+                Ok(()) => 0,
+                Err(payload) => {
+                    if let Some(ThreadExit(value)) = payload.downcast_ref::<ThreadExit>() {
+                        *value
+                    } else {
+                        ariel_os_debug::log::error!("thread {:?} panicked, aborting.", thread_id);
+                        std::process::abort();
+                    }
+                }
+            };
 
             SCHEDULER.with_mut(|mut scheduler| {
-                scheduler.set_state(thread_id, ThreadState::Invalid);
+                scheduler.exit(thread_id, value);
             });
         });
 
@@ -97,6 +108,11 @@ impl Arch for Cpu {
 
     fn schedule() {}
 
+    fn exit(value: usize) -> ! {
+        // Unwind to the thread's entry function, which ends the host thread.
+        std::panic::resume_unwind(Box::new(ThreadExit(value)))
+    }
+
     fn wfi() {
         unimplemented!()
     }
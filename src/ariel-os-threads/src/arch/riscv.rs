@@ -121,7 +121,11 @@ extern "C" fn FROM_CPU_INTR0(trap_frame: &mut TrapFrame) {
             .cpu_intr_from_cpu_0()
             .modify(|_, w| w.cpu_intr_from_cpu_0().clear_bit());
 
-        sched(trap_frame)
+        #[cfg(feature = "trace")]
+        crate::trace::isr_enter(Interrupt::FROM_CPU_INTR0 as u16);
+        sched(trap_frame);
+        #[cfg(feature = "trace")]
+        crate::trace::isr_exit();
     }
 }
 
@@ -135,6 +139,10 @@ extern "C" fn FROM_CPU_INTR0(trap_frame: &mut TrapFrame) {
 /// context switching.
 unsafe fn sched(trap_frame: &mut TrapFrame) {
     loop {
+        // The timer might wake up threads, so it runs before picking the next one.
+        #[cfg(feature = "time")]
+        crate::time::poll_timer_executor();
+
         if SCHEDULER.with_mut(|mut scheduler| {
             #[cfg(feature = "multi-core")]
             scheduler.add_current_thread_to_rq();
@@ -142,10 +150,14 @@ unsafe fn sched(trap_frame: &mut TrapFrame) {
             let next_tid = match scheduler.get_next_tid() {
                 Some(tid) => tid,
                 None => {
+                    #[cfg(feature = "cpu-time")]
+                    scheduler.account_cpu_time(None);
                     Cpu::wfi();
                     return false;
                 }
             };
+            #[cfg(feature = "cpu-time")]
+            scheduler.account_cpu_time(Some(next_tid));
 
             if let Some(current_tid) = scheduler.current_tid() {
                 if next_tid == current_tid {
@@ -155,7 +167,16 @@ unsafe fn sched(trap_frame: &mut TrapFrame) {
                     trap_frame,
                     &mut scheduler.threads[usize::from(current_tid)].data,
                 );
+                #[cfg(feature = "stack-overflow-check")]
+                scheduler.check_stack(current_tid, trap_frame.sp);
+                #[cfg(feature = "alloc")]
+                scheduler.release_stacks(current_tid);
             }
+            #[cfg(feature = "trace")]
+            crate::trace::record(crate::trace::TraceEvent::Switch {
+                from: scheduler.current_tid(),
+                to: Some(next_tid),
+            });
             *scheduler.current_tid_mut() = Some(next_tid);
 
             copy_registers(&scheduler.get_unchecked(next_tid).data, trap_frame);
@@ -164,4 +185,7 @@ unsafe fn sched(trap_frame: &mut TrapFrame) {
             break;
         }
     }
+
+    #[cfg(feature = "time-slicing")]
+    crate::time_slice::start_tick_if_needed();
 }
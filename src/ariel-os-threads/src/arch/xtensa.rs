@@ -85,7 +85,11 @@ extern "C" fn FROM_CPU_INTR0(trap_frame: &mut TrapFrame) {
             .cpu_intr_from_cpu_0()
             .modify(|_, w| w.cpu_intr_from_cpu_0().clear_bit());
 
-        sched(trap_frame)
+        #[cfg(feature = "trace")]
+        crate::trace::isr_enter(Interrupt::FROM_CPU_INTR0 as u16);
+        sched(trap_frame);
+        #[cfg(feature = "trace")]
+        crate::trace::isr_exit();
     }
 }
 
@@ -101,7 +105,11 @@ extern "C" fn FROM_CPU_INTR1(trap_frame: &mut TrapFrame) {
             .cpu_intr_from_cpu_1()
             .modify(|_, w| w.cpu_intr_from_cpu_1().clear_bit());
 
-        sched(trap_frame)
+        #[cfg(feature = "trace")]
+        crate::trace::isr_enter(Interrupt::FROM_CPU_INTR1 as u16);
+        sched(trap_frame);
+        #[cfg(feature = "trace")]
+        crate::trace::isr_exit();
     }
 }
 
@@ -115,20 +123,37 @@ extern "C" fn FROM_CPU_INTR1(trap_frame: &mut TrapFrame) {
 /// context switching.
 unsafe fn sched(trap_frame: &mut TrapFrame) {
     loop {
+        // The timer might wake up threads, so it runs before picking the next one.
+        #[cfg(feature = "time")]
+        crate::time::poll_timer_executor();
+
         if SCHEDULER.with_mut(|mut scheduler| {
             #[cfg(feature = "multi-core")]
             scheduler.add_current_thread_to_rq();
 
             let Some(next_tid) = scheduler.get_next_tid() else {
+                #[cfg(feature = "cpu-time")]
+                scheduler.account_cpu_time(None);
                 return false;
             };
+            #[cfg(feature = "cpu-time")]
+            scheduler.account_cpu_time(Some(next_tid));
 
             if let Some(current_tid) = scheduler.current_tid() {
                 if next_tid == current_tid {
                     return true;
                 }
                 scheduler.threads[usize::from(current_tid)].data = *trap_frame;
+                #[cfg(feature = "stack-overflow-check")]
+                scheduler.check_stack(current_tid, trap_frame.A1 as usize);
+                #[cfg(feature = "alloc")]
+                scheduler.release_stacks(current_tid);
             }
+            #[cfg(feature = "trace")]
+            crate::trace::record(crate::trace::TraceEvent::Switch {
+                from: scheduler.current_tid(),
+                to: Some(next_tid),
+            });
             *scheduler.current_tid_mut() = Some(next_tid);
 
             *trap_frame = scheduler.threads[usize::from(next_tid)].data;
@@ -141,4 +166,7 @@ unsafe fn sched(trap_frame: &mut TrapFrame) {
         // that another interrupt handler will try to borrow it while we still have it borrowed.
         Cpu::wfi()
     }
+
+    #[cfg(feature = "time-slicing")]
+    crate::time_slice::start_tick_if_needed();
 }
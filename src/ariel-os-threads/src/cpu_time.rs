@@ -0,0 +1,145 @@
+//! Accounting of the CPU time used by each thread.
+//!
+//! On every invocation of the scheduler, the time since the previous invocation is added
+//! to the thread that was running on that core, or to the idle time if the core was
+//! sleeping.
+//! On multi-core, cores don't sleep in the scheduler but run their idle threads, so
+//! idle time is accounted to the threads named "idle" instead.
+use embassy_time::{Duration, Instant};
+
+use crate::{CORE_COUNT, SCHEDULER, Scheduler, THREAD_COUNT, ThreadId, core_id};
+
+/// CPU time used by a thread, see [`cpu_time()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CpuTime {
+    /// Total time the thread has been running.
+    pub runtime: Duration,
+    /// Number of times the thread has been switched to.
+    pub switches: u32,
+}
+
+/// CPU time bookkeeping.
+#[derive(Debug)]
+pub(crate) struct CpuTimes {
+    /// Accumulated CPU time of each thread, indexed by [`ThreadId`].
+    threads: [CpuTime; THREAD_COUNT],
+    /// Accumulated time all cores have been sleeping.
+    idle: Duration,
+    /// What each core has been doing since its last accounting: running a thread, or
+    /// sleeping (`None`).
+    running: [Option<ThreadId>; CORE_COUNT],
+    /// Time of the last accounting on each core.
+    last: [Instant; CORE_COUNT],
+    /// Time of the last reset.
+    since: Instant,
+}
+
+impl CpuTimes {
+    /// Creates a new [`CpuTimes`].
+    pub const fn new() -> Self {
+        Self {
+            threads: [CpuTime {
+                runtime: Duration::from_ticks(0),
+                switches: 0,
+            }; THREAD_COUNT],
+            idle: Duration::from_ticks(0),
+            running: [None; CORE_COUNT],
+            last: [Instant::MIN; CORE_COUNT],
+            since: Instant::MIN,
+        }
+    }
+
+    /// Returns the time the current activity of `core` has been going on for, if the
+    /// core runs `thread_id` or sleeps (`None`).
+    fn ongoing(&self, core: usize, thread_id: Option<ThreadId>, now: Instant) -> Duration {
+        if self.running[core] == thread_id {
+            now.saturating_duration_since(self.last[core])
+        } else {
+            Duration::from_ticks(0)
+        }
+    }
+}
+
+/// Returns the CPU time used by a thread since it was created or since the last
+/// [`reset_cpu_times()`], whichever happened later.
+///
+/// Returns `None` if this is not a valid thread.
+pub fn cpu_time(thread_id: ThreadId) -> Option<CpuTime> {
+    let now = Instant::now();
+    SCHEDULER.with(|scheduler| {
+        if !scheduler.is_valid_tid(thread_id) {
+            return None;
+        }
+        let times = &scheduler.cpu_times;
+        let mut cpu_time = times.threads[usize::from(thread_id)];
+        for core in 0..CORE_COUNT {
+            cpu_time.runtime += times.ongoing(core, Some(thread_id), now);
+        }
+        Some(cpu_time)
+    })
+}
+
+/// Returns the time all cores together have been sleeping since the last
+/// [`reset_cpu_times()`].
+pub fn idle_time() -> Duration {
+    let now = Instant::now();
+    SCHEDULER.with(|scheduler| {
+        let times = &scheduler.cpu_times;
+        (0..CORE_COUNT).fold(times.idle, |idle, core| {
+            idle + times.ongoing(core, None, now)
+        })
+    })
+}
+
+/// Returns the time since the last [`reset_cpu_times()`], or since startup.
+///
+/// The CPU load of a thread is its [`CpuTime::runtime`] divided by this; on multi-core,
+/// also divided by [`CORE_COUNT`].
+pub fn cpu_time_elapsed() -> Duration {
+    let now = Instant::now();
+    SCHEDULER.with(|scheduler| now.saturating_duration_since(scheduler.cpu_times.since))
+}
+
+/// Resets the CPU times of all threads and the idle time.
+pub fn reset_cpu_times() {
+    let now = Instant::now();
+    SCHEDULER.with_mut(|mut scheduler| {
+        let times = &mut scheduler.cpu_times;
+        times.threads = [CpuTime::default(); THREAD_COUNT];
+        times.idle = Duration::from_ticks(0);
+        times.last = [now; CORE_COUNT];
+        times.since = now;
+    });
+}
+
+impl Scheduler {
+    /// Resets the CPU time of a newly created thread.
+    pub(crate) fn reset_cpu_time(&mut self, thread_id: ThreadId) {
+        self.cpu_times.threads[usize::from(thread_id)] = CpuTime::default();
+    }
+
+    /// Accounts the time since the last accounting on the current core, and records
+    /// that the core runs `next` from now on, or sleeps if `next` is `None`.
+    ///
+    /// This must be called on every invocation of the scheduler.
+    #[allow(dead_code, reason = "not used in all configurations")]
+    pub(crate) fn account_cpu_time(&mut self, next: Option<ThreadId>) {
+        let now = Instant::now();
+        let core = usize::from(core_id());
+        let times = &mut self.cpu_times;
+        let elapsed = now.saturating_duration_since(times.last[core]);
+        match times.running[core] {
+            Some(thread_id) => times.threads[usize::from(thread_id)].runtime += elapsed,
+            None => times.idle += elapsed,
+        }
+        if let Some(next) = next {
+            if times.running[core] != Some(next) {
+                let switches = &mut times.threads[usize::from(next)].switches;
+                *switches = switches.saturating_add(1);
+            }
+        }
+        times.running[core] = next;
+        times.last[core] = now;
+    }
+}
@@ -0,0 +1,315 @@
+//! Earliest-deadline-first (EDF) scheduling of periodic threads.
+//!
+//! A thread becomes an EDF thread by calling [`edf_start()`] with its period and relative
+//! deadline, which moves it to the [`EDF_PRIORITY`] band.
+//! Each period, the thread runs one job, which it completes by calling
+//! [`edf_wait_next_period()`]; the job's deadline is the start of the period plus the
+//! relative deadline.
+//!
+//! Among the ready threads with priority [`EDF_PRIORITY`], the one whose job has the
+//! earliest deadline runs.
+//! Threads with a higher priority still preempt EDF threads, and EDF threads preempt
+//! threads with a lower priority.
+//! Other threads that have priority [`EDF_PRIORITY`], e.g., because they inherited it
+//! through a [`Mutex`](crate::sync::Mutex), run before the EDF threads.
+//!
+//! A job that completes after its deadline is a deadline miss, which is logged and
+//! counted, see [`deadline_misses()`].
+use ariel_os_runqueue::DeadlineRunQueue;
+use embassy_time::{Duration, Instant};
+
+use crate::{
+    RunqueueId, SCHED_PRIO_LEVELS, SCHEDULER, Scheduler, THREAD_COUNT, ThreadId, schedule,
+    time::sleep_until,
+};
+
+#[cfg(feature = "multi-core")]
+use crate::{CORE_COUNT, CoreId, schedule_on_core};
+
+const EDF_PRIO: u8 = ariel_os_utils::u8_from_env_or!(
+    "CONFIG_THREAD_EDF_PRIORITY",
+    (SCHED_PRIO_LEVELS - 1) as u8,
+    "priority of EDF threads"
+);
+
+const _: () = assert!(
+    EDF_PRIO > 0 && (EDF_PRIO as usize) < SCHED_PRIO_LEVELS,
+    "`CONFIG_THREAD_EDF_PRIORITY` must be between 1 and `CONFIG_SCHED_PRIO_LEVELS` - 1"
+);
+
+/// Priority of EDF threads.
+///
+/// This defaults to the highest priority and can be configured using the
+/// `CONFIG_THREAD_EDF_PRIORITY` environment variable.
+pub const EDF_PRIORITY: RunqueueId = RunqueueId::new(EDF_PRIO);
+
+/// Timing of an EDF thread.
+#[derive(Clone, Copy, Debug)]
+struct EdfThread {
+    period: Duration,
+    /// Deadline relative to the start of each period.
+    deadline: Duration,
+    /// Start of the current period.
+    release: Instant,
+    /// Number of jobs that completed after their deadline.
+    misses: u32,
+    /// Base priority the thread had before [`edf_start()`].
+    prev_prio: RunqueueId,
+}
+
+impl EdfThread {
+    /// Returns the absolute deadline of the current job.
+    fn absolute_deadline(&self) -> Instant {
+        self.release + self.deadline
+    }
+}
+
+/// EDF bookkeeping.
+#[derive(Debug)]
+pub(crate) struct Edf {
+    /// All threads in state [`ThreadState::Running`](crate::ThreadState::Running), ordered
+    /// by the deadline of their current job.
+    ///
+    /// Threads that aren't EDF threads come first.
+    queue: DeadlineRunQueue<THREAD_COUNT>,
+    /// Timing of each EDF thread, indexed by [`ThreadId`].
+    threads: [Option<EdfThread>; THREAD_COUNT],
+}
+
+impl Edf {
+    /// Creates a new [`Edf`].
+    pub const fn new() -> Self {
+        Self {
+            queue: DeadlineRunQueue::new(),
+            threads: [None; THREAD_COUNT],
+        }
+    }
+
+    /// Returns the deadline a thread is ordered by: the absolute deadline of its current
+    /// job for EDF threads, `0` for other threads.
+    fn deadline(&self, thread_id: ThreadId) -> u64 {
+        self.threads[usize::from(thread_id)]
+            .map_or(0, |thread| thread.absolute_deadline().as_ticks())
+    }
+
+    /// Updates the position of a thread in the queue after its deadline changed, if it
+    /// is queued.
+    fn requeue(&mut self, thread_id: ThreadId) {
+        if self.queue.deadline(thread_id).is_some() {
+            self.queue.del(thread_id);
+            self.queue.add(thread_id, self.deadline(thread_id));
+        }
+    }
+}
+
+/// Makes the current thread an EDF thread that runs one job every `period`, each of
+/// which has to complete within `deadline`.
+///
+/// The first period starts now.
+/// The thread's base priority is set to [`EDF_PRIORITY`] until [`edf_stop()`].
+///
+/// This might trigger a context switch.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context, or if `deadline` is zero or
+/// longer than `period`.
+pub fn edf_start(period: Duration, deadline: Duration) {
+    assert!(
+        deadline > Duration::from_ticks(0) && deadline <= period,
+        "EDF deadline must be non-zero and not longer than the period"
+    );
+    let release = Instant::now();
+    SCHEDULER.with_mut(|mut scheduler| {
+        let thread_id = scheduler
+            .current_tid()
+            .expect("Function should be called inside a thread context.");
+        let prev_prio = match scheduler.edf.threads[usize::from(thread_id)] {
+            Some(thread) => thread.prev_prio,
+            None => scheduler.get_unchecked(thread_id).base_prio,
+        };
+        scheduler.edf.threads[usize::from(thread_id)] = Some(EdfThread {
+            period,
+            deadline,
+            release,
+            misses: 0,
+            prev_prio,
+        });
+        scheduler.edf.requeue(thread_id);
+        scheduler.set_base_priority(thread_id, EDF_PRIORITY);
+        // Another EDF thread might have an earlier deadline.
+        schedule();
+    });
+}
+
+/// Completes the current job of the current EDF thread and waits for the next period
+/// to start.
+///
+/// If the job completed after its deadline, the miss is logged and counted.
+/// If the job overran its whole period, the next job starts right away.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context, or if the current thread is not
+/// an EDF thread.
+pub fn edf_wait_next_period() {
+    let now = Instant::now();
+    let release = SCHEDULER.with_mut(|mut scheduler| {
+        let thread_id = scheduler
+            .current_tid()
+            .expect("Function should be called inside a thread context.");
+        let edf = &mut scheduler.edf;
+        let thread = edf.threads[usize::from(thread_id)]
+            .as_mut()
+            .expect("Function should be called from an EDF thread.");
+        let deadline = thread.absolute_deadline();
+        if now > deadline {
+            thread.misses = thread.misses.saturating_add(1);
+            #[allow(
+                unused_variables,
+                reason = "only used for logging, which might be disabled"
+            )]
+            let late = (now - deadline).as_micros();
+            ariel_os_debug::log::warn!(
+                "thread {} missed its deadline by {}us",
+                usize::from(thread_id),
+                late
+            );
+        }
+        thread.release += thread.period;
+        let release = thread.release;
+        edf.requeue(thread_id);
+        if release <= now {
+            // The thread doesn't sleep, but other EDF threads might have an earlier
+            // deadline now.
+            schedule();
+        }
+        release
+    });
+    sleep_until(release);
+}
+
+/// Turns the current thread back into a regular thread.
+///
+/// Its base priority is restored to what it was before [`edf_start()`].
+/// Does nothing if the current thread is not an EDF thread.
+///
+/// This might trigger a context switch.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn edf_stop() {
+    SCHEDULER.with_mut(|mut scheduler| {
+        let thread_id = scheduler
+            .current_tid()
+            .expect("Function should be called inside a thread context.");
+        let Some(thread) = scheduler.edf.threads[usize::from(thread_id)].take() else {
+            return;
+        };
+        scheduler.edf.requeue(thread_id);
+        scheduler.set_base_priority(thread_id, thread.prev_prio);
+    });
+}
+
+/// Returns the number of jobs of an EDF thread that completed after their deadline.
+///
+/// Returns `None` if this is not a valid EDF thread.
+pub fn deadline_misses(thread_id: ThreadId) -> Option<u32> {
+    SCHEDULER.with(|scheduler| {
+        if !scheduler.is_valid_tid(thread_id) {
+            return None;
+        }
+        scheduler.edf.threads[usize::from(thread_id)].map(|thread| thread.misses)
+    })
+}
+
+impl Scheduler {
+    /// Resets the EDF timing of a newly created thread.
+    pub(crate) fn reset_edf(&mut self, thread_id: ThreadId) {
+        self.edf.threads[usize::from(thread_id)] = None;
+        self.edf.queue.del(thread_id);
+    }
+
+    /// Adds a thread that became ready to the EDF queue, and triggers the scheduler if
+    /// it should preempt a running thread with the same priority.
+    pub(crate) fn edf_add(&mut self, thread_id: ThreadId) {
+        let deadline = self.edf.deadline(thread_id);
+        self.edf.queue.add(thread_id, deadline);
+        if self.get_unchecked(thread_id).prio != EDF_PRIORITY {
+            return;
+        }
+
+        #[cfg(not(feature = "multi-core"))]
+        if let Some(current) = self.current_tid() {
+            if self.edf_preempts(thread_id, deadline, current) {
+                schedule();
+            }
+        }
+
+        #[cfg(feature = "multi-core")]
+        {
+            #[cfg(feature = "core-affinity")]
+            let affinity = self.get_unchecked(thread_id).core_affinity;
+            // Preempt the running thread with the latest deadline.
+            let core = (0..CORE_COUNT)
+                .filter_map(|core| {
+                    let current = self.current_threads[core]?;
+                    #[cfg(feature = "core-affinity")]
+                    if !affinity.contains(CoreId(core as u8)) {
+                        return None;
+                    }
+                    self.edf_preempts(thread_id, deadline, current)
+                        .then(|| (core, self.edf.deadline(current)))
+                })
+                .max_by_key(|&(_, deadline)| deadline);
+            if let Some((core, _)) = core {
+                schedule_on_core(CoreId(core as u8));
+            }
+        }
+    }
+
+    /// Removes a thread that stopped being ready from the EDF queue.
+    pub(crate) fn edf_del(&mut self, thread_id: ThreadId) {
+        self.edf.queue.del(thread_id);
+    }
+
+    /// Checks whether the thread with `deadline` should run instead of the running
+    /// thread `current`.
+    fn edf_preempts(&self, thread_id: ThreadId, deadline: u64, current: ThreadId) -> bool {
+        current != thread_id
+            && self.get_unchecked(current).prio == EDF_PRIORITY
+            && self.edf.threads[usize::from(current)].is_some()
+            && self.edf.deadline(current) > deadline
+    }
+
+    /// Returns the thread that should run instead of `next`, the next thread from the
+    /// runqueue.
+    ///
+    /// If `next` has priority [`EDF_PRIORITY`], this is the ready thread with that priority
+    /// whose job has the earliest deadline.
+    #[allow(dead_code, reason = "used in scheduler implementation")]
+    pub(crate) fn edf_pick(&self, next: ThreadId) -> ThreadId {
+        if self.get_unchecked(next).prio != EDF_PRIORITY {
+            return next;
+        }
+        let Some(earliest) = self.edf.queue.get_next_filter(|&thread_id| {
+            // Threads that are running on other cores are not in the runqueue.
+            #[cfg(feature = "multi-core")]
+            if self
+                .is_running(thread_id)
+                .is_some_and(|core| core != usize::from(crate::core_id()))
+            {
+                return false;
+            }
+            #[cfg(feature = "core-affinity")]
+            if !self.is_affine_to_curr_core(thread_id) {
+                return false;
+            }
+            self.get_unchecked(thread_id).prio == EDF_PRIORITY
+        }) else {
+            return next;
+        };
+        earliest
+    }
+}
@@ -28,6 +28,15 @@ impl<T> EnsureOnce<T> {
         with(|cs| self.with_mut_cs(cs, f))
     }
 
+    /// Like [`Self::with()`], but returns `None` instead of panicking if the inner value
+    /// is currently borrowed mutably.
+    pub fn try_with<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(Ref<'_, T>) -> R,
+    {
+        with(|cs| self.inner.borrow(cs).try_borrow().ok().map(f))
+    }
+
     pub fn with_cs<F, R>(&self, cs: CriticalSection<'_>, f: F) -> R
     where
         F: FnOnce(Ref<'_, T>) -> R,
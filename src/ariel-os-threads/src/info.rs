@@ -0,0 +1,144 @@
+//! Introspection of the threads known to the scheduler.
+#[cfg(feature = "core-affinity")]
+use crate::CoreAffinity;
+use crate::{
+    RunqueueId, SCHEDULER, THREAD_COUNT, ThreadId, ThreadState, thread::STACK_PAINT_COLOR,
+};
+
+/// Snapshot of a thread's scheduling and stack information, see [`threads()`].
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ThreadInfo {
+    /// Id of the thread.
+    pub tid: ThreadId,
+    /// Name of the thread, if any.
+    pub name: Option<&'static str>,
+    /// State of the thread, including what it is blocked on.
+    pub state: ThreadState,
+    /// Current priority of the thread, including priority inheritance.
+    pub prio: RunqueueId,
+    /// Priority of the thread without priority inheritance.
+    pub base_prio: RunqueueId,
+    /// Core affinity of the thread.
+    #[cfg(feature = "core-affinity")]
+    pub core_affinity: CoreAffinity,
+    /// Size of the thread's stack in bytes.
+    ///
+    /// This is zero on architectures where stacks aren't managed by the scheduler.
+    pub stack_size: usize,
+    /// Maximum stack space used by the thread so far, in bytes.
+    ///
+    /// This is computed from the stack paint, and so is a lower bound.
+    pub stack_used_max: usize,
+}
+
+impl ThreadInfo {
+    /// Returns whether the thread currently runs with an inherited priority.
+    #[must_use]
+    pub fn is_inheriting(&self) -> bool {
+        self.prio > self.base_prio
+    }
+
+    /// Returns the remaining stack space that has never been used, in bytes.
+    #[must_use]
+    pub fn stack_free_min(&self) -> usize {
+        self.stack_size - self.stack_used_max
+    }
+}
+
+/// Iterator over snapshots of all threads, see [`threads()`].
+#[derive(Debug)]
+pub struct Threads {
+    next: usize,
+}
+
+impl Iterator for Threads {
+    type Item = ThreadInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < THREAD_COUNT {
+            let thread_id = ThreadId::new(self.next as u8);
+            self.next += 1;
+            if let Some(info) = snapshot(thread_id) {
+                return Some(info);
+            }
+        }
+        None
+    }
+}
+
+/// Returns an iterator over snapshots of all existing threads, ordered by [`ThreadId`].
+///
+/// Each thread is captured at the time the iterator reaches it, so the snapshots of
+/// different threads are not necessarily consistent with each other.
+///
+/// Computing the stack usage reads a thread's whole unused stack, which takes
+/// `O(stack size)`.
+#[must_use]
+pub fn threads() -> Threads {
+    Threads { next: 0 }
+}
+
+/// Number of stack bytes that are scanned within one critical section.
+const SCAN_CHUNK_SIZE: usize = 64;
+
+/// Returns a snapshot of a thread, or `None` if the thread doesn't exist.
+fn snapshot(thread_id: ThreadId) -> Option<ThreadInfo> {
+    let (mut info, stack_lowest) = SCHEDULER.with(|scheduler| {
+        if !scheduler.is_valid_tid(thread_id) {
+            return None;
+        }
+        let thread = scheduler.get_unchecked(thread_id);
+        let info = ThreadInfo {
+            tid: thread_id,
+            name: thread.name,
+            state: thread.state,
+            prio: thread.prio,
+            base_prio: thread.base_prio,
+            #[cfg(feature = "core-affinity")]
+            core_affinity: thread.core_affinity,
+            stack_size: thread.stack_highest - thread.stack_lowest,
+            stack_used_max: 0,
+        };
+        Some((info, thread.stack_lowest))
+    })?;
+    let painted = stack_paint_remaining(thread_id, stack_lowest, info.stack_size)?;
+    info.stack_used_max = info.stack_size - painted;
+    Some(info)
+}
+
+/// Returns the number of bytes at the lowest end of a thread's stack that still have the
+/// stack paint.
+///
+/// The stack is scanned in chunks, each within its own critical section, to not block
+/// interrupts for too long.
+/// Returns `None` if the thread finished during the scan, as its stack might have been
+/// freed, see `spawn()`.
+fn stack_paint_remaining(thread_id: ThreadId, lowest: usize, size: usize) -> Option<usize> {
+    let mut painted = 0;
+    while painted < size {
+        let chunk_end = (painted + SCAN_CHUNK_SIZE).min(size);
+        let chunk_painted = SCHEDULER.with(|scheduler| {
+            if !scheduler.is_valid_tid(thread_id)
+                || scheduler.get_unchecked(thread_id).stack_lowest != lowest
+            {
+                return None;
+            }
+            let chunk = (lowest + painted..lowest + chunk_end)
+                .take_while(|&pos| {
+                    // SAFETY: the thread still exists and uses this stack, and stacks are
+                    // only freed by the scheduler, which is borrowed here. Reading a byte of a
+                    // stack is fine even if another thread writes to it concurrently.
+                    let byte = unsafe { core::ptr::read_volatile(pos as *const u8) };
+                    byte == STACK_PAINT_COLOR
+                })
+                .count();
+            Some(chunk)
+        })?;
+        painted += chunk_painted;
+        if painted < chunk_end {
+            break;
+        }
+    }
+    Some(painted)
+}
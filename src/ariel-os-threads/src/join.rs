@@ -0,0 +1,202 @@
+//! Joining threads and retrieving their exit values.
+use core::task::Poll;
+
+use crate::{Arch, Cpu, SCHEDULER, Scheduler, THREAD_COUNT, ThreadId, ThreadState};
+
+/// Handle to wait for a thread to finish and to retrieve its exit value.
+///
+/// [`ThreadId`]s are reused once a thread has finished.
+/// A [`JoinHandle`] additionally carries the generation of its thread slot, so that it
+/// keeps referring to the thread it was created for even after the slot is reused.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct JoinHandle {
+    thread_id: ThreadId,
+    generation: u16,
+}
+
+impl JoinHandle {
+    /// Returns the [`ThreadId`] of the thread.
+    ///
+    /// Note that the id might already refer to a different thread if this thread has
+    /// finished.
+    #[must_use]
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// Returns whether the thread has finished.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        SCHEDULER.with(|scheduler| {
+            scheduler.join_slots[usize::from(self.thread_id)].is_finished(self.generation)
+        })
+    }
+
+    /// Waits for the thread to finish (blocking).
+    ///
+    /// Returns the exit value of the thread, see [`exit()`].
+    /// Returns `None` if the exit value is not available anymore, which happens when
+    /// the thread's slot has been reused by a thread that has finished as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context, or by the thread that is
+    /// being joined.
+    pub fn join(self) -> Option<usize> {
+        loop {
+            if let Poll::Ready(value) = SCHEDULER.with_mut(|mut scheduler| scheduler.join(&self)) {
+                return value;
+            }
+        }
+    }
+
+    /// Returns the exit value of the thread if it has finished (non-blocking).
+    ///
+    /// Returns `None` if the thread has not finished yet, or if the exit value is not
+    /// available anymore, see [`Self::join()`].
+    #[must_use]
+    pub fn try_join(&self) -> Option<usize> {
+        SCHEDULER.with(|scheduler| {
+            scheduler.join_slots[usize::from(self.thread_id)].exit_value(self.generation)
+        })
+    }
+}
+
+/// Ends the current thread with exit value `value`.
+///
+/// The exit value can be retrieved with [`JoinHandle::join()`].
+/// Returning from the thread function is equivalent to calling `exit(0)`.
+///
+/// Destructors of values that live on the thread's stack are not run.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn exit(value: usize) -> ! {
+    Cpu::exit(value)
+}
+
+/// Join bookkeeping of a single thread slot.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct JoinSlot {
+    /// Generation of the most recently created thread in this slot.
+    generation: u16,
+    /// Generation and exit value of the most recently finished thread in this slot.
+    exited: Option<(u16, usize)>,
+}
+
+impl JoinSlot {
+    /// Creates a new [`JoinSlot`].
+    pub const fn new() -> Self {
+        Self {
+            generation: 0,
+            exited: None,
+        }
+    }
+
+    /// Starts a new generation for a newly created thread and returns it.
+    pub fn start(&mut self) -> u16 {
+        self.generation = self.generation.wrapping_add(1);
+        self.generation
+    }
+
+    /// Returns the generation of the most recently created thread.
+    pub fn generation(&self) -> u16 {
+        self.generation
+    }
+
+    /// Records the exit value of the current generation.
+    pub fn finish(&mut self, value: usize) {
+        self.exited = Some((self.generation, value));
+    }
+
+    /// Returns whether the thread of `generation` has finished.
+    pub fn is_finished(&self, generation: u16) -> bool {
+        // A newer generation can only exist after the previous thread finished.
+        generation != self.generation || self.exit_value(generation).is_some()
+    }
+
+    /// Returns the exit value of the thread of `generation`, if still available.
+    pub fn exit_value(&self, generation: u16) -> Option<usize> {
+        self.exited
+            .filter(|(exited, _)| *exited == generation)
+            .map(|(_, value)| value)
+    }
+}
+
+impl Scheduler {
+    /// Starts a new generation for the thread slot of `thread_id` and returns a
+    /// [`JoinHandle`] for it.
+    pub(crate) fn new_join_handle(&mut self, thread_id: ThreadId) -> JoinHandle {
+        let generation = self.join_slots[usize::from(thread_id)].start();
+        JoinHandle {
+            thread_id,
+            generation,
+        }
+    }
+
+    /// Ends a thread with exit value `value` and wakes up all threads joining it.
+    pub(crate) fn exit(&mut self, thread_id: ThreadId, value: usize) {
+        self.join_slots[usize::from(thread_id)].finish(value);
+        for i in 0..THREAD_COUNT {
+            if self.threads[i].state == ThreadState::JoinBlocked(thread_id) {
+                self.set_state(ThreadId::new(i as u8), ThreadState::Running);
+            }
+        }
+        #[cfg(feature = "msg")]
+        crate::msg::close_mailbox(self, thread_id);
+        self.set_state(thread_id, ThreadState::Invalid);
+        // Native threads don't run on their stacks, so they can be freed right away.
+        #[cfg(all(feature = "alloc", feature = "infini-core"))]
+        self.free_stack(thread_id);
+    }
+
+    /// Returns the exit value if the thread of `handle` has finished, otherwise blocks
+    /// the current thread until it does.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context, or by the thread that is
+    /// being joined.
+    fn join(&mut self, handle: &JoinHandle) -> Poll<Option<usize>> {
+        let slot = &self.join_slots[usize::from(handle.thread_id)];
+        if slot.is_finished(handle.generation) {
+            return Poll::Ready(slot.exit_value(handle.generation));
+        }
+        let thread_id = self
+            .current_tid()
+            .expect("Function should be called inside a thread context.");
+        assert_ne!(thread_id, handle.thread_id, "a thread cannot join itself");
+        self.set_state(thread_id, ThreadState::JoinBlocked(handle.thread_id));
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_slot_generations() {
+        let mut slot = JoinSlot::new();
+        let first = slot.start();
+        assert!(!slot.is_finished(first));
+        assert_eq!(slot.exit_value(first), None);
+
+        slot.finish(42);
+        assert!(slot.is_finished(first));
+        assert_eq!(slot.exit_value(first), Some(42));
+
+        // Reusing the slot doesn't affect the previous generation until it finishes.
+        let second = slot.start();
+        assert!(slot.is_finished(first));
+        assert!(!slot.is_finished(second));
+        assert_eq!(slot.exit_value(first), Some(42));
+
+        slot.finish(7);
+        assert!(slot.is_finished(first));
+        assert_eq!(slot.exit_value(first), None);
+        assert_eq!(slot.exit_value(second), Some(7));
+    }
+}
@@ -2,24 +2,69 @@
 //!
 //! Implements a scheduler based on fixed priorities and preemption.
 //! Within one priority level, threads are scheduled cooperatively.
-//! This means that there is no time slicing that would equally distribute CPU time among same-priority threads.
+//! This means that by default there is no time slicing that would equally distribute CPU time among same-priority threads.
 //! **Instead, you need to use [`yield_same()`] to explicitly yield to another thread with the same priority.**
+//! With the `time-slicing` feature enabled, a thread that has run for longer than its time
+//! slice (see `set_time_slice()`) is preempted in favor of other ready threads with the same priority.
 //! If no thread is ready, the core is prompted to enter deep sleep until a next thread is ready.
 //!
+//! With the `time` feature enabled, threads can block for a given time using `sleep()`
+//! and `sleep_until()`, and the blocking synchronization primitives provide `*_timeout()`
+//! variants that return a `TimeoutError` when their timeout expires.
+//! The deadlines are handled by an embassy task that threads cannot delay, which requires an
+//! executor other than `executor-none`.
+//!
 //! Threads should be implemented using the `ariel_os_macros::thread` proc macro, which takes care
 //! of calling the necessary initialization methods and linking the thread function element it into the binary.
 //! A [`ThreadId`] between 0 and [`THREAD_COUNT`] is assigned to each thread in the order in
 //! which the threads are declared.
+//! Threads created with [`create_joinable()`] can be waited for using [`JoinHandle::join()`],
+//! which also returns the value the thread passed to [`exit()`].
+//! [`threads()`] lists all threads with their state, priorities and stack usage, e.g., for
+//! diagnostics.
+//! Per-thread state can be declared with [`thread_local!`], and is reset when a thread's
+//! [`ThreadId`] is reused.
+//!
+//! With the `alloc` feature enabled, `spawn()` creates threads with stacks allocated on the
+//! heap, which are freed again after the thread has finished.
+//!
+//! With the `stack-overflow-check` feature enabled, the stack of each thread is checked for
+//! overflows whenever it is switched out, see `set_stack_overflow_hook()`.
+//! This is not supported on native, where threads run on stacks managed by the host OS.
+//!
+//! With the `msg` feature enabled, threads can exchange small messages in the style of RIOT,
+//! see `msg`.
+//!
+//! With the `deadlock-detection` feature enabled, locking a [`Mutex`](sync::Mutex) panics if
+//! it would deadlock; `lock_checked()` returns an error instead.
+//! As a [`Lock`](sync::Lock) may be released by a thread other than the one that acquired it,
+//! it is only checked through `acquire_checked()`.
+//!
+//! With the `watchdog` feature enabled, threads can be supervised by a software watchdog
+//! that detects threads that stopped making progress, see `watchdog`.
+//!
+//! With the `trace` feature enabled, scheduler events are recorded for inspection on the host,
+//! see `trace`.
+//!
+//! With the `cpu-time` feature enabled, the CPU time used by each thread is accounted, see
+//! `cpu_time()`.
+//!
+//! With the `edf` feature enabled, periodic threads can be scheduled earliest-deadline-first
+//! within the `EDF_PRIORITY` band, see `edf_start()`.
 //!
 //! Optionally, the stacksize and a priority between 1 and [`SCHED_PRIO_LEVELS`] can be configured.
 //! By default, the stack size is 2048 bytes and priority is 1.
 //!
 //! # Synchronization
 //!
-//! The `threading` module supports three basic synchronization primitives:
+//! The `threading` module supports these basic synchronization primitives:
 //! - [`Channel`](sync::Channel): synchronous (blocking) channel for sending data between threads
+//! - [`Queue`](sync::Queue): bounded (blocking) queue for sending data between threads
 //! - [`Lock`](sync::Lock): basic locking object
+//! - [`Mutex`](sync::Mutex): mutex with priority inheritance, with [`Condvar`](sync::Condvar) to wait for conditions
+//! - [`Semaphore`](sync::Semaphore): counting semaphore
 //! - [`thread_flags`]: thread-flag implementation for signaling between threads
+//! - `msg`: message passing between threads, if the `msg` feature is enabled
 
 #![cfg_attr(not(any(test, context = "native")), no_std)]
 #![cfg_attr(target_arch = "xtensa", feature(asm_experimental_arch))]
@@ -29,17 +74,41 @@
 #![allow(clippy::indexing_slicing)]
 #![expect(clippy::cast_possible_truncation)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 mod arch;
 mod autostart_thread;
+#[cfg(all(feature = "cpu-time", not(feature = "infini-core")))]
+mod cpu_time;
+#[cfg(all(feature = "edf", not(feature = "infini-core")))]
+mod edf;
 mod ensure_once;
+mod info;
+mod join;
+#[cfg(feature = "alloc")]
+mod spawn;
+#[cfg(feature = "stack-overflow-check")]
+mod stack_check;
 mod thread;
+mod thread_local;
 mod threadlist;
+#[cfg(feature = "time")]
+mod time;
+#[cfg(all(feature = "time-slicing", not(feature = "infini-core")))]
+mod time_slice;
 
 #[cfg(feature = "multi-core")]
 mod smp;
 
+#[cfg(feature = "msg")]
+pub mod msg;
 pub mod sync;
 pub mod thread_flags;
+#[cfg(feature = "trace")]
+pub mod trace;
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
 
 #[doc(hidden)]
 pub mod macro_reexports {
@@ -54,10 +123,30 @@ pub mod events {
     use crate::sync::Event;
     // this is set in `ariel_os_embassy::init_task()`
     pub static THREAD_START_EVENT: Event = Event::new();
+
+    // this is spawned in `ariel_os_embassy::init_task()`
+    #[cfg(feature = "time")]
+    pub use crate::time::{pend_timer_executor, run_timer, set_timer_executor};
 }
 
 pub use ariel_os_runqueue::{RunqueueId, ThreadId};
+#[cfg(all(feature = "cpu-time", not(feature = "infini-core")))]
+pub use cpu_time::{CpuTime, cpu_time, cpu_time_elapsed, idle_time, reset_cpu_times};
+#[cfg(all(feature = "edf", not(feature = "infini-core")))]
+pub use edf::{EDF_PRIORITY, deadline_misses, edf_start, edf_stop, edf_wait_next_period};
+pub use info::{ThreadInfo, Threads, threads};
+pub use join::{JoinHandle, exit};
+#[cfg(feature = "alloc")]
+pub use spawn::{SpawnError, spawn};
+#[cfg(feature = "stack-overflow-check")]
+pub use stack_check::{CANARY_SIZE, StackOverflowHook, set_stack_overflow_hook};
+pub use thread::ThreadState;
 pub use thread_flags as flags;
+pub use thread_local::ThreadLocal;
+#[cfg(feature = "time")]
+pub use time::{TimeoutError, sleep, sleep_until};
+#[cfg(all(feature = "time-slicing", not(feature = "infini-core")))]
+pub use time_slice::{DEFAULT_TIME_SLICE, get_time_slice, set_time_slice};
 
 #[cfg(feature = "core-affinity")]
 pub use smp::CoreAffinity;
@@ -67,10 +156,10 @@ pub use smp::isr_stack_core1_get_limits;
 use arch::{Arch, Cpu, ThreadData, schedule};
 
 #[cfg(not(feature = "infini-core"))]
-use ariel_os_runqueue::RunQueue;
+use ariel_os_runqueue::{RunQueue, bitcache_words};
 
 use ensure_once::EnsureOnce;
-use thread::{Thread, ThreadState};
+use thread::Thread;
 
 #[cfg(feature = "multi-core")]
 use smp::{Multicore, schedule_on_core};
@@ -80,17 +169,88 @@ use static_cell::ConstStaticCell;
 /// Dummy type that is needed because [`CoreAffinity`] is part of the general API.
 ///
 /// To configure core affinities for threads, the `core-affinity` feature must be enabled.
-#[cfg(not(feature = "core-affinity"))]
+#[cfg(not(any(feature = "core-affinity", feature = "infini-core")))]
 pub struct CoreAffinity {
     // Phantom field to ensure that `CoreAffinity` can never be constructed by a user.
     _phantom: core::marker::PhantomData<()>,
 }
 
+/// Affinity mask that defines on what cores a thread can be scheduled.
+///
+/// On native, each thread runs on its own host thread, so the affinity of a thread is
+/// stored, but doesn't restrict where it runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg(feature = "infini-core")]
+pub struct CoreAffinity(u8);
+
+#[cfg(feature = "infini-core")]
+impl CoreAffinity {
+    /// Allows a thread to be scheduled on any core.
+    #[must_use]
+    pub const fn no_affinity() -> Self {
+        Self(u8::MAX)
+    }
+
+    /// Restricts the thread execution to a specific core.
+    #[must_use]
+    pub fn one(core: CoreId) -> Self {
+        Self(1 << core.0)
+    }
+
+    /// Checks if the affinity mask "allows" this `core`.
+    #[must_use]
+    pub fn contains(&self, core: CoreId) -> bool {
+        self.0 & (1 << core.0) > 0
+    }
+}
+
+#[cfg(feature = "infini-core")]
+impl Default for CoreAffinity {
+    fn default() -> Self {
+        Self::no_affinity()
+    }
+}
+
 /// The number of possible priority levels.
-pub const SCHED_PRIO_LEVELS: usize = THREAD_COUNT;
+///
+/// This defaults to 16 and can be configured using the `CONFIG_SCHED_PRIO_LEVELS`
+/// environment variable, up to [`MAX_QUEUES`](ariel_os_runqueue::MAX_QUEUES).
+pub const SCHED_PRIO_LEVELS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SCHED_PRIO_LEVELS",
+    16,
+    "number of thread priority levels"
+);
 
 /// The maximum number of concurrent threads that can be created.
-pub const THREAD_COUNT: usize = 16;
+///
+/// This defaults to 16 and can be configured using the `CONFIG_THREAD_COUNT`
+/// environment variable, up to [`MAX_THREADS`](ariel_os_runqueue::MAX_THREADS).
+pub const THREAD_COUNT: usize =
+    ariel_os_utils::usize_from_env_or!("CONFIG_THREAD_COUNT", 16, "maximum number of threads");
+
+const _: () = {
+    assert!(
+        THREAD_COUNT <= ariel_os_runqueue::MAX_THREADS,
+        "`CONFIG_THREAD_COUNT` is too large"
+    );
+    assert!(
+        SCHED_PRIO_LEVELS <= ariel_os_runqueue::MAX_QUEUES,
+        "`CONFIG_SCHED_PRIO_LEVELS` is too large"
+    );
+    // Threads are created with priority 1 by default; 0 is the idle priority.
+    assert!(
+        SCHED_PRIO_LEVELS >= 2,
+        "`CONFIG_SCHED_PRIO_LEVELS` must be at least 2"
+    );
+    assert!(THREAD_COUNT > 0, "`CONFIG_THREAD_COUNT` must not be 0");
+    // Every core needs an idle thread.
+    #[cfg(feature = "multi-core")]
+    assert!(
+        THREAD_COUNT > CORE_COUNT,
+        "`CONFIG_THREAD_COUNT` must be larger than the number of cores"
+    );
+};
 
 /// Number of processor cores.
 pub const CORE_COUNT: usize = {
@@ -117,13 +277,33 @@ pub static THREAD_FNS: [ThreadFn] = [..];
 struct Scheduler {
     /// Global thread runqueue.
     #[cfg(not(feature = "infini-core"))]
-    runqueue: RunQueue<SCHED_PRIO_LEVELS, THREAD_COUNT>,
+    runqueue: RunQueue<SCHED_PRIO_LEVELS, THREAD_COUNT, { bitcache_words(SCHED_PRIO_LEVELS) }>,
 
     /// The actual TCBs.
     threads: [Thread; THREAD_COUNT],
     /// `Some` when a thread is blocking another thread due to conflicting
     /// resource access.
     thread_blocklist: [Option<ThreadId>; THREAD_COUNT],
+    /// Threads that wait for a deadline.
+    #[cfg(feature = "time")]
+    timer_queue: time::TimerQueue,
+    /// Time slices of the threads.
+    #[cfg(all(feature = "time-slicing", not(feature = "infini-core")))]
+    time_slicer: time_slice::TimeSlicer,
+    /// Join bookkeeping, indexed by [`ThreadId`].
+    join_slots: [join::JoinSlot; THREAD_COUNT],
+    /// CPU time used by the threads.
+    #[cfg(all(feature = "cpu-time", not(feature = "infini-core")))]
+    cpu_times: cpu_time::CpuTimes,
+    /// Deadlines of the ready threads for earliest-deadline-first scheduling.
+    #[cfg(all(feature = "edf", not(feature = "infini-core")))]
+    edf: edf::Edf,
+    /// Address of the [`Lock`](sync::Lock) each thread waits for, if it does.
+    #[cfg(feature = "deadlock-detection")]
+    waiting_for_lock: [Option<usize>; THREAD_COUNT],
+    /// Stacks of threads created with [`spawn()`].
+    #[cfg(feature = "alloc")]
+    heap_stacks: spawn::HeapStacks,
 
     /// The currently running thread(s).
     #[cfg(feature = "multi-core")]
@@ -139,6 +319,19 @@ impl Scheduler {
             runqueue: RunQueue::new(),
             threads: [const { Thread::default() }; THREAD_COUNT],
             thread_blocklist: [const { None }; THREAD_COUNT],
+            #[cfg(feature = "time")]
+            timer_queue: time::TimerQueue::new(),
+            #[cfg(all(feature = "time-slicing", not(feature = "infini-core")))]
+            time_slicer: time_slice::TimeSlicer::new(),
+            join_slots: [const { join::JoinSlot::new() }; THREAD_COUNT],
+            #[cfg(all(feature = "cpu-time", not(feature = "infini-core")))]
+            cpu_times: cpu_time::CpuTimes::new(),
+            #[cfg(all(feature = "edf", not(feature = "infini-core")))]
+            edf: edf::Edf::new(),
+            #[cfg(feature = "deadlock-detection")]
+            waiting_for_lock: [None; THREAD_COUNT],
+            #[cfg(feature = "alloc")]
+            heap_stacks: spawn::HeapStacks::new(),
             #[cfg(feature = "multi-core")]
             current_threads: [None; CORE_COUNT],
             #[cfg(feature = "single-core")]
@@ -208,27 +401,46 @@ impl Scheduler {
     /// This sets up the stack and TCB for this thread.
     ///
     /// Returns `None` if there is no free thread slot.
+    #[allow(
+        clippy::needless_pass_by_value,
+        reason = "core affinity only used in some configurations"
+    )]
     fn create(
         &mut self,
         func: fn(),
         arg: Option<usize>,
         stack: &'static mut [u8],
         prio: RunqueueId,
-        _core_affinity: Option<CoreAffinity>,
+        core_affinity: Option<CoreAffinity>,
     ) -> Option<ThreadId> {
         let (thread, tid) = self.get_unused()?;
         thread.prio = prio;
+        thread.base_prio = prio;
         thread.tid = tid;
+        thread.name = None;
         thread.state = ThreadState::Parked;
 
         // At least native needs the `tid` field populated, so we call this
         // after populating `thread` with the already known info.
         Cpu::setup_stack(thread, stack, func, arg);
 
-        #[cfg(feature = "core-affinity")]
+        #[cfg(any(feature = "core-affinity", feature = "infini-core"))]
         {
-            thread.core_affinity = _core_affinity.unwrap_or_default();
+            thread.core_affinity = core_affinity.unwrap_or_default();
         }
+        #[cfg(not(any(feature = "core-affinity", feature = "infini-core")))]
+        let _ = core_affinity;
+
+        #[cfg(all(feature = "time-slicing", not(feature = "infini-core")))]
+        self.reset_time_slice(tid);
+        #[cfg(all(feature = "cpu-time", not(feature = "infini-core")))]
+        self.reset_cpu_time(tid);
+        #[cfg(all(feature = "edf", not(feature = "infini-core")))]
+        self.reset_edf(tid);
+        #[cfg(feature = "msg")]
+        msg::reset_mailbox(tid);
+        #[cfg(feature = "watchdog")]
+        watchdog::reset_registration(tid);
 
         Some(tid)
     }
@@ -256,11 +468,24 @@ impl Scheduler {
     }
 
     /// Returns an unused [`ThreadId`] / Thread slot.
+    ///
+    /// Skips slots of finished threads that are still current on a core, or whose
+    /// heap-allocated stack hasn't been freed yet.
     fn get_unused(&mut self) -> Option<(&mut Thread, ThreadId)> {
         for i in 0..THREAD_COUNT {
-            if self.threads[i].state == ThreadState::Invalid {
-                return Some((&mut self.threads[i], ThreadId::new(i as u8)));
+            let thread_id = ThreadId::new(i as u8);
+            if self.threads[i].state != ThreadState::Invalid {
+                continue;
             }
+            #[cfg(any(feature = "single-core", feature = "multi-core"))]
+            if self.is_running(thread_id).is_some() {
+                continue;
+            }
+            #[cfg(feature = "alloc")]
+            if self.has_heap_stack(thread_id) {
+                continue;
+            }
+            return Some((&mut self.threads[i], thread_id));
         }
         None
     }
@@ -286,13 +511,29 @@ impl Scheduler {
         let thread = self.get_unchecked_mut(tid);
         let old_state = core::mem::replace(&mut thread.state, state);
         let prio = thread.prio;
+        #[cfg(feature = "trace")]
+        trace::record(trace::TraceEvent::State {
+            thread_id: tid,
+            state,
+        });
         if state == ThreadState::Running {
             #[cfg(not(feature = "infini-core"))]
             self.runqueue.add(tid, prio);
             self.schedule_if_higher_prio(tid, prio);
+            #[cfg(all(feature = "edf", not(feature = "infini-core")))]
+            self.edf_add(tid);
+            #[cfg(all(feature = "time-slicing", not(feature = "infini-core")))]
+            self.schedule_if_tick_needed();
 
             #[cfg(feature = "infini-core")]
             Cpu::set_running(tid);
+            #[cfg(all(feature = "infini-core", feature = "trace"))]
+            if old_state != ThreadState::Running {
+                trace::record(trace::TraceEvent::Switch {
+                    from: None,
+                    to: Some(tid),
+                });
+            }
         } else if old_state == ThreadState::Running {
             // A running thread is only set to a non-running state
             // if it itself initiated it.
@@ -300,12 +541,24 @@ impl Scheduler {
 
             #[cfg(feature = "infini-core")]
             Cpu::set_stopped(tid);
+            #[cfg(all(feature = "infini-core", feature = "trace"))]
+            trace::record(trace::TraceEvent::Switch {
+                from: Some(tid),
+                to: None,
+            });
 
             // On multi-core, the currently running thread is not in the runqueue
             // anyway, so we don't need to remove it here.
             // On infini-core, there's no runqueue.
+            // With EDF, the current thread isn't necessarily the head of its runqueue.
             #[cfg(not(any(feature = "multi-core", feature = "infini-core")))]
-            self.runqueue.pop_head(tid, prio);
+            if cfg!(feature = "edf") {
+                self.runqueue.del(tid);
+            } else {
+                self.runqueue.pop_head(tid, prio);
+            }
+            #[cfg(all(feature = "edf", not(feature = "infini-core")))]
+            self.edf_del(tid);
 
             schedule();
         }
@@ -338,6 +591,8 @@ impl Scheduler {
             return;
         }
         thread.prio = prio;
+        #[cfg(feature = "trace")]
+        trace::record(trace::TraceEvent::Priority { thread_id, prio });
 
         // with infini-core, no re-scheduling is needed.
         if cfg!(feature = "infini-core") {
@@ -439,6 +694,12 @@ impl Scheduler {
             return;
         };
         self.runqueue.add(tid, prio);
+        // A thread whose core affinity changed while it was running has to continue on
+        // another core.
+        #[cfg(feature = "core-affinity")]
+        if !self.is_affine_to_curr_core(tid) {
+            self.schedule_if_higher_prio(tid, prio);
+        }
     }
 
     /// Returns the next thread from the runqueue.
@@ -454,28 +715,47 @@ impl Scheduler {
     fn get_next_tid(&mut self) -> Option<ThreadId> {
         // On single-core, only read the head of the runqueue.
         #[cfg(feature = "single-core")]
-        {
-            self.runqueue.get_next()
-        }
+        let next = self.runqueue.get_next();
 
         // On multi-core, the head is popped of the runqueue.
-        #[cfg(all(feature = "multi-core", not(feature = "core-affinity")))]
-        {
-            self.runqueue.pop_next()
-        }
+        #[cfg(all(
+            feature = "multi-core",
+            not(feature = "core-affinity"),
+            not(feature = "edf")
+        ))]
+        let next = self.runqueue.pop_next();
+
+        // With EDF, the head is only read, as another thread might be picked instead.
+        #[cfg(all(
+            feature = "multi-core",
+            not(feature = "core-affinity"),
+            feature = "edf"
+        ))]
+        let next = self.runqueue.get_next();
 
         // On multi-core with core-affinities, get next thread with matching affinity.
+        // TODO: this would benefit from a `del_one_with_filter` to avoid
+        // iterating twice.
         #[cfg(all(feature = "multi-core", feature = "core-affinity"))]
-        {
-            // TODO: this would benefit from a `del_one_with_filter` to avoid
-            // iterating twice.
-            let next = self
-                .runqueue
-                .get_next_filter(|&t| self.is_affine_to_curr_core(t))?;
-            // Delete thread from runqueue to match the `pop_next`.
+        let next = self
+            .runqueue
+            .get_next_filter(|&t| self.is_affine_to_curr_core(t));
+
+        // Among the threads with the EDF priority, the one with the earliest deadline runs.
+        #[cfg(feature = "edf")]
+        let next = next.map(|next| self.edf_pick(next));
+
+        // Delete the chosen thread from the runqueue to match the `pop_next`, which leaves
+        // the order of all other threads unchanged.
+        #[cfg(all(
+            feature = "multi-core",
+            any(feature = "core-affinity", feature = "edf")
+        ))]
+        if let Some(next) = next {
             self.runqueue.del(next);
-            Some(next)
         }
+
+        next
     }
 
     /// Searches for the lowest priority thread among the currently running threads.
@@ -516,6 +796,41 @@ impl Scheduler {
             .core_affinity
             .contains(crate::core_id())
     }
+
+    /// Changes the core affinity of a thread and triggers the scheduler if needed.
+    #[cfg(feature = "core-affinity")]
+    fn set_core_affinity(&mut self, thread_id: ThreadId, affinity: CoreAffinity) {
+        if !self.is_valid_tid(thread_id) {
+            return;
+        }
+        let thread = self.get_unchecked_mut(thread_id);
+        thread.core_affinity = affinity;
+        if thread.state != ThreadState::Running {
+            // The new affinity is considered once the thread is ready again.
+            return;
+        }
+        let prio = thread.prio;
+        match self.is_running(thread_id) {
+            // The scheduler of that core puts the thread back into the runqueue, and hands
+            // it over to a matching core, see `add_current_thread_to_rq()`.
+            Some(core) if !affinity.contains(CoreId(core as u8)) => {
+                schedule_on_core(CoreId(core as u8));
+            }
+            Some(_) => {}
+            // A matching core might run a lower priority thread.
+            None => self.schedule_if_higher_prio(thread_id, prio),
+        }
+    }
+
+    /// Changes the core affinity of a thread.
+    ///
+    /// Native threads each run on their own host thread, so the affinity is only stored.
+    #[cfg(feature = "infini-core")]
+    fn set_core_affinity(&mut self, thread_id: ThreadId, affinity: CoreAffinity) {
+        if self.is_valid_tid(thread_id) {
+            self.get_unchecked_mut(thread_id).core_affinity = affinity;
+        }
+    }
 }
 
 /// ID of a physical core.
@@ -565,7 +880,8 @@ pub unsafe fn start_threading() {
 
         // Create one idle thread for each core with lowest priority.
         for stack in &IDLE_THREAD_STACKS {
-            create_noarg(idle_thread, stack.take(), 0, None);
+            let thread_id = create_noarg(idle_thread, stack.take(), 0, None);
+            set_name(thread_id, "idle");
         }
 
         let isr_stack_core1 = ISR_STACK_CORE1.take();
@@ -624,6 +940,27 @@ pub fn create<T>(
     prio: u8,
     core_affinity: Option<CoreAffinity>,
 ) -> ThreadId
+where
+    T: Arguable + Send,
+{
+    create_joinable(func, arg, stack, prio, core_affinity).thread_id()
+}
+
+/// Low-level function to create a thread that runs `func` with `arg`, returning a
+/// [`JoinHandle`] for it.
+///
+/// See [`create()`].
+///
+/// # Panics
+///
+/// Panics if more than [`THREAD_COUNT`] concurrent threads have been created.
+pub fn create_joinable<T>(
+    func: fn(T),
+    arg: T,
+    stack: &'static mut [u8],
+    prio: u8,
+    core_affinity: Option<CoreAffinity>,
+) -> JoinHandle
 where
     T: Arguable + Send,
 {
@@ -638,7 +975,7 @@ where
         unsafe { core::mem::transmute::<*const (), fn()>(func) }
     };
 
-    unsafe { create_raw(func, arg, stack, prio, core_affinity) }
+    unsafe { create_raw_joinable(func, arg, stack, prio, core_affinity) }
 }
 
 /// Low-level function to create a thread without argument
@@ -652,7 +989,22 @@ pub fn create_noarg(
     prio: u8,
     core_affinity: Option<CoreAffinity>,
 ) -> ThreadId {
-    unsafe { create_raw(func, None, stack, prio, core_affinity) }
+    create_noarg_joinable(func, stack, prio, core_affinity).thread_id()
+}
+
+/// Low-level function to create a thread without argument, returning a [`JoinHandle`]
+/// for it.
+///
+/// # Panics
+///
+/// Panics if more than [`THREAD_COUNT`] concurrent threads have been created.
+pub fn create_noarg_joinable(
+    func: fn(),
+    stack: &'static mut [u8],
+    prio: u8,
+    core_affinity: Option<CoreAffinity>,
+) -> JoinHandle {
+    unsafe { create_raw_joinable(func, None, stack, prio, core_affinity) }
 }
 
 /// Creates a thread, low-level.
@@ -668,12 +1020,33 @@ pub unsafe fn create_raw(
     prio: u8,
     core_affinity: Option<CoreAffinity>,
 ) -> ThreadId {
+    unsafe { create_raw_joinable(func, arg, stack, prio, core_affinity) }.thread_id()
+}
+
+/// Creates a thread, low-level, returning a [`JoinHandle`] for it.
+///
+/// # Panics
+///
+/// Panics if more than [`THREAD_COUNT`] concurrent threads have been created.
+///
+/// # Safety
+///
+/// Only use when you know what you are doing.
+unsafe fn create_raw_joinable(
+    func: fn(),
+    arg: Option<usize>,
+    stack: &'static mut [u8],
+    prio: u8,
+    core_affinity: Option<CoreAffinity>,
+) -> JoinHandle {
     SCHEDULER.with_mut(|mut scheduler| {
         let thread_id = scheduler
             .create(func, arg, stack, RunqueueId::new(prio), core_affinity)
             .expect("Max `THREAD_COUNT` concurrent threads should be created.");
+        // The handle must be created before the thread can run (and possibly finish).
+        let handle = scheduler.new_join_handle(thread_id);
         scheduler.set_state(thread_id, ThreadState::Running);
-        thread_id
+        handle
     })
 }
 
@@ -685,6 +1058,47 @@ pub fn current_tid() -> Option<ThreadId> {
     SCHEDULER.with(|scheduler| scheduler.current_tid())
 }
 
+/// Sets the name of a thread.
+///
+/// Threads started with the `ariel_os::thread` macro are named after their function by
+/// default.
+/// Does nothing if this is not a valid thread.
+pub fn set_name(thread_id: ThreadId, name: &'static str) {
+    SCHEDULER.with_mut(|mut scheduler| {
+        if scheduler.is_valid_tid(thread_id) {
+            scheduler.get_unchecked_mut(thread_id).name = Some(name);
+        }
+    });
+}
+
+/// Returns the name of a thread.
+///
+/// Returns `None` if this is not a valid thread or if the thread has no name.
+pub fn thread_name(thread_id: ThreadId) -> Option<&'static str> {
+    SCHEDULER.with(|scheduler| {
+        scheduler
+            .is_valid_tid(thread_id)
+            .then(|| scheduler.get_unchecked(thread_id).name)
+            .flatten()
+    })
+}
+
+/// Returns the name of the currently active thread.
+///
+/// Returns `None` if there is no current thread or if it has no name.
+///
+/// This does not panic even when called while the scheduler is in use, e.g., from a panic
+/// handler.
+pub fn current_name() -> Option<&'static str> {
+    SCHEDULER
+        .try_with(|scheduler| {
+            scheduler
+                .current_tid()
+                .and_then(|thread_id| scheduler.get_unchecked(thread_id).name)
+        })
+        .flatten()
+}
+
 /// Returns the id of the CPU that this thread is running on.
 #[must_use]
 pub fn core_id() -> CoreId {
@@ -713,12 +1127,7 @@ pub fn is_valid_tid(thread_id: ThreadId) -> bool {
 /// Panics if this is called outside of a thread context.
 #[allow(unused)]
 fn cleanup() -> ! {
-    SCHEDULER.with_mut(|mut scheduler| {
-        let thread_id = scheduler.current_tid().unwrap();
-        scheduler.set_state(thread_id, ThreadState::Invalid);
-    });
-
-    unreachable!();
+    exit(0)
 }
 
 /// "Yields" to another thread with the same priority.
@@ -803,9 +1212,37 @@ pub fn get_priority(thread_id: ThreadId) -> Option<RunqueueId> {
 
 /// Changes the priority of a thread.
 ///
+/// While the thread owns a [`Mutex`](sync::Mutex), it keeps a higher priority that it
+/// inherited from waiting threads until the mutex is released.
+///
 /// This might trigger a context switch.
 pub fn set_priority(thread_id: ThreadId, prio: RunqueueId) {
-    SCHEDULER.with_mut(|mut scheduler| scheduler.set_priority(thread_id, prio));
+    SCHEDULER.with_mut(|mut scheduler| scheduler.set_base_priority(thread_id, prio));
+}
+
+/// Returns the core affinity of a thread.
+///
+/// Returns `None` if this is not a valid thread.
+#[cfg(any(feature = "core-affinity", feature = "infini-core"))]
+pub fn get_core_affinity(thread_id: ThreadId) -> Option<CoreAffinity> {
+    SCHEDULER.with(|scheduler| {
+        scheduler
+            .is_valid_tid(thread_id)
+            .then(|| scheduler.get_unchecked(thread_id).core_affinity)
+    })
+}
+
+/// Changes the core affinity of a thread.
+///
+/// If the thread is running on a core that doesn't match the new affinity, it is moved
+/// to a matching core: right away if one of them runs a lower priority thread, otherwise
+/// once it is the thread's turn again.
+///
+/// This might trigger a context switch.
+/// On native, the affinity is only stored, see [`CoreAffinity`].
+#[cfg(any(feature = "core-affinity", feature = "infini-core"))]
+pub fn set_core_affinity(thread_id: ThreadId, affinity: CoreAffinity) {
+    SCHEDULER.with_mut(|mut scheduler| scheduler.set_core_affinity(thread_id, affinity));
 }
 
 /// Returns the current thread's stack limits (lowest, highest).
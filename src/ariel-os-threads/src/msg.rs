@@ -0,0 +1,474 @@
+//! Message passing between threads, similar to RIOT's `msg` API.
+//!
+//! Each thread has a mailbox that queues up to [`MSG_QUEUE_SIZE`] messages, which can be
+//! configured using the `CONFIG_THREAD_MSG_QUEUE_SIZE` environment variable.
+//!
+//! [`send()`] hands a [`Msg`] directly to the receiving thread if it is waiting in
+//! [`receive()`], or puts it into the receiver's queue.
+//! If the queue is full, the sender blocks until the receiver takes the message.
+//! [`send_receive()`] additionally waits until the receiver answers using [`reply()`].
+//!
+//! The mailbox of a thread is emptied when the thread finishes.
+//! Threads that are still blocked sending to it are woken up, and [`send()`] or
+//! [`send_receive()`] return [`MsgError::ReceiverGone`].
+use core::{cell::RefCell, mem::MaybeUninit};
+
+use critical_section::{CriticalSection, Mutex};
+
+use crate::{SCHEDULER, Scheduler, THREAD_COUNT, ThreadId, ThreadState, threadlist::ThreadList};
+
+#[cfg(feature = "time")]
+use crate::time::{self, TimeoutError};
+#[cfg(feature = "time")]
+use embassy_time::Duration;
+
+/// Number of messages the mailbox of each thread can queue.
+pub const MSG_QUEUE_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_THREAD_MSG_QUEUE_SIZE",
+    4,
+    "number of messages the mailbox of each thread can queue"
+);
+
+const _: () = assert!(
+    MSG_QUEUE_SIZE > 0,
+    "`CONFIG_THREAD_MSG_QUEUE_SIZE` must not be 0"
+);
+
+/// Message sent between threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Msg {
+    /// Thread that sent the message.
+    pub sender: ThreadId,
+    /// Type of the message, to be interpreted by the application.
+    pub msg_type: u16,
+    /// Content of the message.
+    pub content: usize,
+}
+
+/// Error returned when a message could not be sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MsgError {
+    /// The receiving thread does not exist.
+    InvalidThread,
+    /// The receiving thread's queue is full.
+    WouldBlock,
+    /// The thread that sent the message doesn't wait for a reply.
+    NotWaitingForReply,
+    /// The receiving thread finished while the sender was blocked.
+    ReceiverGone,
+}
+
+/// Mailbox of a thread.
+struct Mailbox {
+    queue: [Option<Msg>; MSG_QUEUE_SIZE],
+    /// Index of the oldest message.
+    head: usize,
+    len: usize,
+    /// Threads that wait for space in the queue.
+    senders: ThreadList,
+    /// Whether the receiver of this thread's blocked send finished before taking the
+    /// message.
+    receiver_gone: bool,
+}
+
+impl Mailbox {
+    const fn new() -> Self {
+        Self {
+            queue: [None; MSG_QUEUE_SIZE],
+            head: 0,
+            len: 0,
+            senders: ThreadList::new(),
+            receiver_gone: false,
+        }
+    }
+
+    /// Appends a message to the queue.
+    ///
+    /// Returns `false` if the queue is full.
+    fn push(&mut self, msg: Msg) -> bool {
+        if self.len == MSG_QUEUE_SIZE {
+            return false;
+        }
+        self.queue[(self.head + self.len) % MSG_QUEUE_SIZE] = Some(msg);
+        self.len += 1;
+        true
+    }
+
+    /// Removes the oldest message from the queue.
+    fn pop(&mut self) -> Option<Msg> {
+        if self.len == 0 {
+            return None;
+        }
+        let msg = self.queue[self.head].take();
+        self.head = (self.head + 1) % MSG_QUEUE_SIZE;
+        self.len -= 1;
+        msg
+    }
+}
+
+static MAILBOXES: Mutex<RefCell<[Mailbox; THREAD_COUNT]>> =
+    Mutex::new(RefCell::new([const { Mailbox::new() }; THREAD_COUNT]));
+
+/// Sends a message to thread `target` (blocking).
+///
+/// If the target's queue is full, the current thread is suspended until the target
+/// takes the message.
+///
+/// # Errors
+///
+/// Returns [`MsgError::InvalidThread`] if `target` is not a valid thread, or
+/// [`MsgError::ReceiverGone`] if `target` finished while this thread was blocked.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn send(target: ThreadId, msg_type: u16, content: usize) -> Result<(), MsgError> {
+    let msg = new_msg(msg_type, content);
+    critical_section::with(|cs| match try_send_cs(cs, target, msg) {
+        Err(MsgError::WouldBlock) => {
+            let state = ThreadState::MsgSendBlocked(core::ptr::from_ref(&msg) as usize);
+            put_sender(cs, target, state);
+            // Context switch happens here as soon as we leave the critical section.
+            Ok(())
+        }
+        res => res,
+    })?;
+    critical_section::with(take_receiver_gone)
+}
+
+/// Sends a message to thread `target` (non-blocking).
+///
+/// # Errors
+///
+/// Returns [`MsgError::InvalidThread`] if `target` is not a valid thread, or
+/// [`MsgError::WouldBlock`] if the target's queue is full.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn try_send(target: ThreadId, msg_type: u16, content: usize) -> Result<(), MsgError> {
+    let msg = new_msg(msg_type, content);
+    critical_section::with(|cs| try_send_cs(cs, target, msg))
+}
+
+/// Sends a message to thread `target` and waits for its [`reply()`] (blocking).
+///
+/// Returns the reply.
+///
+/// # Errors
+///
+/// Returns [`MsgError::InvalidThread`] if `target` is not a valid thread, or
+/// [`MsgError::ReceiverGone`] if `target` finished while this thread was blocked sending.
+/// A thread that finishes after taking the message without replying leaves the sender
+/// blocked.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn send_receive(target: ThreadId, msg_type: u16, content: usize) -> Result<Msg, MsgError> {
+    // The message is overwritten with the reply.
+    let mut msg = new_msg(msg_type, content);
+    let ptr = core::ptr::from_mut(&mut msg) as usize;
+    critical_section::with(|cs| {
+        match try_send_cs(cs, target, msg) {
+            Ok(()) => SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                let thread_id = scheduler.current_tid().unwrap();
+                scheduler.set_state(thread_id, ThreadState::MsgReplyBlocked(ptr));
+            }),
+            Err(MsgError::WouldBlock) => {
+                put_sender(cs, target, ThreadState::MsgSendReceiveBlocked(ptr));
+            }
+            Err(err) => return Err(err),
+        }
+        // Context switch happens here as soon as we leave the critical section.
+        Ok(())
+    })?;
+    critical_section::with(take_receiver_gone)?;
+
+    // ensure the compiler honors what happened to memory while the thread
+    // was scheduled away.
+    core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+
+    // SAFETY: the reply was written through `ptr` while this thread was blocked.
+    Ok(unsafe { core::ptr::read_volatile(ptr as *const Msg) })
+}
+
+/// Replies to a message received from a thread that waits in [`send_receive()`].
+///
+/// # Errors
+///
+/// Returns [`MsgError::NotWaitingForReply`] if the sender of `msg` is not waiting for a
+/// reply.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn reply(msg: &Msg, msg_type: u16, content: usize) -> Result<(), MsgError> {
+    let reply = new_msg(msg_type, content);
+    SCHEDULER.with_mut(|mut scheduler| {
+        let Some(ThreadState::MsgReplyBlocked(ptr)) = scheduler.get_state(msg.sender) else {
+            return Err(MsgError::NotWaitingForReply);
+        };
+        // SAFETY: the sender is blocked until it is woken up below, so its reply buffer
+        // is still valid.
+        unsafe { (ptr as *mut Msg).write(reply) };
+        scheduler.set_state(msg.sender, ThreadState::Running);
+        Ok(())
+    })
+}
+
+/// Receives a message (blocking).
+///
+/// If there is no message queued yet, the current thread is suspended until a message
+/// is sent to it.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+#[must_use]
+pub fn receive() -> Msg {
+    let mut res: MaybeUninit<Msg> = MaybeUninit::uninit();
+    let received = critical_section::with(|cs| {
+        let msg = try_receive_cs(cs);
+        if msg.is_none() {
+            // sender will copy message
+            block_receiver(cs, res.as_mut_ptr());
+        }
+        msg
+    });
+    received.unwrap_or_else(|| {
+        // ensure the compiler honors what happened to memory while the thread
+        // was scheduled away.
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        unsafe { res.assume_init() }
+    })
+}
+
+/// Receives a message (blocking), giving up after `timeout`.
+///
+/// Behaves like [`receive()`], but returns once `timeout` has passed without a message
+/// being sent.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if no message was received within `timeout`.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+#[cfg(feature = "time")]
+pub fn receive_timeout(timeout: Duration) -> Result<Msg, TimeoutError> {
+    let deadline = crate::time::deadline_after(timeout);
+    let mut res: MaybeUninit<Msg> = MaybeUninit::uninit();
+    let received = critical_section::with(|cs| {
+        let msg = try_receive_cs(cs);
+        if msg.is_none() {
+            // sender will copy message
+            block_receiver(cs, res.as_mut_ptr());
+            time::set_deadline(cs, deadline);
+        }
+        msg
+    });
+    if let Some(msg) = received {
+        return Ok(msg);
+    }
+    critical_section::with(time::clear_deadline)?;
+
+    // ensure the compiler honors what happened to memory while the thread
+    // was scheduled away.
+    core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+
+    Ok(unsafe { res.assume_init() })
+}
+
+/// Receives a message (non-blocking).
+///
+/// Returns `None` if no message is queued.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn try_receive() -> Option<Msg> {
+    critical_section::with(try_receive_cs)
+}
+
+/// Returns the number of messages queued for the current thread.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+#[must_use]
+pub fn available() -> usize {
+    let thread_id = current_tid();
+    critical_section::with(|cs| MAILBOXES.borrow_ref(cs)[usize::from(thread_id)].len)
+}
+
+/// Empties the mailbox of a newly created thread.
+pub(crate) fn reset_mailbox(thread_id: ThreadId) {
+    critical_section::with(|cs| {
+        MAILBOXES.borrow_ref_mut(cs)[usize::from(thread_id)] = Mailbox::new();
+    });
+}
+
+/// Wakes up all threads blocked sending to `thread_id`, which has finished, and empties
+/// its mailbox.
+///
+/// The woken up senders return [`MsgError::ReceiverGone`].
+pub(crate) fn close_mailbox(scheduler: &mut Scheduler, thread_id: ThreadId) {
+    critical_section::with(|cs| {
+        let mut mailboxes = MAILBOXES.borrow_ref_mut(cs);
+        let mut senders = core::mem::take(&mut mailboxes[usize::from(thread_id)].senders);
+        while let Some((sender, _)) = senders.pop_in(scheduler, |_| ThreadState::Running) {
+            mailboxes[usize::from(sender)].receiver_gone = true;
+        }
+        mailboxes[usize::from(thread_id)] = Mailbox::new();
+    });
+}
+
+/// Checks whether the current thread was woken up because the receiver of its blocked
+/// send finished, and resets that state.
+///
+/// # Errors
+///
+/// Returns [`MsgError::ReceiverGone`] if the receiver finished.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+fn take_receiver_gone(cs: CriticalSection<'_>) -> Result<(), MsgError> {
+    let gone = core::mem::take(
+        &mut MAILBOXES.borrow_ref_mut(cs)[usize::from(current_tid())].receiver_gone,
+    );
+    if gone {
+        Err(MsgError::ReceiverGone)
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the [`ThreadId`] of the current thread.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+fn current_tid() -> ThreadId {
+    crate::current_tid().expect("Function should be called inside a thread context.")
+}
+
+/// Creates a message sent by the current thread.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+fn new_msg(msg_type: u16, content: usize) -> Msg {
+    Msg {
+        sender: current_tid(),
+        msg_type,
+        content,
+    }
+}
+
+/// Hands `msg` over to `target` if it waits in [`receive()`], or queues it otherwise.
+///
+/// # Errors
+///
+/// Returns [`MsgError::InvalidThread`] if `target` is not a valid thread, or
+/// [`MsgError::WouldBlock`] if the target's queue is full.
+fn try_send_cs(cs: CriticalSection<'_>, target: ThreadId, msg: Msg) -> Result<(), MsgError> {
+    let delivered = SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+        match scheduler.get_state(target) {
+            None => Err(MsgError::InvalidThread),
+            Some(ThreadState::MsgReceiveBlocked(ptr)) => {
+                // SAFETY: the receiver is blocked until it is woken up below, so its
+                // buffer is still valid.
+                unsafe { (ptr as *mut Msg).write(msg) };
+                scheduler.set_state(target, ThreadState::Running);
+                Ok(true)
+            }
+            Some(_) => Ok(false),
+        }
+    })?;
+    if delivered || MAILBOXES.borrow_ref_mut(cs)[usize::from(target)].push(msg) {
+        Ok(())
+    } else {
+        Err(MsgError::WouldBlock)
+    }
+}
+
+/// Puts the current thread into the list of threads waiting for space in the queue of
+/// `target`.
+fn put_sender(cs: CriticalSection<'_>, target: ThreadId, state: ThreadState) {
+    MAILBOXES.borrow_ref_mut(cs)[usize::from(target)]
+        .senders
+        .put_current(cs, state);
+}
+
+/// Takes the oldest message queued for the current thread, if any.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+fn try_receive_cs(cs: CriticalSection<'_>) -> Option<Msg> {
+    let mut mailboxes = MAILBOXES.borrow_ref_mut(cs);
+    let mailbox = &mut mailboxes[usize::from(current_tid())];
+    let msg = mailbox.pop();
+    // Senders only wait while the queue is full, so the first one can queue its message
+    // now.
+    let Some((_, state)) = mailbox.senders.pop_with(cs, |state| match state {
+        ThreadState::MsgSendReceiveBlocked(ptr) => ThreadState::MsgReplyBlocked(ptr),
+        _ => ThreadState::Running,
+    }) else {
+        return msg;
+    };
+    let (ThreadState::MsgSendBlocked(ptr) | ThreadState::MsgSendReceiveBlocked(ptr)) = state else {
+        unreachable!("unexpected thread state");
+    };
+    // SAFETY: the sender was blocked until now, so its message is still valid.
+    let sent = unsafe { *(ptr as *const Msg) };
+    match msg {
+        Some(msg) => {
+            mailbox.push(sent);
+            Some(msg)
+        }
+        None => Some(sent),
+    }
+}
+
+/// Blocks the current thread until a message is written to `ptr`.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+fn block_receiver(cs: CriticalSection<'_>, ptr: *mut Msg) {
+    SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+        let thread_id = scheduler.current_tid().unwrap();
+        scheduler.set_state(thread_id, ThreadState::MsgReceiveBlocked(ptr as usize));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mailbox_queue() {
+        let msg = |content| Msg {
+            sender: ThreadId::new(0),
+            msg_type: 0,
+            content,
+        };
+        let mut mailbox = Mailbox::new();
+        for i in 0..MSG_QUEUE_SIZE {
+            assert!(mailbox.push(msg(i)));
+        }
+        assert!(!mailbox.push(msg(MSG_QUEUE_SIZE)));
+        assert_eq!(mailbox.pop(), Some(msg(0)));
+        assert!(mailbox.push(msg(MSG_QUEUE_SIZE)));
+        for i in 1..=MSG_QUEUE_SIZE {
+            assert_eq!(mailbox.pop(), Some(msg(i)));
+        }
+        assert_eq!(mailbox.pop(), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Threads with heap-allocated stacks.
+use alloc::alloc::{Layout, alloc, dealloc, handle_alloc_error};
+
+use crate::{
+    Arguable, CORE_COUNT, JoinHandle, RunqueueId, SCHEDULER, Scheduler, THREAD_COUNT, ThreadId,
+    ThreadState,
+};
+
+/// Alignment of heap-allocated stacks.
+const STACK_ALIGN: usize = 8;
+
+/// Heap-allocated stacks, indexed by [`ThreadId`].
+#[derive(Debug)]
+pub(crate) struct HeapStacks {
+    /// Address and size of the stack of each thread, if it was allocated on the heap.
+    stacks: [Option<(usize, usize)>; THREAD_COUNT],
+    /// Finished thread that each core last switched away from, whose stack is freed on
+    /// the next context switch of that core.
+    switched_from: [Option<ThreadId>; CORE_COUNT],
+}
+
+impl HeapStacks {
+    /// Creates a new [`HeapStacks`].
+    pub const fn new() -> Self {
+        Self {
+            stacks: [None; THREAD_COUNT],
+            switched_from: [None; CORE_COUNT],
+        }
+    }
+}
+
+/// Error returned by [`spawn()`] when all [`THREAD_COUNT`] thread slots are in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SpawnError;
+
+/// Creates a thread that runs `func` with `arg` on a stack of `stack_size` bytes
+/// allocated on the heap, and returns a [`JoinHandle`] for it.
+///
+/// The stack is freed once the thread has finished and its core has switched away from it.
+/// Until then, its thread slot is not reused.
+///
+/// # Errors
+///
+/// Returns [`SpawnError`] if [`THREAD_COUNT`] concurrent threads already exist.
+///
+/// # Panics
+///
+/// Panics if `stack_size` is zero, or if the stack cannot be allocated.
+pub fn spawn<T>(func: fn(T), arg: T, stack_size: usize, prio: u8) -> Result<JoinHandle, SpawnError>
+where
+    T: Arguable + Send,
+{
+    assert!(stack_size > 0, "the stack size must not be zero");
+
+    let Ok(layout) = Layout::from_size_align(stack_size, STACK_ALIGN) else {
+        panic!("invalid stack size");
+    };
+    // SAFETY: the layout has a non-zero size.
+    let ptr = unsafe { alloc(layout) };
+    if ptr.is_null() {
+        handle_alloc_error(layout);
+    }
+    // SAFETY: the allocation is valid for `stack_size` bytes, and is only freed once the
+    // thread using it has finished and isn't running anymore.
+    let stack = unsafe { core::slice::from_raw_parts_mut(ptr, stack_size) };
+
+    let arg = Some(arg.into_arg());
+    // Convert `fn(T)` into `fn()`, see `create_joinable()`.
+    // SAFETY: transmuting between raw pointers and function pointers is fine.
+    let func = unsafe { core::mem::transmute::<*const (), fn()>(func as *const ()) };
+
+    SCHEDULER.with_mut(|mut scheduler| {
+        let Some(thread_id) = scheduler.create(func, arg, stack, RunqueueId::new(prio), None)
+        else {
+            // SAFETY: the stack was allocated above with this layout, and no thread uses it.
+            unsafe { dealloc(ptr, layout) };
+            return Err(SpawnError);
+        };
+        // The stack must be recorded before the thread can run (and possibly finish).
+        scheduler.heap_stacks.stacks[usize::from(thread_id)] = Some((ptr as usize, stack_size));
+        let handle = scheduler.new_join_handle(thread_id);
+        scheduler.set_state(thread_id, ThreadState::Running);
+        Ok(handle)
+    })
+}
+
+impl Scheduler {
+    /// Frees the heap-allocated stacks of finished threads, after the current core
+    /// switched away from `previous`.
+    ///
+    /// The interrupt frame of a context switch might live on the stack of the thread that
+    /// is switched away from, so the stack of a finished thread is only freed on the next
+    /// context switch of the same core.
+    #[allow(dead_code, reason = "used in scheduler implementation")]
+    pub(crate) fn release_stacks(&mut self, previous: ThreadId) {
+        let core = usize::from(crate::core_id());
+        if let Some(finished) = self.heap_stacks.switched_from[core].take() {
+            self.free_stack(finished);
+        }
+        if self.threads[usize::from(previous)].state == ThreadState::Invalid {
+            self.heap_stacks.switched_from[core] = Some(previous);
+        }
+    }
+
+    /// Returns whether a thread still has a heap-allocated stack.
+    pub(crate) fn has_heap_stack(&self, thread_id: ThreadId) -> bool {
+        self.heap_stacks.stacks[usize::from(thread_id)].is_some()
+    }
+
+    /// Frees the heap-allocated stack of a thread, if it has one.
+    ///
+    /// The thread must have finished and must not be running anymore.
+    pub(crate) fn free_stack(&mut self, thread_id: ThreadId) {
+        if let Some((ptr, size)) = self.heap_stacks.stacks[usize::from(thread_id)].take() {
+            // SAFETY: the stack was allocated in `spawn()` with this layout, and is not
+            // in use anymore.
+            unsafe {
+                let layout = Layout::from_size_align_unchecked(size, STACK_ALIGN);
+                dealloc(ptr as *mut u8, layout);
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Detection of thread stack overflows.
+//!
+//! At every context switch, the stack of the thread that is switched out is checked:
+//! - the lowest [`CANARY_SIZE`] bytes of the stack must still have the stack paint, and
+//! - the saved stack pointer must be within the stack.
+//!
+//! Not supported on native, where threads run on stacks managed by the host OS, so enabling
+//! the `stack-overflow-check` feature there is a compile error.
+#[cfg(context = "native")]
+compile_error!("the `stack-overflow-check` feature is not supported on native");
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use crate::{Scheduler, ThreadId, thread::STACK_PAINT_COLOR};
+
+/// Number of bytes at the lowest end of each stack that act as canary.
+///
+/// Threads must never write to these bytes.
+pub const CANARY_SIZE: usize = 16;
+
+/// Function that is called when a stack overflow is detected, see
+/// [`set_stack_overflow_hook()`].
+pub type StackOverflowHook = fn(ThreadId);
+
+static STACK_OVERFLOW_HOOK: Mutex<Cell<Option<StackOverflowHook>>> = Mutex::new(Cell::new(None));
+
+/// Sets a function that is called with the [`ThreadId`] of a thread whose stack has
+/// overflowed, instead of panicking.
+///
+/// The hook is called during the context switch, from within a critical section, so it
+/// must not use any of the scheduler's functions.
+/// If the hook returns, the thread keeps running on its corrupted stack.
+pub fn set_stack_overflow_hook(hook: StackOverflowHook) {
+    critical_section::with(|cs| STACK_OVERFLOW_HOOK.borrow(cs).set(Some(hook)));
+}
+
+impl Scheduler {
+    /// Checks the stack of a thread that is being switched out.
+    ///
+    /// `sp` is the saved stack pointer of the thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stack has overflowed and no hook has been set with
+    /// [`set_stack_overflow_hook()`].
+    #[allow(dead_code, reason = "not used in all configurations")]
+    pub(crate) fn check_stack(&self, thread_id: ThreadId, sp: usize) {
+        let thread = self.get_unchecked(thread_id);
+        // Stacks that aren't managed by the scheduler are empty.
+        if thread.stack_lowest == thread.stack_highest {
+            return;
+        }
+        let sp_in_bounds = (thread.stack_lowest..=thread.stack_highest).contains(&sp);
+        let canary_intact = (thread.stack_lowest..thread.stack_lowest + CANARY_SIZE)
+            // SAFETY: the thread's stack is still valid while it is switched out.
+            .all(|pos| unsafe { core::ptr::read_volatile(pos as *const u8) } == STACK_PAINT_COLOR);
+        if sp_in_bounds && canary_intact {
+            return;
+        }
+        match critical_section::with(|cs| STACK_OVERFLOW_HOOK.borrow(cs).get()) {
+            Some(hook) => hook(thread_id),
+            None => panic!("stack overflow in thread {}", usize::from(thread_id)),
+        }
+    }
+}
@@ -6,7 +6,12 @@ use core::mem::MaybeUninit;
 
 use crate::ThreadState;
 use crate::threadlist::ThreadList;
-use critical_section::with;
+use critical_section::{CriticalSection, with};
+
+#[cfg(feature = "time")]
+use crate::time::{self, TimeoutError};
+#[cfg(feature = "time")]
+use embassy_time::Duration;
 
 enum ChannelState {
     Idle,
@@ -42,70 +47,47 @@ impl<T: Copy + Send> Channel<T> {
     /// Panics if this is called outside of a thread context.
     pub fn send(&self, something: &T) {
         with(|cs| {
-            let state = unsafe { &mut *self.state.get() };
-            match state {
-                ChannelState::Idle => {
-                    let mut waiters = ThreadList::new();
-                    waiters.put_current(
-                        cs,
-                        crate::ThreadState::ChannelTxBlocked(
-                            core::ptr::from_ref::<T>(something) as usize
-                        ),
-                    );
-                    *state = ChannelState::SendersWaiting(waiters);
-                }
-                ChannelState::ReceiversWaiting(waiters) => {
-                    if let Some((_, head_state)) = waiters.pop(cs) {
-                        if waiters.is_empty(cs) {
-                            *state = ChannelState::Idle;
-                        }
-                        if let ThreadState::ChannelRxBlocked(ptr) = head_state {
-                            // copy over `something`
-                            unsafe { (ptr as *mut T).write(*something) };
-                        } else {
-                            unreachable!("unexpected thread state");
-                        }
-                    } else {
-                        unreachable!("unexpected empty thread list");
-                    }
-                }
-                ChannelState::SendersWaiting(waiters) => {
-                    waiters.put_current(
-                        cs,
-                        crate::ThreadState::ChannelTxBlocked(core::ptr::from_ref(self) as usize),
-                    );
-                }
+            if !self.try_send_cs(cs, something) {
+                self.put_sender(cs, something);
             }
         });
     }
 
+    /// Send on the channel (blocking), giving up after `timeout`.
+    ///
+    /// Behaves like [`Self::send()`], but returns once `timeout` has passed without
+    /// a receiver taking the data.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if no receiver took the data within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "time")]
+    pub fn send_timeout(&self, something: &T, timeout: Duration) -> Result<(), TimeoutError> {
+        let deadline = crate::time::deadline_after(timeout);
+        let waiting = with(|cs| {
+            if self.try_send_cs(cs, something) {
+                return false;
+            }
+            self.put_sender(cs, something);
+            time::set_deadline(cs, deadline);
+            true
+        });
+        if !waiting {
+            return Ok(());
+        }
+        with(|cs| time::clear_deadline(cs).inspect_err(|_| self.remove_current(cs)))
+    }
+
     /// Try to send on the channel (non-blocking).
     ///
     /// Returns `true` if a receiver was waiting and received
     /// the data, `false` otherwise.
     pub fn try_send(&self, something: &T) -> bool {
-        with(|cs| {
-            let state = unsafe { &mut *self.state.get() };
-            match state {
-                ChannelState::ReceiversWaiting(waiters) => {
-                    if let Some((_, head_state)) = waiters.pop(cs) {
-                        if waiters.is_empty(cs) {
-                            *state = ChannelState::Idle;
-                        }
-                        if let ThreadState::ChannelRxBlocked(ptr) = head_state {
-                            // copy over `something`
-                            unsafe { (ptr as *mut T).write(*something) };
-                        } else {
-                            unreachable!("unexpected thread state");
-                        }
-                    } else {
-                        unreachable!("unexpected empty thread list");
-                    }
-                    true
-                }
-                _ => false,
-            }
-        })
+        with(|cs| self.try_send_cs(cs, something))
     }
 
     /// Receive on the channel (blocking).
@@ -120,33 +102,10 @@ impl<T: Copy + Send> Channel<T> {
         let mut res: MaybeUninit<T> = MaybeUninit::uninit();
 
         with(|cs| {
-            let state = unsafe { &mut *self.state.get() };
             let ptr = res.as_mut_ptr();
-            match state {
-                ChannelState::Idle => {
-                    let mut waiters = ThreadList::new();
-                    waiters.put_current(cs, crate::ThreadState::ChannelRxBlocked(ptr as usize));
-                    *state = ChannelState::ReceiversWaiting(waiters);
-                }
-                ChannelState::ReceiversWaiting(waiters) => {
-                    waiters.put_current(cs, crate::ThreadState::ChannelRxBlocked(ptr as usize));
-                    // sender will copy message
-                }
-                ChannelState::SendersWaiting(waiters) => {
-                    if let Some((_, head_state)) = waiters.pop(cs) {
-                        if waiters.is_empty(cs) {
-                            *state = ChannelState::Idle;
-                        }
-                        if let ThreadState::ChannelTxBlocked(other_ptr) = head_state {
-                            // copy over `something`
-                            unsafe { ptr.write(*(other_ptr as *const T)) };
-                        } else {
-                            unreachable!("unexpected thread state");
-                        }
-                    } else {
-                        unreachable!("unexpected empty thread list");
-                    }
-                }
+            if !self.try_recv_cs(cs, ptr) {
+                // sender will copy message
+                self.put_receiver(cs, ptr);
             }
         });
 
@@ -157,35 +116,51 @@ impl<T: Copy + Send> Channel<T> {
         unsafe { res.assume_init() }
     }
 
+    /// Receive on the channel (blocking), giving up after `timeout`.
+    ///
+    /// Behaves like [`Self::recv()`], but returns once `timeout` has passed without
+    /// a sender providing data.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if no data was received within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "time")]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, TimeoutError> {
+        let deadline = crate::time::deadline_after(timeout);
+        let mut res: MaybeUninit<T> = MaybeUninit::uninit();
+
+        let waiting = with(|cs| {
+            let ptr = res.as_mut_ptr();
+            if self.try_recv_cs(cs, ptr) {
+                return false;
+            }
+            // sender will copy message
+            self.put_receiver(cs, ptr);
+            time::set_deadline(cs, deadline);
+            true
+        });
+        if waiting {
+            with(|cs| time::clear_deadline(cs).inspect_err(|_| self.remove_current(cs)))?;
+        }
+
+        // ensure the compiler honors what happened to memory while the thread
+        // was scheduled away.
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+
+        Ok(unsafe { res.assume_init() })
+    }
+
     /// Try to send on the channel (non-blocking).
     ///
     /// Returns `Some` data if a sender was waiting and the
     /// data could be received, `None` otherwise.
     pub fn try_recv(&self) -> Option<T> {
         let mut res: MaybeUninit<T> = MaybeUninit::uninit();
-        let have_received = with(|cs| {
-            let state = unsafe { &mut *self.state.get() };
-            match state {
-                ChannelState::SendersWaiting(waiters) => {
-                    let ptr = res.as_mut_ptr();
-                    if let Some((_, head_state)) = waiters.pop(cs) {
-                        if waiters.is_empty(cs) {
-                            *state = ChannelState::Idle;
-                        }
-                        if let ThreadState::ChannelTxBlocked(other_ptr) = head_state {
-                            // copy over `something`
-                            unsafe { ptr.write(*(other_ptr as *const T)) };
-                        } else {
-                            unreachable!("unexpected thread state");
-                        }
-                        true
-                    } else {
-                        unreachable!("unexpected empty thread list");
-                    }
-                }
-                _ => false,
-            }
-        });
+        let have_received = with(|cs| self.try_recv_cs(cs, res.as_mut_ptr()));
 
         if have_received {
             core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::Acquire);
@@ -194,6 +169,109 @@ impl<T: Copy + Send> Channel<T> {
             None
         }
     }
+
+    /// Hands `something` over to the first waiting receiver, if any.
+    ///
+    /// Returns `true` if a receiver got the data.
+    fn try_send_cs(&self, cs: CriticalSection<'_>, something: &T) -> bool {
+        let state = unsafe { &mut *self.state.get() };
+        let ChannelState::ReceiversWaiting(waiters) = state else {
+            return false;
+        };
+        let Some((_, head_state)) = waiters.pop(cs) else {
+            // All remaining receivers have timed out.
+            *state = ChannelState::Idle;
+            return false;
+        };
+        if waiters.is_empty(cs) {
+            *state = ChannelState::Idle;
+        }
+        if let ThreadState::ChannelRxBlocked(ptr) = head_state {
+            // copy over `something`
+            unsafe { (ptr as *mut T).write(*something) };
+        } else {
+            unreachable!("unexpected thread state");
+        }
+        true
+    }
+
+    /// Takes the data of the first waiting sender, if any, and writes it to `ptr`.
+    ///
+    /// Returns `true` if data was received.
+    fn try_recv_cs(&self, cs: CriticalSection<'_>, ptr: *mut T) -> bool {
+        let state = unsafe { &mut *self.state.get() };
+        let ChannelState::SendersWaiting(waiters) = state else {
+            return false;
+        };
+        let Some((_, head_state)) = waiters.pop(cs) else {
+            // All remaining senders have timed out.
+            *state = ChannelState::Idle;
+            return false;
+        };
+        if waiters.is_empty(cs) {
+            *state = ChannelState::Idle;
+        }
+        if let ThreadState::ChannelTxBlocked(other_ptr) = head_state {
+            // copy over `something`
+            unsafe { ptr.write(*(other_ptr as *const T)) };
+        } else {
+            unreachable!("unexpected thread state");
+        }
+        true
+    }
+
+    /// Puts the current thread into the list of waiting senders.
+    ///
+    /// Must only be called if there is no waiting receiver.
+    fn put_sender(&self, cs: CriticalSection<'_>, something: &T) {
+        let state = unsafe { &mut *self.state.get() };
+        let thread_state =
+            ThreadState::ChannelTxBlocked(core::ptr::from_ref::<T>(something) as usize);
+        match state {
+            ChannelState::Idle => {
+                let mut waiters = ThreadList::new();
+                waiters.put_current(cs, thread_state);
+                *state = ChannelState::SendersWaiting(waiters);
+            }
+            ChannelState::SendersWaiting(waiters) => {
+                waiters.put_current(cs, thread_state);
+            }
+            ChannelState::ReceiversWaiting(_) => unreachable!("unexpected waiting receivers"),
+        }
+    }
+
+    /// Puts the current thread into the list of waiting receivers.
+    ///
+    /// Must only be called if there is no waiting sender.
+    fn put_receiver(&self, cs: CriticalSection<'_>, ptr: *mut T) {
+        let state = unsafe { &mut *self.state.get() };
+        let thread_state = ThreadState::ChannelRxBlocked(ptr as usize);
+        match state {
+            ChannelState::Idle => {
+                let mut waiters = ThreadList::new();
+                waiters.put_current(cs, thread_state);
+                *state = ChannelState::ReceiversWaiting(waiters);
+            }
+            ChannelState::ReceiversWaiting(waiters) => {
+                waiters.put_current(cs, thread_state);
+            }
+            ChannelState::SendersWaiting(_) => unreachable!("unexpected waiting senders"),
+        }
+    }
+
+    /// Removes the current thread from the waiting senders or receivers.
+    #[cfg(feature = "time")]
+    fn remove_current(&self, cs: CriticalSection<'_>) {
+        let state = unsafe { &mut *self.state.get() };
+        if let ChannelState::SendersWaiting(waiters) | ChannelState::ReceiversWaiting(waiters) =
+            state
+        {
+            waiters.remove_current(cs);
+            if waiters.is_empty(cs) {
+                *state = ChannelState::Idle;
+            }
+        }
+    }
 }
 
 impl<T: Copy + Send> Default for Channel<T> {
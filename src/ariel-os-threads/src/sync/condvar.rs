@@ -0,0 +1,131 @@
+//! This module provides a condition variable.
+
+use core::cell::UnsafeCell;
+
+use critical_section::CriticalSection;
+
+use super::MutexGuard;
+use crate::{ThreadState, threadlist::ThreadList};
+
+#[cfg(feature = "time")]
+use crate::time::{self, TimeoutError};
+#[cfg(feature = "time")]
+use embassy_time::Duration;
+
+/// A condition variable, to block threads while waiting for a condition that is
+/// protected by a [`Mutex`](super::Mutex).
+///
+/// Waiting releases the mutex and blocks the current thread in one atomic step, so
+/// that a notification cannot be missed between checking the condition and
+/// starting to wait.
+/// Notified threads are woken up in order of their priority and re-acquire the
+/// mutex before returning, with the mutex's usual priority inheritance.
+///
+/// As with most condition variables, waiting threads should always re-check their
+/// condition in a loop.
+pub struct Condvar {
+    waiters: UnsafeCell<ThreadList>,
+}
+
+unsafe impl Sync for Condvar {}
+
+impl Condvar {
+    /// Creates a new [`Condvar`].
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            waiters: UnsafeCell::new(ThreadList::new()),
+        }
+    }
+
+    /// Releases the mutex of `guard` and blocks the current thread until it is
+    /// notified.
+    ///
+    /// The mutex is locked again before this function returns.
+    /// A priority that the current thread inherited as owner of the mutex is
+    /// reset while it waits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = critical_section::with(|cs| self.unlock_and_wait(cs, guard));
+        mutex.lock()
+    }
+
+    /// Releases the mutex of `guard` and blocks the current thread until it is
+    /// notified or until `timeout` has passed.
+    ///
+    /// Behaves like [`Self::wait()`]; the mutex is locked again before this function
+    /// returns, also if the timeout expired.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] alongside the guard if the thread was not notified
+    /// within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "time")]
+    pub fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        timeout: Duration,
+    ) -> (MutexGuard<'a, T>, Result<(), TimeoutError>) {
+        let deadline = crate::time::deadline_after(timeout);
+        let mutex = critical_section::with(|cs| {
+            let mutex = self.unlock_and_wait(cs, guard);
+            time::set_deadline(cs, deadline);
+            mutex
+        });
+        let res = critical_section::with(|cs| {
+            time::clear_deadline(cs).inspect_err(|_| {
+                let waiters = unsafe { &mut *self.waiters.get() };
+                waiters.remove_current(cs);
+            })
+        });
+        (mutex.lock(), res)
+    }
+
+    /// Releases the mutex of `guard` and puts the current thread into the waitlist.
+    fn unlock_and_wait<'a, T>(
+        &self,
+        cs: CriticalSection<'_>,
+        guard: MutexGuard<'a, T>,
+    ) -> &'a super::Mutex<T> {
+        // Release first, so that the thread is queued with its own priority rather
+        // than one it inherited as owner of the mutex.
+        let mutex = guard.unlock_cs(cs);
+        let waiters = unsafe { &mut *self.waiters.get() };
+        waiters.put_current(cs, ThreadState::CondvarBlocked);
+        // Context switch happens as soon as the critical section is left.
+        mutex
+    }
+
+    /// Wakes up the highest priority waiting thread, if any.
+    ///
+    /// This can be called from interrupt handlers.
+    pub fn notify_one(&self) {
+        critical_section::with(|cs| {
+            let waiters = unsafe { &mut *self.waiters.get() };
+            waiters.pop(cs);
+        });
+    }
+
+    /// Wakes up all waiting threads.
+    ///
+    /// This can be called from interrupt handlers.
+    pub fn notify_all(&self) {
+        critical_section::with(|cs| {
+            let waiters = unsafe { &mut *self.waiters.get() };
+            while waiters.pop(cs).is_some() {}
+        });
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
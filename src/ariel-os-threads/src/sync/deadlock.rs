@@ -0,0 +1,113 @@
+//! Detection of deadlocks between threads waiting for [`Mutex`](super::Mutex)es and
+//! [`Lock`](super::Lock)s.
+//!
+//! Before the current thread starts waiting for a mutex or lock, the chain of owners is
+//! followed: the owner of the mutex, the mutex or lock that owner waits for, its owner, and
+//! so on.
+//! If the chain leads back to the current thread, it would wait forever.
+//! The threads in the cycle and the addresses of the mutexes and locks they wait for are
+//! then logged as errors.
+//!
+//! The owner of a [`Lock`](super::Lock) is the thread that acquired it last; a lock created
+//! with [`Lock::new_locked()`](super::Lock::new_locked()) has no owner until it is acquired.
+//! As a lock may be released by another thread than its owner, locks are only part of the
+//! chain for the explicit checks of `lock_checked()` and `acquire_checked()`, whose errors
+//! can be handled, but not when [`Mutex::lock()`](super::Mutex::lock()) panics.
+use crate::{Scheduler, THREAD_COUNT, ThreadId, ThreadState};
+
+use super::{lock, mutex};
+
+/// Error returned when waiting for a [`Mutex`](super::Mutex) or [`Lock`](super::Lock) would
+/// deadlock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeadlockError;
+
+impl core::fmt::Display for DeadlockError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "deadlock detected")
+    }
+}
+
+impl Scheduler {
+    /// Checks whether the current thread would deadlock when waiting for the mutex or lock
+    /// identified by `addr`, which is owned by `owner`, and logs the cycle if so.
+    ///
+    /// Without `follow_locks`, the owner chain ends at threads waiting for a
+    /// [`Lock`](super::Lock).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeadlockError`] if the owner chain leads back to the current thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a thread context.
+    #[allow(
+        unused_variables,
+        reason = "only used for logging, which might be disabled"
+    )]
+    pub(crate) fn check_deadlock(
+        &self,
+        addr: usize,
+        owner: ThreadId,
+        follow_locks: bool,
+    ) -> Result<(), DeadlockError> {
+        let current = self
+            .current_tid()
+            .expect("Function should be called inside a thread context.");
+        if !self
+            .owner_chain(addr, owner, follow_locks)
+            .any(|(_, owner)| owner == current)
+        {
+            return Ok(());
+        }
+        ariel_os_debug::log::error!("deadlock detected:");
+        // Each thread in the cycle waits for a mutex or lock owned by the next one.
+        let chain = || self.owner_chain(addr, owner, follow_locks);
+        let waiters = core::iter::once(current).chain(chain().map(|(_, owner)| owner));
+        for (thread_id, (addr, owner)) in waiters.zip(chain()) {
+            ariel_os_debug::log::error!(
+                "  thread {} waits for {:#x}, owned by thread {}",
+                usize::from(thread_id),
+                addr,
+                usize::from(owner),
+            );
+            if owner == current {
+                break;
+            }
+        }
+        Err(DeadlockError)
+    }
+
+    /// Returns the mutexes and locks along the owner chain starting at the one identified
+    /// by `addr`, together with their owners.
+    ///
+    /// The chain is cut off after [`THREAD_COUNT`] links, as it must contain a cycle then.
+    fn owner_chain(
+        &self,
+        addr: usize,
+        owner: ThreadId,
+        follow_locks: bool,
+    ) -> impl Iterator<Item = (usize, ThreadId)> + '_ {
+        core::iter::successors(Some((addr, owner)), move |&(_, owner)| {
+            self.waits_for(owner, follow_locks)
+        })
+        .take(THREAD_COUNT)
+    }
+
+    /// Returns the address identifying the mutex or lock a thread waits for, together
+    /// with its owner, if it has one.
+    ///
+    /// Threads waiting for a lock are only considered with `follow_locks`.
+    fn waits_for(&self, thread_id: ThreadId, follow_locks: bool) -> Option<(usize, ThreadId)> {
+        match self.get_unchecked(thread_id).state {
+            ThreadState::MutexBlocked(state) => Some((state, mutex::owner(state)?)),
+            ThreadState::LockBlocked if follow_locks => {
+                let addr = self.waiting_for_lock[usize::from(thread_id)]?;
+                Some((addr, lock::owner(addr)?))
+            }
+            _ => None,
+        }
+    }
+}
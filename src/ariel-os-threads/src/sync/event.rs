@@ -4,8 +4,15 @@
 
 use core::cell::UnsafeCell;
 
+use critical_section::CriticalSection;
+
 use crate::{ThreadState, threadlist::ThreadList};
 
+#[cfg(feature = "time")]
+use crate::time::{self, TimeoutError};
+#[cfg(feature = "time")]
+use embassy_time::Duration;
+
 /// An [`Event`], allowing to notify multiple threads that some event has happened.
 ///
 /// An [`Event`] manages an internal flag that can be set to true with the [`Self::set()`] method and reset
@@ -64,15 +71,56 @@ impl Event {
     ///
     /// Panics if this is called outside of a thread context.
     pub fn wait(&self) {
+        critical_section::with(|cs| self.wait_if_unset(cs));
+    }
+
+    /// Waits for this [`Event`] to be set (blocking), giving up after `timeout`.
+    ///
+    /// Behaves like [`Self::wait()`], but returns once `timeout` has passed without
+    /// the event being set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the event was not set within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "time")]
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<(), TimeoutError> {
+        let deadline = crate::time::deadline_after(timeout);
+        let waiting = critical_section::with(|cs| {
+            let waiting = self.wait_if_unset(cs);
+            if waiting {
+                time::set_deadline(cs, deadline);
+            }
+            waiting
+        });
+        if !waiting {
+            return Ok(());
+        }
         critical_section::with(|cs| {
-            let state = unsafe { &mut *self.state.get() };
-            match state {
-                LockState::Unlocked => {}
-                LockState::Locked(waiters) => {
-                    waiters.put_current(cs, ThreadState::LockBlocked);
+            time::clear_deadline(cs).inspect_err(|_| {
+                let state = unsafe { &mut *self.state.get() };
+                if let LockState::Locked(waiters) = state {
+                    waiters.remove_current(cs);
                 }
+            })
+        })
+    }
+
+    /// Puts the current thread into the waitlist if the event is unset.
+    ///
+    /// Returns `true` if the current thread has to wait.
+    fn wait_if_unset(&self, cs: CriticalSection<'_>) -> bool {
+        let state = unsafe { &mut *self.state.get() };
+        match state {
+            LockState::Unlocked => false,
+            LockState::Locked(waiters) => {
+                waiters.put_current(cs, ThreadState::LockBlocked);
+                true
             }
-        });
+        }
     }
 
     /// Clears the event (non-blocking).
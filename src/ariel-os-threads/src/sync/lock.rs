@@ -1,14 +1,31 @@
 //! This module provides a Lock implementation.
 use core::cell::UnsafeCell;
 
+use critical_section::CriticalSection;
+
 use crate::{ThreadState, threadlist::ThreadList};
 
+#[cfg(feature = "deadlock-detection")]
+use super::DeadlockError;
+#[cfg(feature = "deadlock-detection")]
+use crate::{SCHEDULER, ThreadId};
+#[cfg(feature = "deadlock-detection")]
+use core::cell::Cell;
+
+#[cfg(feature = "time")]
+use crate::time::{self, TimeoutError};
+#[cfg(feature = "time")]
+use embassy_time::Duration;
+
 /// A basic locking object.
 ///
 /// A `Lock` behaves like a Mutex, but carries no data.
 /// This is supposed to be used to implement other locking primitives.
 pub struct Lock {
     state: UnsafeCell<LockState>,
+    /// Thread that acquired the lock last, if it is locked.
+    #[cfg(feature = "deadlock-detection")]
+    owner: Cell<Option<ThreadId>>,
 }
 
 unsafe impl Sync for Lock {}
@@ -24,6 +41,8 @@ impl Lock {
     pub const fn new() -> Self {
         Self {
             state: UnsafeCell::new(LockState::Unlocked),
+            #[cfg(feature = "deadlock-detection")]
+            owner: Cell::new(None),
         }
     }
 
@@ -32,6 +51,8 @@ impl Lock {
     pub const fn new_locked() -> Self {
         Self {
             state: UnsafeCell::new(LockState::Locked(ThreadList::new())),
+            #[cfg(feature = "deadlock-detection")]
+            owner: Cell::new(None),
         }
     }
 
@@ -56,14 +77,123 @@ impl Lock {
     /// Panics if this is called outside of a thread context.
     pub fn acquire(&self) {
         critical_section::with(|cs| {
-            let state = unsafe { &mut *self.state.get() };
-            match state {
-                LockState::Unlocked => *state = LockState::Locked(ThreadList::new()),
-                LockState::Locked(waiters) => {
-                    waiters.put_current(cs, ThreadState::LockBlocked);
-                }
+            self.acquire_or_wait(cs);
+        });
+    }
+
+    /// Get this lock like [`Self::acquire()`], but fail instead of waiting forever if the
+    /// owner of the lock is, directly or through other mutexes and locks, waiting for the
+    /// current thread.
+    ///
+    /// The owner of a lock is the thread that acquired it last.
+    /// As a lock may also be released by another thread, e.g., when it is used for
+    /// signaling, this can report patterns that don't actually deadlock; [`Self::acquire()`]
+    /// therefore doesn't check for deadlocks.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeadlockError`], after logging the involved threads, if waiting for the lock
+    /// would deadlock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "deadlock-detection")]
+    pub fn acquire_checked(&self) -> Result<(), DeadlockError> {
+        critical_section::with(|cs| {
+            self.check_deadlock(cs)?;
+            self.acquire_or_wait(cs);
+            Ok(())
+        })
+    }
+
+    /// Get this lock (blocking), giving up after `timeout`.
+    ///
+    /// Behaves like [`Self::acquire()`], but returns once `timeout` has passed without
+    /// the lock being acquired.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the lock could not be acquired within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "time")]
+    pub fn acquire_timeout(&self, timeout: Duration) -> Result<(), TimeoutError> {
+        let deadline = crate::time::deadline_after(timeout);
+        let waiting = critical_section::with(|cs| {
+            let waiting = self.acquire_or_wait(cs);
+            if waiting {
+                time::set_deadline(cs, deadline);
             }
+            waiting
         });
+        if !waiting {
+            return Ok(());
+        }
+        critical_section::with(|cs| {
+            time::clear_deadline(cs).inspect_err(|_| {
+                let state = unsafe { &mut *self.state.get() };
+                if let LockState::Locked(waiters) = state {
+                    waiters.remove_current(cs);
+                }
+                #[cfg(feature = "deadlock-detection")]
+                SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                    let thread_id = scheduler.current_tid().unwrap();
+                    scheduler.waiting_for_lock[usize::from(thread_id)] = None;
+                });
+            })
+        })
+    }
+
+    /// Locks the lock if it was unlocked, otherwise puts the current thread
+    /// into the waitlist.
+    ///
+    /// Returns `true` if the current thread has to wait.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    fn acquire_or_wait(&self, cs: CriticalSection<'_>) -> bool {
+        let state = unsafe { &mut *self.state.get() };
+        match state {
+            LockState::Unlocked => {
+                *state = LockState::Locked(ThreadList::new());
+                #[cfg(feature = "deadlock-detection")]
+                self.owner.set(crate::current_tid());
+                false
+            }
+            LockState::Locked(waiters) => {
+                #[cfg(feature = "deadlock-detection")]
+                SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                    let thread_id = scheduler.current_tid().unwrap();
+                    scheduler.waiting_for_lock[usize::from(thread_id)] =
+                        Some(core::ptr::from_ref(self) as usize);
+                });
+                waiters.put_current(cs, ThreadState::LockBlocked);
+                true
+            }
+        }
+    }
+
+    /// Checks whether the current thread would deadlock when waiting for this lock.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DeadlockError`] if the owner chain leads back to the current thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "deadlock-detection")]
+    fn check_deadlock(&self, cs: CriticalSection<'_>) -> Result<(), DeadlockError> {
+        let Some(owner) = self.owner.get() else {
+            return Ok(());
+        };
+        SCHEDULER.with_cs(cs, |scheduler| {
+            scheduler.check_deadlock(core::ptr::from_ref(self) as usize, owner, true)
+        })
     }
 
     /// Get the lock (non-blocking).
@@ -76,6 +206,8 @@ impl Lock {
             match state {
                 LockState::Unlocked => {
                     *state = LockState::Locked(ThreadList::new());
+                    #[cfg(feature = "deadlock-detection")]
+                    self.owner.set(crate::current_tid());
                     true
                 }
                 LockState::Locked(_) => false,
@@ -95,15 +227,39 @@ impl Lock {
             match state {
                 LockState::Unlocked => {}
                 LockState::Locked(waiters) => {
-                    if waiters.pop(cs).is_none() {
+                    let next_owner = waiters.pop(cs).map(|(thread_id, _)| thread_id);
+                    if next_owner.is_none() {
                         *state = LockState::Unlocked;
                     }
+                    #[cfg(feature = "deadlock-detection")]
+                    self.set_next_owner(cs, next_owner);
                 }
             }
         });
     }
 }
 
+#[cfg(feature = "deadlock-detection")]
+impl Lock {
+    /// Records the thread that acquired the lock on its release, if any.
+    fn set_next_owner(&self, cs: CriticalSection<'_>, next_owner: Option<ThreadId>) {
+        self.owner.set(next_owner);
+        if let Some(thread_id) = next_owner {
+            SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                scheduler.waiting_for_lock[usize::from(thread_id)] = None;
+            });
+        }
+    }
+}
+
+/// Returns the owner of the lock at `addr`, if it has one.
+#[cfg(feature = "deadlock-detection")]
+pub(super) fn owner(addr: usize) -> Option<ThreadId> {
+    // SAFETY: a lock can't be moved or dropped while a thread waits for it, and access
+    // to its owner only happens in critical sections.
+    unsafe { &*(addr as *const Lock) }.owner.get()
+}
+
 impl Default for Lock {
     fn default() -> Self {
         Self::new()
@@ -117,6 +273,7 @@ mod tests {
     #[test]
     fn check_type_sizes() {
         assert_eq!(size_of::<LockState>(), 2);
+        #[cfg(not(feature = "deadlock-detection"))]
         assert_eq!(size_of::<Lock>(), 2);
     }
 }
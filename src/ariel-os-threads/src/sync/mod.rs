@@ -1,10 +1,20 @@
 //! Synchronization primitives.
 mod channel;
+mod condvar;
+#[cfg(feature = "deadlock-detection")]
+mod deadlock;
 mod event;
 mod lock;
 mod mutex;
+mod queue;
+mod semaphore;
 
 pub use channel::Channel;
+pub use condvar::Condvar;
+#[cfg(feature = "deadlock-detection")]
+pub use deadlock::DeadlockError;
 pub use event::Event;
 pub use lock::Lock;
 pub use mutex::{Mutex, MutexGuard};
+pub use queue::Queue;
+pub use semaphore::Semaphore;
@@ -9,9 +9,20 @@ use core::{
 use ariel_os_runqueue::{RunqueueId, ThreadId};
 use critical_section::CriticalSection;
 
-use crate::{SCHEDULER, thread::ThreadState, threadlist::ThreadList};
+use crate::{SCHEDULER, Scheduler, thread::ThreadState, threadlist::ThreadList};
+
+#[cfg(feature = "deadlock-detection")]
+use super::DeadlockError;
+
+#[cfg(feature = "time")]
+use crate::time::{self, TimeoutError};
+#[cfg(feature = "time")]
+use embassy_time::Duration;
 
 /// A basic mutex with priority inheritance.
+///
+/// Priority inheritance is transitive: if the owner of a mutex is itself waiting for
+/// another mutex, the owner of that mutex inherits the priority as well.
 pub struct Mutex<T> {
     state: UnsafeCell<LockState>,
     inner: UnsafeCell<T>,
@@ -23,8 +34,6 @@ enum LockState {
     Locked {
         //. The current owner of the lock.
         owner_id: ThreadId,
-        /// The original priority of the current owner (without priority inheritance).
-        owner_prio: RunqueueId,
         //. Waiters for the mutex.
         waiters: ThreadList,
     },
@@ -38,16 +47,14 @@ impl LockState {
     ///
     /// Panics if called outside of a thread context.
     fn locked_with_current(cs: CriticalSection<'_>) -> Self {
-        let (owner_id, owner_prio) = SCHEDULER.with_mut_cs(cs, |mut scheduler| {
-            let current = scheduler
-                .current()
-                .expect("Function should be called inside a thread context.");
-            (current.tid, current.prio)
+        let owner_id = SCHEDULER.with_cs(cs, |scheduler| {
+            scheduler
+                .current_tid()
+                .expect("Function should be called inside a thread context.")
         });
         LockState::Locked {
             waiters: ThreadList::new(),
             owner_id,
-            owner_prio,
         }
     }
 }
@@ -78,41 +85,22 @@ impl<T> Mutex<T> {
     /// unlocked elsewhere.
     ///
     /// If the current owner of the mutex has a lower priority than the current thread, it will inherit
-    /// the waiting thread's priority.
-    /// The priority is reset once the mutex is released. This means that a **user can not change a thread's
-    /// priority while it holds the lock**, because it will be changed back after release!
+    /// the waiting thread's priority, and so will the owners of the mutexes that it is waiting for in turn.
+    /// The inherited priority is given up once the mutex is released, unless the owner still holds
+    /// another mutex that a thread with that priority is waiting for.
     ///
     /// # Panics
     ///
     /// Panics if called outside of a thread context.
+    /// With the `deadlock-detection` feature enabled, also panics if waiting for the mutex
+    /// would deadlock through a chain of mutexes, see `lock_checked()`.
     pub fn lock(&self) -> MutexGuard<'_, T> {
         critical_section::with(|cs| {
-            // SAFETY: access to the state only happens in critical sections, so it's always unique.
-            let state = unsafe { &mut *self.state.get() };
-            match state {
-                LockState::Unlocked => {
-                    *state = LockState::locked_with_current(cs);
-                }
-                LockState::Locked {
-                    waiters,
-                    owner_id,
-                    owner_prio,
-                } => {
-                    // Insert thread in waitlist, which also triggers the scheduler.
-                    match waiters.put_current(cs, ThreadState::LockBlocked) {
-                        // `Some` when the inserted thread is the highest priority
-                        // thread in the waitlist.
-                        Some(waiter_prio) if waiter_prio > *owner_prio => {
-                            // Current mutex owner inherits the priority.
-                            SCHEDULER.with_mut_cs(cs, |mut scheduler| {
-                                scheduler.set_priority(*owner_id, waiter_prio);
-                            });
-                        }
-                        _ => {}
-                    }
-                    // Context switch happens here as soon as we leave the critical section.
-                }
+            #[cfg(feature = "deadlock-detection")]
+            if let Err(err) = self.check_deadlock(cs, false) {
+                panic!("{}", err);
             }
+            self.lock_or_wait(cs)
         });
         // Mutex was either directly acquired because it was unlocked, or the current thread was entered
         // to the waitlist. In the latter case, it only continues running here after it was popped again
@@ -121,6 +109,132 @@ impl<T> Mutex<T> {
         MutexGuard::new(self)
     }
 
+    /// Acquires a mutex like [`Self::lock()`], but fails instead of waiting forever if the
+    /// owner of the mutex is, directly or through other mutexes and locks, waiting for the
+    /// current thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DeadlockError`], after logging the involved threads, if waiting for the mutex
+    /// would deadlock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a thread context.
+    #[cfg(feature = "deadlock-detection")]
+    pub fn lock_checked(&self) -> Result<MutexGuard<'_, T>, DeadlockError> {
+        critical_section::with(|cs| {
+            self.check_deadlock(cs, true)?;
+            self.lock_or_wait(cs);
+            Ok(())
+        })?;
+        Ok(MutexGuard::new(self))
+    }
+
+    /// Checks whether the current thread would deadlock when waiting for this mutex.
+    ///
+    /// With `follow_locks`, the owner chain is also followed through
+    /// [`Lock`](super::Lock)s.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DeadlockError`] if the owner chain leads back to the current thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a thread context.
+    #[cfg(feature = "deadlock-detection")]
+    fn check_deadlock(
+        &self,
+        cs: CriticalSection<'_>,
+        follow_locks: bool,
+    ) -> Result<(), DeadlockError> {
+        // SAFETY: access to the state only happens in critical sections, so it's always unique.
+        let state = unsafe { &*self.state.get() };
+        let LockState::Locked { owner_id, .. } = state else {
+            return Ok(());
+        };
+        SCHEDULER.with_cs(cs, |scheduler| {
+            scheduler.check_deadlock(self.state.get() as usize, *owner_id, follow_locks)
+        })
+    }
+
+    /// Acquires a mutex, blocking the current thread until it is able to do so or until `timeout`
+    /// has passed.
+    ///
+    /// Behaves like [`Self::lock()`], including the priority inheritance.
+    /// If the timeout expires, a priority that the owner inherited from the current thread is
+    /// given up again.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the mutex could not be acquired within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a thread context.
+    #[cfg(feature = "time")]
+    pub fn lock_timeout(&self, timeout: Duration) -> Result<MutexGuard<'_, T>, TimeoutError> {
+        let deadline = crate::time::deadline_after(timeout);
+        let waiting = critical_section::with(|cs| {
+            let waiting = self.lock_or_wait(cs);
+            if waiting {
+                time::set_deadline(cs, deadline);
+            }
+            waiting
+        });
+        if waiting {
+            critical_section::with(|cs| {
+                time::clear_deadline(cs).inspect_err(|_| {
+                    // SAFETY: access to the state only happens in critical sections, so it's always unique.
+                    let state = unsafe { &mut *self.state.get() };
+                    if let LockState::Locked { waiters, owner_id } = state {
+                        waiters.remove_current(cs);
+                        let owner_id = *owner_id;
+                        // The owner might have inherited the priority of the current thread.
+                        SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                            scheduler.update_inherited_priority(owner_id);
+                        });
+                    }
+                })
+            })?;
+        }
+        Ok(MutexGuard::new(self))
+    }
+
+    /// Locks the mutex if it was unlocked, otherwise puts the current thread into the
+    /// waitlist.
+    ///
+    /// Returns `true` if the current thread has to wait.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a thread context.
+    fn lock_or_wait(&self, cs: CriticalSection<'_>) -> bool {
+        // SAFETY: access to the state only happens in critical sections, so it's always unique.
+        let state = unsafe { &mut *self.state.get() };
+        match state {
+            LockState::Unlocked => {
+                *state = LockState::locked_with_current(cs);
+                false
+            }
+            LockState::Locked { waiters, owner_id } => {
+                // Insert thread in waitlist, which also triggers the scheduler.
+                // `Some` when the inserted thread is the highest priority thread in the waitlist.
+                let thread_state = ThreadState::MutexBlocked(self.state.get() as usize);
+                if waiters.put_current(cs, thread_state).is_some() {
+                    let owner_id = *owner_id;
+                    // Current mutex owner (and the owners of mutexes it waits for) inherit the priority.
+                    SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                        scheduler.update_inherited_priority(owner_id);
+                    });
+                }
+                // Context switch happens here as soon as we leave the critical section.
+                true
+            }
+        }
+    }
+
     /// Attempts to acquire this lock, in a non-blocking fashion.
     ///
     /// If the mutex was unlocked, it will be locked and a [`MutexGuard`] is returned.
@@ -142,36 +256,114 @@ impl<T> Mutex<T> {
     ///
     /// If there are waiters, the first waiter will be woken up.
     fn release(&self) {
-        critical_section::with(|cs| {
-            // SAFETY: access to the state only happens in critical sections, so it's always unique.
-            let state = unsafe { &mut *self.state.get() };
-            if let LockState::Locked {
-                waiters,
-                owner_id,
-                owner_prio,
-            } = state
-            {
-                // Reset original priority of owner.
-                SCHEDULER.with_mut_cs(cs, |mut scheduler| {
-                    scheduler.set_priority(*owner_id, *owner_prio);
-                });
-                // Pop next thread from waitlist so that it can acquire the mutex.
-                if let Some((tid, _)) = waiters.pop(cs) {
-                    SCHEDULER.with_mut_cs(cs, |scheduler| {
-                        *owner_id = tid;
-                        *owner_prio = scheduler.get_unchecked(tid).prio;
-                    });
-                } else {
-                    // Unlock if waitlist was empty.
-                    *state = LockState::Unlocked;
-                }
+        critical_section::with(|cs| self.release_cs(cs));
+    }
+
+    /// Releases the mutex within an existing critical section.
+    ///
+    /// See [`Self::release()`].
+    fn release_cs(&self, cs: CriticalSection<'_>) {
+        // SAFETY: access to the state only happens in critical sections, so it's always unique.
+        let state = unsafe { &mut *self.state.get() };
+        if let LockState::Locked { waiters, owner_id } = state {
+            let prev_owner = *owner_id;
+            // Pop next thread from waitlist so that it can acquire the mutex.
+            let next_owner = waiters.pop(cs).map(|(tid, _)| tid);
+            match next_owner {
+                Some(tid) => *owner_id = tid,
+                // Unlock if waitlist was empty.
+                None => *state = LockState::Unlocked,
             }
-        });
+            SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                // Give up the priority inherited through this mutex.
+                scheduler.update_inherited_priority(prev_owner);
+                // The new owner inherits the priority of the remaining waiters.
+                if let Some(tid) = next_owner {
+                    scheduler.update_inherited_priority(tid);
+                }
+            });
+        }
     }
 }
 
 unsafe impl<T> Sync for Mutex<T> {}
 
+/// Returns the owner of the mutex whose state is at `state`, if it is locked.
+#[cfg(feature = "deadlock-detection")]
+pub(super) fn owner(state: usize) -> Option<ThreadId> {
+    // SAFETY: a mutex can't be moved or dropped while a thread waits for it, and
+    // access to its state only happens in critical sections.
+    match unsafe { &*(state as *const LockState) } {
+        LockState::Locked { owner_id, .. } => Some(*owner_id),
+        LockState::Unlocked => None,
+    }
+}
+
+impl Scheduler {
+    /// Sets the priority that a thread has without priority inheritance.
+    ///
+    /// The thread keeps a higher priority inherited through a [`Mutex`] that it owns.
+    pub(crate) fn set_base_priority(&mut self, thread_id: ThreadId, prio: RunqueueId) {
+        if !self.is_valid_tid(thread_id) {
+            return;
+        }
+        self.get_unchecked_mut(thread_id).base_prio = prio;
+        self.update_inherited_priority(thread_id);
+    }
+
+    /// Updates the priority of a thread to the highest of its base priority and the
+    /// priorities of the threads that wait for a [`Mutex`] it owns.
+    ///
+    /// If the priority changed and the thread is waiting for a mutex itself, the owner
+    /// of that mutex is updated in turn, and so on down the chain.
+    fn update_inherited_priority(&mut self, mut thread_id: ThreadId) {
+        loop {
+            let prio = self.inherited_priority(thread_id);
+            let thread = self.get_unchecked(thread_id);
+            if prio == thread.prio {
+                return;
+            }
+            let ThreadState::MutexBlocked(state) = thread.state else {
+                self.set_priority(thread_id, prio);
+                return;
+            };
+            // Blocked threads aren't in the runqueue, only their priority changes.
+            self.get_unchecked_mut(thread_id).prio = prio;
+            #[cfg(feature = "trace")]
+            crate::trace::record(crate::trace::TraceEvent::Priority { thread_id, prio });
+            // SAFETY: a mutex can't be moved or dropped while a thread waits for it, and
+            // access to its state only happens in critical sections.
+            let state = unsafe { &mut *(state as *mut LockState) };
+            let LockState::Locked { waiters, owner_id } = state else {
+                unreachable!("waiting for an unlocked mutex");
+            };
+            // Keep the waitlist sorted by priority.
+            waiters.reorder(self, thread_id);
+            thread_id = *owner_id;
+        }
+    }
+
+    /// Returns the highest of the base priority of a thread and the priorities of the
+    /// threads that wait for a [`Mutex`] it owns.
+    fn inherited_priority(&self, thread_id: ThreadId) -> RunqueueId {
+        let mut prio = self.get_unchecked(thread_id).base_prio;
+        for waiter in &self.threads {
+            let ThreadState::MutexBlocked(state) = waiter.state else {
+                continue;
+            };
+            // SAFETY: a mutex can't be moved or dropped while a thread waits for it, and
+            // access to its state only happens in critical sections.
+            let state = unsafe { &*(state as *const LockState) };
+            if let LockState::Locked { owner_id, .. } = state
+                && *owner_id == thread_id
+            {
+                prio = prio.max(waiter.prio);
+            }
+        }
+        prio
+    }
+}
+
 /// Grants access to the [`Mutex`] inner data.
 ///
 /// Dropping the [`MutexGuard`] will unlock the [`Mutex`];
@@ -188,6 +380,18 @@ impl<'a, T> MutexGuard<'a, T> {
             _not_send: PhantomData,
         }
     }
+
+    /// Releases the mutex within an existing critical section and returns it.
+    ///
+    /// This is used by [`super::Condvar`] to atomically release the mutex and
+    /// start waiting.
+    pub(super) fn unlock_cs(self, cs: CriticalSection<'_>) -> &'a Mutex<T> {
+        let mutex = self.mutex;
+        // The mutex is released here instead of in `drop()`.
+        core::mem::forget(self);
+        mutex.release_cs(cs);
+        mutex
+    }
 }
 
 impl<T> Deref for MutexGuard<'_, T> {
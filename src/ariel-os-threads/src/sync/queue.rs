@@ -0,0 +1,253 @@
+//! Bounded multi-producer multi-consumer queue for sending data between threads.
+
+use core::cell::UnsafeCell;
+use core::mem::{ManuallyDrop, MaybeUninit};
+
+use critical_section::CriticalSection;
+use rbi::RingBufferIndex;
+
+use crate::{ThreadState, threadlist::ThreadList};
+
+/// Bounded blocking queue for sending data between threads.
+///
+/// The queue buffers up to `N` elements, so senders only block when the queue
+/// is full, and receivers only block when it is empty.
+/// Any number of threads may send and receive on the same queue.
+///
+/// [`Self::try_send()`] and [`Self::try_recv()`] never block and can be used
+/// from interrupt handlers, e.g., to feed data to a thread.
+///
+/// `N` must be a power of two between 2 and 128.
+/// The underlying ring buffer index cannot hold a single element; use a
+/// [`Channel`](super::Channel) for unbuffered hand-over instead.
+pub struct Queue<T, const N: usize> {
+    state: UnsafeCell<QueueState<T, N>>,
+}
+
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+struct QueueState<T, const N: usize> {
+    index: RingBufferIndex,
+    buffer: [MaybeUninit<T>; N],
+    /// Senders waiting for space.
+    senders: ThreadList,
+    /// Receivers waiting for data.
+    receivers: ThreadList,
+}
+
+impl<T, const N: usize> QueueState<T, N> {
+    /// Moves the value at `value` into the queue, or hands it to a waiting receiver.
+    ///
+    /// Returns `false` if the queue is full, in which case the value at `value`
+    /// has not been touched.
+    ///
+    /// # Safety
+    ///
+    /// `value` must point to a valid `T`. If `true` is returned, the caller must
+    /// treat the value as moved.
+    unsafe fn push(&mut self, cs: CriticalSection<'_>, value: *const T) -> bool {
+        // A waiting receiver implies that the buffer is empty.
+        if let Some((_, head_state)) = self.receivers.pop(cs) {
+            let ThreadState::QueueRxBlocked(ptr) = head_state else {
+                unreachable!("unexpected thread state");
+            };
+            // SAFETY: the receiver is blocked until it is scheduled again, so its
+            // destination is valid and not accessed concurrently.
+            unsafe { core::ptr::copy_nonoverlapping(value, ptr as *mut T, 1) };
+            return true;
+        }
+        let Some(pos) = self.index.put() else {
+            return false;
+        };
+        // SAFETY: the caller guarantees that `value` is valid, and treats it as moved.
+        self.buffer[usize::from(pos)].write(unsafe { value.read() });
+        true
+    }
+
+    /// Moves the oldest element of the queue to `dst`.
+    ///
+    /// If a sender was waiting for space, its value is moved into the queue.
+    ///
+    /// Returns `false` if the queue is empty, in which case `dst` has not been written.
+    ///
+    /// # Safety
+    ///
+    /// `dst` must be valid for writes.
+    unsafe fn pop(&mut self, cs: CriticalSection<'_>, dst: *mut T) -> bool {
+        let Some(pos) = self.index.get() else {
+            return false;
+        };
+        // SAFETY: only initialized elements are returned by the index.
+        unsafe { dst.write(self.buffer[usize::from(pos)].assume_init_read()) };
+
+        // A waiting sender implies that the buffer was full, there is space now.
+        if let Some((_, head_state)) = self.senders.pop(cs) {
+            let ThreadState::QueueTxBlocked(ptr) = head_state else {
+                unreachable!("unexpected thread state");
+            };
+            let Some(pos) = self.index.put() else {
+                unreachable!("queue full after pop");
+            };
+            // SAFETY: the sender is blocked until it is scheduled again, so its value
+            // is valid; the sender treats it as moved.
+            self.buffer[usize::from(pos)].write(unsafe { (ptr as *const T).read() });
+        }
+        true
+    }
+}
+
+impl<T, const N: usize> Queue<T, N> {
+    /// Returns a new empty [`Queue`].
+    #[must_use]
+    pub const fn new() -> Self {
+        const {
+            assert!(
+                N.is_power_of_two() && N >= 2 && N <= 128,
+                "the queue capacity must be a power of two between 2 and 128"
+            );
+        }
+        Self {
+            state: UnsafeCell::new(QueueState {
+                index: RingBufferIndex::new(N as u8),
+                buffer: [const { MaybeUninit::uninit() }; N],
+                senders: ThreadList::new(),
+                receivers: ThreadList::new(),
+            }),
+        }
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements currently in the queue.
+    pub fn len(&self) -> usize {
+        critical_section::with(|_| {
+            let state = unsafe { &*self.state.get() };
+            usize::from(state.index.available())
+        })
+    }
+
+    /// Returns whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether the queue is full.
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Sends on the queue (blocking).
+    ///
+    /// If the queue is full, the current thread is suspended until a receiver
+    /// made space.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn send(&self, value: T) {
+        // The value is moved out through its pointer, either below or by a receiver.
+        let value = ManuallyDrop::new(value);
+        let ptr = core::ptr::from_ref::<T>(&value);
+        critical_section::with(|cs| {
+            let state = unsafe { &mut *self.state.get() };
+            if !unsafe { state.push(cs, ptr) } {
+                // receiver will move the value into the queue
+                state
+                    .senders
+                    .put_current(cs, ThreadState::QueueTxBlocked(ptr as usize));
+            }
+        });
+
+        // ensure the compiler honors what happened to memory while the thread
+        // was scheduled away.
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Release);
+    }
+
+    /// Tries to send on the queue (non-blocking).
+    ///
+    /// This can be called from interrupt handlers.
+    ///
+    /// # Errors
+    ///
+    /// Returns the value back if the queue is full.
+    pub fn try_send(&self, value: T) -> Result<(), T> {
+        let value = ManuallyDrop::new(value);
+        let sent = critical_section::with(|cs| {
+            let state = unsafe { &mut *self.state.get() };
+            unsafe { state.push(cs, core::ptr::from_ref::<T>(&value)) }
+        });
+        if sent {
+            Ok(())
+        } else {
+            Err(ManuallyDrop::into_inner(value))
+        }
+    }
+
+    /// Receives from the queue (blocking).
+    ///
+    /// If the queue is empty, the current thread is suspended until a sender
+    /// provided data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn recv(&self) -> T {
+        let mut res: MaybeUninit<T> = MaybeUninit::uninit();
+
+        critical_section::with(|cs| {
+            let state = unsafe { &mut *self.state.get() };
+            let ptr = res.as_mut_ptr();
+            if !unsafe { state.pop(cs, ptr) } {
+                // sender will move the value
+                state
+                    .receivers
+                    .put_current(cs, ThreadState::QueueRxBlocked(ptr as usize));
+            }
+        });
+
+        // ensure the compiler honors what happened to memory while the thread
+        // was scheduled away.
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+
+        unsafe { res.assume_init() }
+    }
+
+    /// Tries to receive from the queue (non-blocking).
+    ///
+    /// This can be called from interrupt handlers.
+    ///
+    /// Returns `None` if the queue is empty.
+    pub fn try_recv(&self) -> Option<T> {
+        let mut res: MaybeUninit<T> = MaybeUninit::uninit();
+        let received = critical_section::with(|cs| {
+            let state = unsafe { &mut *self.state.get() };
+            unsafe { state.pop(cs, res.as_mut_ptr()) }
+        });
+
+        if received {
+            Some(unsafe { res.assume_init() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        let state = self.state.get_mut();
+        while let Some(pos) = state.index.get() {
+            // SAFETY: only initialized elements are returned by the index.
+            unsafe { state.buffer[usize::from(pos)].assume_init_drop() };
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! This module provides a counting semaphore.
+
+use core::cell::UnsafeCell;
+
+use critical_section::CriticalSection;
+
+use crate::{ThreadState, threadlist::ThreadList};
+
+#[cfg(feature = "time")]
+use crate::time::{self, TimeoutError};
+#[cfg(feature = "time")]
+use embassy_time::Duration;
+
+/// A counting semaphore.
+///
+/// A [`Semaphore`] manages a number of permits. [`Self::wait()`] takes a permit,
+/// blocking until one is available, and [`Self::post()`] returns one.
+///
+/// Waiting threads are woken up in order of their priority.
+/// [`Self::post()`] never blocks and can be used from interrupt handlers, e.g., to
+/// signal a thread that data is ready.
+pub struct Semaphore {
+    state: UnsafeCell<SemaphoreState>,
+}
+
+unsafe impl Sync for Semaphore {}
+
+struct SemaphoreState {
+    /// Number of available permits.
+    count: usize,
+    /// Threads waiting for a permit; only non-empty while `count` is zero.
+    waiters: ThreadList,
+}
+
+impl Semaphore {
+    /// Creates a new [`Semaphore`] with `count` available permits.
+    #[must_use]
+    pub const fn new(count: usize) -> Self {
+        Self {
+            state: UnsafeCell::new(SemaphoreState {
+                count,
+                waiters: ThreadList::new(),
+            }),
+        }
+    }
+
+    /// Returns the number of available permits.
+    pub fn count(&self) -> usize {
+        critical_section::with(|_| {
+            let state = unsafe { &*self.state.get() };
+            state.count
+        })
+    }
+
+    /// Takes a permit (blocking).
+    ///
+    /// If a permit is available, it is taken and the function returns.
+    /// Otherwise, the current thread is blocked until a permit is posted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn wait(&self) {
+        critical_section::with(|cs| self.take_or_wait(cs));
+    }
+
+    /// Takes a permit (blocking), giving up after `timeout`.
+    ///
+    /// Behaves like [`Self::wait()`], but returns once `timeout` has passed without
+    /// a permit becoming available.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if no permit could be taken within `timeout`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "time")]
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<(), TimeoutError> {
+        let deadline = crate::time::deadline_after(timeout);
+        let waiting = critical_section::with(|cs| {
+            let waiting = self.take_or_wait(cs);
+            if waiting {
+                time::set_deadline(cs, deadline);
+            }
+            waiting
+        });
+        if !waiting {
+            return Ok(());
+        }
+        critical_section::with(|cs| {
+            time::clear_deadline(cs).inspect_err(|_| {
+                let state = unsafe { &mut *self.state.get() };
+                state.waiters.remove_current(cs);
+            })
+        })
+    }
+
+    /// Takes a permit if one is available, otherwise puts the current thread
+    /// into the waitlist.
+    ///
+    /// Returns `true` if the current thread has to wait.
+    fn take_or_wait(&self, cs: CriticalSection<'_>) -> bool {
+        let state = unsafe { &mut *self.state.get() };
+        if state.count > 0 {
+            state.count -= 1;
+            false
+        } else {
+            // `post()` hands its permit over directly.
+            state.waiters.put_current(cs, ThreadState::SemaphoreBlocked);
+            true
+        }
+    }
+
+    /// Takes a permit (non-blocking).
+    ///
+    /// Returns `true` if a permit was taken, `false` if none was available.
+    pub fn try_wait(&self) -> bool {
+        critical_section::with(|_| {
+            let state = unsafe { &mut *self.state.get() };
+            if state.count > 0 {
+                state.count -= 1;
+                true
+            } else {
+                false
+            }
+        })
+    }
+
+    /// Posts a permit.
+    ///
+    /// If there are waiters, the permit is handed to the highest priority waiter,
+    /// which is woken up.
+    /// Otherwise, the number of available permits is increased, saturating at
+    /// [`usize::MAX`].
+    ///
+    /// This can be called from interrupt handlers.
+    pub fn post(&self) {
+        critical_section::with(|cs| {
+            let state = unsafe { &mut *self.state.get() };
+            if state.waiters.pop(cs).is_none() {
+                state.count = state.count.saturating_add(1);
+            }
+        });
+    }
+}
+
+impl Default for Semaphore {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
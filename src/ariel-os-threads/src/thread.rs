@@ -1,5 +1,8 @@
 use crate::{Arch, Cpu, RunqueueId, ThreadData, ThreadId, thread_flags::ThreadFlags};
 
+/// Byte that's used to paint stacks.
+pub(crate) const STACK_PAINT_COLOR: u8 = 0xCC;
+
 /// Main struct for holding thread data.
 #[derive(Debug)]
 pub struct Thread {
@@ -13,16 +16,20 @@ pub struct Thread {
     /// Priority of the thread between 0..[`super::SCHED_PRIO_LEVELS`].
     /// Multiple threads may have the same priority.
     pub prio: RunqueueId,
+    /// Priority of the thread without priority inheritance.
+    pub base_prio: RunqueueId,
     /// Id of the thread between 0..[`super::THREAD_COUNT`].
     /// Ids are unique while a thread is alive but reused after a thread finished.
     pub tid: ThreadId,
+    /// Name of the thread, if any.
+    pub name: Option<&'static str>,
     /// Flags set for the thread.
     pub flags: ThreadFlags,
     /// Arch-specific thread data.
     #[allow(dead_code)]
     pub(crate) data: ThreadData,
     /// Core affinity of the thread.
-    #[cfg(feature = "core-affinity")]
+    #[cfg(any(feature = "core-affinity", feature = "infini-core"))]
     pub core_affinity: crate::CoreAffinity,
 
     /// Lowest stack address.
@@ -32,8 +39,12 @@ pub struct Thread {
 }
 
 /// Possible states of a thread
+///
+/// Some variants only exist with certain features enabled, so matches on this need a
+/// wildcard arm.
 #[derive(Copy, Clone, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
 pub enum ThreadState {
     /// No active thread.
     Invalid,
@@ -44,14 +55,51 @@ pub enum ThreadState {
     Running,
     /// Suspended / paused.
     Parked,
-    /// Waiting to acquire a [`super::lock::Lock`].
+    /// Sleeping until a deadline, see [`crate::sleep()`].
+    #[cfg(feature = "time")]
+    Sleeping,
+    /// Waiting to acquire a [`crate::sync::Lock`].
     LockBlocked,
+    /// Waiting for a permit of a [`crate::sync::Semaphore`].
+    SemaphoreBlocked,
+    /// Waiting to be notified through a [`crate::sync::Condvar`].
+    CondvarBlocked,
+    /// Waiting to acquire a [`crate::sync::Mutex`].
+    ///
+    /// Holds the address of the mutex's state, to find its owner for priority inheritance.
+    MutexBlocked(usize),
     /// Waiting for [`ThreadFlags`] to be set.
     FlagBlocked(crate::thread_flags::WaitMode),
     /// Waiting to receive on a [`crate::sync::Channel`], i.e. waiting for the sender.
     ChannelRxBlocked(usize),
     /// Waiting to send on a [`crate::sync::Channel`], i.e. waiting for the receiver.
     ChannelTxBlocked(usize),
+    /// Waiting to receive on a [`crate::sync::Queue`], i.e. waiting for the queue to be non-empty.
+    QueueRxBlocked(usize),
+    /// Waiting to send on a [`crate::sync::Queue`], i.e. waiting for the queue to be non-full.
+    QueueTxBlocked(usize),
+    /// Waiting for a thread to finish, see [`crate::JoinHandle::join()`].
+    JoinBlocked(ThreadId),
+    /// Waiting to receive a [`crate::msg::Msg`].
+    ///
+    /// Holds the address the message is written to.
+    #[cfg(feature = "msg")]
+    MsgReceiveBlocked(usize),
+    /// Waiting to send a [`crate::msg::Msg`], i.e. waiting for the receiver's queue to
+    /// have space.
+    ///
+    /// Holds the address of the message.
+    #[cfg(feature = "msg")]
+    MsgSendBlocked(usize),
+    /// Like [`Self::MsgSendBlocked`], but waiting for a reply afterwards, see
+    /// [`crate::msg::send_receive()`].
+    #[cfg(feature = "msg")]
+    MsgSendReceiveBlocked(usize),
+    /// Waiting for the reply to a [`crate::msg::Msg`], see [`crate::msg::reply()`].
+    ///
+    /// Holds the address the reply is written to.
+    #[cfg(feature = "msg")]
+    MsgReplyBlocked(usize),
 }
 
 impl Thread {
@@ -62,8 +110,10 @@ impl Thread {
             data: Cpu::DEFAULT_THREAD_DATA,
             flags: 0,
             prio: RunqueueId::new(0),
+            base_prio: RunqueueId::new(0),
             tid: ThreadId::new(0),
-            #[cfg(feature = "core-affinity")]
+            name: None,
+            #[cfg(any(feature = "core-affinity", feature = "infini-core"))]
             core_affinity: crate::CoreAffinity::no_affinity(),
             stack_highest: 0,
             stack_lowest: 0,
@@ -76,9 +126,6 @@ impl Thread {
     /// - must only be called before the stack is active (within `arch::setup_stack()`).
     #[allow(dead_code, reason = "not used in all configurations")]
     pub(crate) unsafe fn stack_paint_init(&mut self, sp: usize) {
-        // Byte that's used to pain stacks.
-        const STACK_PAINT_COLOR: u8 = 0xCC;
-
         for pos in self.stack_lowest..sp {
             // SAFETY: Writing to the slice that was passed to `setup_stack()` is fine
             unsafe {
@@ -97,6 +144,10 @@ mod tests {
         // `ThreadData` is arch-specific, and is replaced with a dummy value in tests; its size is
         // non-zero otherwise.
         assert_eq!(size_of::<ThreadData>(), 0);
-        assert_eq!(size_of::<Thread>(), size_of::<ThreadData>() + 40);
+        // The thread name is a pointer and a length.
+        assert_eq!(
+            size_of::<Thread>(),
+            size_of::<ThreadData>() + 40 + size_of::<Option<&'static str>>()
+        );
     }
 }
@@ -1,6 +1,11 @@
 //! Thread flags.
 use crate::{SCHEDULER, Scheduler, ThreadId, ThreadState};
 
+#[cfg(feature = "time")]
+use crate::time::{self, TimeoutError};
+#[cfg(feature = "time")]
+use embassy_time::{Duration, Instant};
+
 /// Bitmask that represent the flags that are set for a thread.
 pub type ThreadFlags = u16;
 
@@ -72,6 +77,108 @@ pub fn wait_one(mask: ThreadFlags) -> ThreadFlags {
     }
 }
 
+/// Waits until all flags in `mask` are set for the current thread, or until `timeout` has passed.
+///
+/// Returns the set flags for this mask and clears them for the thread.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if the flags were not set within `timeout`.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+#[cfg(feature = "time")]
+pub fn wait_all_timeout(mask: ThreadFlags, timeout: Duration) -> Result<ThreadFlags, TimeoutError> {
+    wait_until(
+        WaitMode::All(mask),
+        crate::time::deadline_after(timeout),
+        Scheduler::flag_take_all,
+    )
+}
+
+/// Waits until any flag in `mask` is set for the current thread, or until `timeout` has passed.
+///
+/// Returns all set flags for this mask and clears them for the thread.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if none of the flags was set within `timeout`.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+#[cfg(feature = "time")]
+pub fn wait_any_timeout(mask: ThreadFlags, timeout: Duration) -> Result<ThreadFlags, TimeoutError> {
+    wait_until(
+        WaitMode::Any(mask),
+        crate::time::deadline_after(timeout),
+        Scheduler::flag_take_any,
+    )
+}
+
+/// Waits until any flag in `mask` is set for the current thread, or until `timeout` has passed.
+///
+/// Compared to [`wait_any_timeout`], this returns and clears only one flag
+/// from the mask.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if none of the flags was set within `timeout`.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+#[cfg(feature = "time")]
+pub fn wait_one_timeout(mask: ThreadFlags, timeout: Duration) -> Result<ThreadFlags, TimeoutError> {
+    wait_until(
+        WaitMode::Any(mask),
+        crate::time::deadline_after(timeout),
+        Scheduler::flag_take_one,
+    )
+}
+
+/// Waits for flags using `take` until `deadline`, blocking in `mode` in between.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if `take` did not succeed before `deadline`.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+#[cfg(feature = "time")]
+fn wait_until(
+    mode: WaitMode,
+    deadline: Instant,
+    take: fn(&mut Scheduler, ThreadFlags) -> Option<ThreadFlags>,
+) -> Result<ThreadFlags, TimeoutError> {
+    let (WaitMode::Any(mask) | WaitMode::All(mask)) = mode;
+    let mut blocked = false;
+    loop {
+        let res = critical_section::with(|cs| {
+            let timed_out = blocked && time::clear_deadline(cs).is_err();
+            // Flags that got set take precedence over an expired deadline.
+            if let Some(flags) =
+                SCHEDULER.with_mut_cs(cs, |mut scheduler| take(&mut scheduler, mask))
+            {
+                return Some(Ok(flags));
+            }
+            if timed_out || Instant::now() >= deadline {
+                return Some(Err(TimeoutError));
+            }
+            SCHEDULER.with_mut_cs(cs, |mut scheduler| scheduler.flag_block_current(mode));
+            time::set_deadline(cs, deadline);
+            // Context switch happens here as soon as we leave the critical section.
+            None
+        });
+        if let Some(res) = res {
+            return res;
+        }
+        blocked = true;
+    }
+}
+
 /// Clears flags for the current thread.
 ///
 /// # Panics
@@ -113,37 +220,72 @@ impl Scheduler {
     ///
     /// Panics if called outside a thread context.
     fn flag_wait_all(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+        let res = self.flag_take_all(mask);
+        if res.is_none() {
+            self.flag_block_current(WaitMode::All(mask));
+        }
+        res
+    }
+
+    /// # Panics
+    ///
+    /// Panics if called outside a thread context.
+    fn flag_wait_any(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+        let res = self.flag_take_any(mask);
+        if res.is_none() {
+            self.flag_block_current(WaitMode::Any(mask));
+        }
+        res
+    }
+
+    /// # Panics
+    ///
+    /// Panics if called outside a thread context.
+    fn flag_wait_one(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+        let res = self.flag_take_one(mask);
+        if res.is_none() {
+            self.flag_block_current(WaitMode::Any(mask));
+        }
+        res
+    }
+
+    /// Clears and returns `mask` if all its flags are set for the current thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a thread context.
+    fn flag_take_all(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
         let thread = self.current().unwrap();
         if thread.flags & mask == mask {
             thread.flags &= !mask;
             Some(mask)
         } else {
-            let thread_id = thread.tid;
-            self.set_state(thread_id, ThreadState::FlagBlocked(WaitMode::All(mask)));
             None
         }
     }
 
+    /// Clears and returns the flags of `mask` that are set for the current thread, if any.
+    ///
     /// # Panics
     ///
     /// Panics if called outside a thread context.
-    fn flag_wait_any(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+    fn flag_take_any(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
         let thread = self.current().unwrap();
         if thread.flags & mask != 0 {
             let res = thread.flags & mask;
             thread.flags &= !res;
             Some(res)
         } else {
-            let thread_id = thread.tid;
-            self.set_state(thread_id, ThreadState::FlagBlocked(WaitMode::Any(mask)));
             None
         }
     }
 
+    /// Clears and returns the lowest flag of `mask` that is set for the current thread, if any.
+    ///
     /// # Panics
     ///
     /// Panics if called outside a thread context.
-    fn flag_wait_one(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+    fn flag_take_one(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
         let thread = self.current().unwrap();
         if thread.flags & mask != 0 {
             let mut res = thread.flags & mask;
@@ -152,9 +294,17 @@ impl Scheduler {
             thread.flags &= !res;
             Some(res)
         } else {
-            let thread_id = thread.tid;
-            self.set_state(thread_id, ThreadState::FlagBlocked(WaitMode::Any(mask)));
             None
         }
     }
+
+    /// Blocks the current thread until flags matching `mode` are set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a thread context.
+    fn flag_block_current(&mut self, mode: WaitMode) {
+        let thread_id = self.current().unwrap().tid;
+        self.set_state(thread_id, ThreadState::FlagBlocked(mode));
+    }
 }
@@ -0,0 +1,106 @@
+//! Thread-local storage.
+use core::cell::UnsafeCell;
+
+use crate::{SCHEDULER, THREAD_COUNT};
+
+/// Declares thread-local statics of type [`ThreadLocal`].
+///
+/// Each thread gets its own value of the static, which is initialized with the given
+/// expression when the thread first accesses it.
+///
+/// # Examples
+///
+/// ```ignore
+/// use portable_atomic::{AtomicU32, Ordering};
+///
+/// ariel_os::thread::thread_local! {
+///     static COUNTER: AtomicU32 = AtomicU32::new(0);
+/// }
+///
+/// COUNTER.with(|counter| counter.fetch_add(1, Ordering::Relaxed));
+/// ```
+#[macro_export]
+macro_rules! thread_local {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::ThreadLocal<$ty> = $crate::ThreadLocal::new(|| $init);
+        )*
+    };
+}
+
+/// Thread-local value, usually declared with [`thread_local!`](crate::thread_local!).
+///
+/// There is one slot per [`ThreadId`](crate::ThreadId), which is initialized when a thread
+/// first accesses it.
+/// Once a thread has finished and its [`ThreadId`](crate::ThreadId) is reused, the new
+/// thread's first access drops the previous value and initializes the slot again.
+///
+/// As thread-local values can also be accessed from interrupt handlers, which access the
+/// value of the thread they interrupted, only shared references are handed out and `T`
+/// must be [`Sync`]; use atomics or other synchronization primitives for mutable state.
+pub struct ThreadLocal<T> {
+    init: fn() -> T,
+    /// Value and thread generation, see [`JoinHandle`](crate::JoinHandle), of each slot.
+    slots: [UnsafeCell<Option<(u16, T)>>; THREAD_COUNT],
+}
+
+// SAFETY: values are only written to while no references to them exist, see `with()`.
+// Values may be dropped by a different thread than the one that created them, so `T`
+// must be `Send`.
+unsafe impl<T: Send + Sync> Sync for ThreadLocal<T> {}
+
+impl<T> ThreadLocal<T> {
+    /// Creates a new [`ThreadLocal`] whose values are initialized with `init`.
+    #[must_use]
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            slots: [const { UnsafeCell::new(None) }; THREAD_COUNT],
+        }
+    }
+
+    /// Calls `f` with a reference to the current thread's value, initializing it first
+    /// if needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        let (thread_id, generation) = SCHEDULER.with(|scheduler| {
+            let thread_id = scheduler
+                .current_tid()
+                .expect("Function should be called inside a thread context.");
+            (
+                thread_id,
+                scheduler.join_slots[usize::from(thread_id)].generation(),
+            )
+        });
+        let slot = self.slots[usize::from(thread_id)].get();
+        let is_current = |slot: *mut Option<(u16, T)>| {
+            // SAFETY: only shared references exist while a slot is in use, see below.
+            matches!(unsafe { &*slot }, Some((slot_generation, _)) if *slot_generation == generation)
+        };
+        if !is_current(slot) {
+            // The initializer might access this `ThreadLocal` itself, so it must not be
+            // called while the slot is borrowed.
+            let value = (self.init)();
+            critical_section::with(|_| {
+                // An interrupt handler or a nested call might have initialized the slot
+                // in the meantime, and might still hold a reference to that value.
+                if !is_current(slot) {
+                    // SAFETY: references are only handed out to values of the current
+                    // generation, so no reference to the previous value exists anymore,
+                    // and the critical section prevents concurrent access.
+                    unsafe { *slot = Some((generation, value)) };
+                }
+            });
+        }
+        // SAFETY: the slot holds a value of the current generation, which is never
+        // written to again while the generation is current.
+        let Some((_, value)) = (unsafe { &*slot }) else {
+            unreachable!()
+        };
+        f(value)
+    }
+}
@@ -1,6 +1,6 @@
 use critical_section::CriticalSection;
 
-use crate::{RunqueueId, SCHEDULER, ThreadId, ThreadState, thread::Thread};
+use crate::{RunqueueId, SCHEDULER, Scheduler, ThreadId, ThreadState, thread::Thread};
 
 /// Manages blocked [`super::Thread`]s for a resource, and triggering the scheduler when needed.
 #[derive(Debug, Default)]
@@ -31,23 +31,7 @@ impl ThreadList {
             let &mut Thread { tid, prio, .. } = scheduler
                 .current()
                 .expect("Function should be called inside a thread context.");
-            let mut curr = None;
-            let mut next = self.head;
-            while let Some(n) = next {
-                if scheduler.get_unchecked_mut(n).prio < prio {
-                    break;
-                }
-                curr = next;
-                next = scheduler.thread_blocklist[usize::from(n)];
-            }
-            scheduler.thread_blocklist[usize::from(tid)] = next;
-            let inherit_priority = if let Some(curr) = curr {
-                scheduler.thread_blocklist[usize::from(curr)] = Some(tid);
-                None
-            } else {
-                self.head = Some(tid);
-                Some(prio)
-            };
+            let inherit_priority = self.insert(&mut scheduler, tid).then_some(prio);
             scheduler.set_state(tid, state);
             inherit_priority
         })
@@ -59,15 +43,116 @@ impl ThreadList {
     /// the scheduler.
     ///
     /// Returns the thread's [`ThreadId`] and its previous [`ThreadState`].
+    /// Returns `None` if there is no thread left that is still waiting.
     pub fn pop(&mut self, cs: CriticalSection<'_>) -> Option<(ThreadId, ThreadState)> {
-        let head = self.head?;
-        SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+        self.pop_with(cs, |_| ThreadState::Running)
+    }
+
+    /// Removes the head from this [`ThreadList`], like [`Self::pop()`], but sets the
+    /// thread's [`ThreadState`] to the one `next_state` returns for its previous state.
+    pub fn pop_with(
+        &mut self,
+        cs: CriticalSection<'_>,
+        next_state: impl FnOnce(ThreadState) -> ThreadState,
+    ) -> Option<(ThreadId, ThreadState)> {
+        self.head?;
+        SCHEDULER.with_mut_cs(cs, |mut scheduler| self.pop_in(&mut scheduler, next_state))
+    }
+
+    /// Removes the head from this [`ThreadList`], like [`Self::pop_with()`], for callers
+    /// that already have access to the scheduler.
+    pub(crate) fn pop_in(
+        &mut self,
+        scheduler: &mut Scheduler,
+        next_state: impl FnOnce(ThreadState) -> ThreadState,
+    ) -> Option<(ThreadId, ThreadState)> {
+        while let Some(head) = self.head {
             self.head = scheduler.thread_blocklist[usize::from(head)].take();
-            let old_state = scheduler.set_state(head, ThreadState::Running);
-            Some((head, old_state))
+            // A thread whose timeout expired has already been woken up, it just didn't
+            // get to remove itself from the list yet.
+            if cfg!(feature = "time") && scheduler.get_unchecked(head).state == ThreadState::Running
+            {
+                continue;
+            }
+            let state = next_state(scheduler.get_unchecked(head).state);
+            let old_state = scheduler.set_state(head, state);
+            return Some((head, old_state));
+        }
+        None
+    }
+
+    /// Removes the current thread from this [`ThreadList`], without changing its state.
+    ///
+    /// Returns `false` if the thread was not in the list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    #[cfg(feature = "time")]
+    pub fn remove_current(&mut self, cs: CriticalSection<'_>) -> bool {
+        SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+            let tid = scheduler
+                .current_tid()
+                .expect("Function should be called inside a thread context.");
+            self.remove(&mut scheduler, tid)
         })
     }
 
+    /// Moves a thread in this [`ThreadList`] to the position matching its current
+    /// priority, after the priority has changed.
+    ///
+    /// Returns `true` if the thread is the head of the list afterwards.
+    pub(crate) fn reorder(&mut self, scheduler: &mut Scheduler, thread_id: ThreadId) -> bool {
+        self.remove(scheduler, thread_id);
+        self.insert(scheduler, thread_id)
+    }
+
+    /// Inserts a thread into this [`ThreadList`], after all threads with the same or
+    /// a higher priority.
+    ///
+    /// Returns `true` if the thread is the new head of the list.
+    fn insert(&mut self, scheduler: &mut Scheduler, thread_id: ThreadId) -> bool {
+        let prio = scheduler.get_unchecked(thread_id).prio;
+        let mut curr = None;
+        let mut next = self.head;
+        while let Some(n) = next {
+            if scheduler.get_unchecked(n).prio < prio {
+                break;
+            }
+            curr = next;
+            next = scheduler.thread_blocklist[usize::from(n)];
+        }
+        scheduler.thread_blocklist[usize::from(thread_id)] = next;
+        if let Some(curr) = curr {
+            scheduler.thread_blocklist[usize::from(curr)] = Some(thread_id);
+            false
+        } else {
+            self.head = Some(thread_id);
+            true
+        }
+    }
+
+    /// Removes a thread from this [`ThreadList`], without changing its state.
+    ///
+    /// Returns `false` if the thread was not in the list.
+    fn remove(&mut self, scheduler: &mut Scheduler, thread_id: ThreadId) -> bool {
+        let mut curr: Option<ThreadId> = None;
+        let mut next = self.head;
+        while let Some(n) = next {
+            if n == thread_id {
+                let after = scheduler.thread_blocklist[usize::from(n)].take();
+                match curr {
+                    Some(curr) => scheduler.thread_blocklist[usize::from(curr)] = after,
+                    None => self.head = after,
+                }
+                return true;
+            }
+            curr = next;
+            next = scheduler.thread_blocklist[usize::from(n)];
+        }
+        false
+    }
+
     /// Determines if this [`ThreadList`] is empty.
     pub fn is_empty(&self, _cs: CriticalSection<'_>) -> bool {
         self.head.is_none()
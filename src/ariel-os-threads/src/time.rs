@@ -0,0 +1,379 @@
+//! Timed sleeping of threads and timeouts for blocking operations.
+//!
+//! Sleeping threads and threads blocked with a timeout are kept in a [`TimerQueue`]
+//! that is sorted by deadline.
+//! The deadlines are handled by [`run_timer()`], which runs as an embassy task: it waits
+//! for the earliest deadline, wakes up all expired threads, and is signaled whenever an
+//! earlier deadline is set in the meantime.
+//!
+//! The task must not be delayed by threads, or a busy thread would hold back the wakeups of
+//! higher-priority threads.
+//! An executor that runs in a thread therefore runs the task on a separate executor that is
+//! polled by the scheduler, from the context-switching interrupt, see
+//! [`set_timer_executor()`].
+use core::cell::Cell;
+
+use critical_section::{CriticalSection, Mutex};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::{Duration, Instant};
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::{SCHEDULER, Scheduler, THREAD_COUNT, ThreadId, ThreadState};
+
+/// Signals [`run_timer()`] that a deadline earlier than the one it waits for might have
+/// been set.
+static TIMER_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Polls the executor that runs [`run_timer()`], see [`set_timer_executor()`].
+static TIMER_EXECUTOR: Mutex<Cell<fn()>> = Mutex::new(Cell::new(|| {}));
+/// Whether [`TIMER_EXECUTOR`] needs to be polled.
+static TIMER_EXECUTOR_PENDING: AtomicBool = AtomicBool::new(false);
+/// Whether [`TIMER_EXECUTOR`] is being polled.
+static TIMER_EXECUTOR_POLLING: AtomicBool = AtomicBool::new(false);
+
+/// Deadline-sorted queue of threads that wait for a point in time.
+#[derive(Debug)]
+pub(crate) struct TimerQueue {
+    /// Thread with the earliest deadline.
+    head: Option<ThreadId>,
+    /// Next thread in the queue, indexed by [`ThreadId`].
+    next: [Option<ThreadId>; THREAD_COUNT],
+    /// Deadline of each queued thread, indexed by [`ThreadId`].
+    deadlines: [Instant; THREAD_COUNT],
+    /// Whether a blocked thread was woken up because its deadline passed, indexed by [`ThreadId`].
+    timed_out: [bool; THREAD_COUNT],
+}
+
+/// Error returned when a blocking operation did not complete before its timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimeoutError;
+
+impl TimerQueue {
+    /// Creates a new empty [`TimerQueue`].
+    pub const fn new() -> Self {
+        Self {
+            head: None,
+            next: [None; THREAD_COUNT],
+            deadlines: [Instant::MIN; THREAD_COUNT],
+            timed_out: [false; THREAD_COUNT],
+        }
+    }
+
+    /// Inserts a thread with a deadline.
+    ///
+    /// Threads with equal deadlines are kept in insertion order.
+    /// If the thread is already queued, its deadline is updated.
+    ///
+    /// Returns `true` if the thread has the earliest deadline in the queue.
+    pub fn insert(&mut self, thread_id: ThreadId, deadline: Instant) -> bool {
+        self.remove(thread_id);
+        self.deadlines[usize::from(thread_id)] = deadline;
+        let mut curr = None;
+        let mut next = self.head;
+        while let Some(n) = next {
+            if self.deadlines[usize::from(n)] > deadline {
+                break;
+            }
+            curr = next;
+            next = self.next[usize::from(n)];
+        }
+        self.next[usize::from(thread_id)] = next;
+        if let Some(curr) = curr {
+            self.next[usize::from(curr)] = Some(thread_id);
+            false
+        } else {
+            self.head = Some(thread_id);
+            true
+        }
+    }
+
+    /// Removes a thread from the queue.
+    ///
+    /// Returns `false` if the thread wasn't queued.
+    pub fn remove(&mut self, thread_id: ThreadId) -> bool {
+        let mut curr: Option<ThreadId> = None;
+        let mut next = self.head;
+        while let Some(n) = next {
+            if n == thread_id {
+                let after = self.next[usize::from(n)].take();
+                match curr {
+                    Some(curr) => self.next[usize::from(curr)] = after,
+                    None => self.head = after,
+                }
+                return true;
+            }
+            curr = next;
+            next = self.next[usize::from(n)];
+        }
+        false
+    }
+
+    /// Removes and returns the head of the queue if its deadline is at or before `now`.
+    pub fn pop_expired(&mut self, now: Instant) -> Option<ThreadId> {
+        let head = self.head?;
+        if self.deadlines[usize::from(head)] > now {
+            return None;
+        }
+        self.head = self.next[usize::from(head)].take();
+        Some(head)
+    }
+
+    /// Returns the earliest deadline in the queue.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.head.map(|head| self.deadlines[usize::from(head)])
+    }
+}
+
+impl Default for TimerQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Puts the current thread to sleep for (at least) `duration`.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn sleep(duration: Duration) {
+    sleep_until(deadline_after(duration));
+}
+
+/// Puts the current thread to sleep until `deadline` has passed.
+///
+/// Returns immediately if `deadline` is in the past.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn sleep_until(deadline: Instant) {
+    while Instant::now() < deadline {
+        critical_section::with(|cs| {
+            SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                let thread_id = scheduler
+                    .current_tid()
+                    .expect("Function should be called inside a thread context.");
+                scheduler.set_state(thread_id, ThreadState::Sleeping);
+            });
+            set_deadline(cs, deadline);
+            // Context switch happens here as soon as we leave the critical section.
+        });
+    }
+}
+
+/// Returns the deadline for a timeout of `timeout` from now.
+///
+/// Saturates at [`Instant::MAX`] instead of overflowing for long timeouts.
+pub(crate) fn deadline_after(timeout: Duration) -> Instant {
+    Instant::now().checked_add(timeout).unwrap_or(Instant::MAX)
+}
+
+/// Adds the current thread to the timer queue so that it is woken up at `deadline`.
+///
+/// This must be called in the same critical section in which the current thread
+/// is blocked.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub(crate) fn set_deadline(cs: CriticalSection<'_>, deadline: Instant) {
+    let is_next = SCHEDULER.with_mut_cs(cs, |mut scheduler| scheduler.set_deadline(deadline));
+    if is_next {
+        timer_changed();
+    }
+}
+
+/// Removes the current thread from the timer queue.
+///
+/// This must be called after a thread that was blocked with [`set_deadline()`]
+/// runs again.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if the thread was woken up because its deadline passed.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub(crate) fn clear_deadline(cs: CriticalSection<'_>) -> Result<(), TimeoutError> {
+    if SCHEDULER.with_mut_cs(cs, |mut scheduler| scheduler.clear_deadline()) {
+        Err(TimeoutError)
+    } else {
+        Ok(())
+    }
+}
+
+impl Scheduler {
+    /// Adds the current thread to the timer queue.
+    ///
+    /// Returns `true` if the timer needs to be (re-)armed for `deadline`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    fn set_deadline(&mut self, deadline: Instant) -> bool {
+        let thread_id = self
+            .current_tid()
+            .expect("Function should be called inside a thread context.");
+        self.timer_queue.timed_out[usize::from(thread_id)] = false;
+        self.timer_queue.insert(thread_id, deadline)
+    }
+
+    /// Removes the current thread from the timer queue.
+    ///
+    /// Returns `true` if the thread was woken up because its deadline passed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    fn clear_deadline(&mut self) -> bool {
+        let thread_id = self
+            .current_tid()
+            .expect("Function should be called inside a thread context.");
+        self.timer_queue.remove(thread_id);
+        core::mem::take(&mut self.timer_queue.timed_out[usize::from(thread_id)])
+    }
+
+    /// Wakes up all threads whose deadline has passed.
+    ///
+    /// Blocked threads are marked as timed out; they are responsible for removing
+    /// themselves from the [`ThreadList`](crate::threadlist::ThreadList) they are
+    /// waiting in.
+    ///
+    /// Returns the next deadline in the queue.
+    fn wake_expired(&mut self, now: Instant) -> Option<Instant> {
+        while let Some(thread_id) = self.timer_queue.pop_expired(now) {
+            match self.get_unchecked(thread_id).state {
+                ThreadState::Sleeping => {}
+                ThreadState::LockBlocked
+                | ThreadState::SemaphoreBlocked
+                | ThreadState::CondvarBlocked
+                | ThreadState::MutexBlocked(_)
+                | ThreadState::FlagBlocked(_)
+                | ThreadState::ChannelRxBlocked(_)
+                | ThreadState::ChannelTxBlocked(_) => {
+                    self.timer_queue.timed_out[usize::from(thread_id)] = true;
+                }
+                #[cfg(feature = "msg")]
+                ThreadState::MsgReceiveBlocked(_) => {
+                    self.timer_queue.timed_out[usize::from(thread_id)] = true;
+                }
+                _ => continue,
+            }
+            self.set_state(thread_id, ThreadState::Running);
+        }
+        self.timer_queue.next_deadline()
+    }
+}
+
+/// Signals [`run_timer()`] that the earliest deadline might have changed.
+///
+/// Must not be called while the scheduler is borrowed, because waking the timer task
+/// might access it, e.g., with an executor that runs in a thread.
+pub(crate) fn timer_changed() {
+    TIMER_CHANGED.signal(());
+}
+
+/// Handles the deadlines of the threads.
+///
+/// This must run as an embassy task once the time driver has been initialized, see
+/// `ariel_os_embassy::init_task()`.
+pub async fn run_timer() -> ! {
+    loop {
+        match on_timer(Instant::now()) {
+            Some(deadline) => {
+                let _ = embassy_time::with_deadline(deadline, TIMER_CHANGED.wait()).await;
+            }
+            None => TIMER_CHANGED.wait().await,
+        }
+    }
+}
+
+/// Makes the scheduler poll the executor that runs [`run_timer()`].
+///
+/// `poll` is called from the context-switching interrupt after each
+/// [`pend_timer_executor()`], before the next thread is picked, so that threads cannot
+/// delay the timer.
+/// It is never called concurrently or reentrantly.
+///
+/// This is set by `ariel_os_embassy` when its executor runs in a thread.
+pub fn set_timer_executor(poll: fn()) {
+    critical_section::with(|cs| TIMER_EXECUTOR.borrow(cs).set(poll));
+}
+
+/// Requests the executor set with [`set_timer_executor()`] to be polled.
+///
+/// This is meant to be called by the pender of that executor, from any context.
+pub fn pend_timer_executor() {
+    TIMER_EXECUTOR_PENDING.store(true, Ordering::SeqCst);
+    crate::schedule();
+}
+
+/// Polls the executor set with [`set_timer_executor()`] while it is pending.
+///
+/// This is called by the context-switching interrupt before picking the next thread.
+#[allow(dead_code, reason = "used in scheduler implementation")]
+pub(crate) fn poll_timer_executor() {
+    while TIMER_EXECUTOR_PENDING.load(Ordering::SeqCst) {
+        if TIMER_EXECUTOR_POLLING.swap(true, Ordering::SeqCst) {
+            // Another core is polling it and will see the request once it is done.
+            return;
+        }
+        TIMER_EXECUTOR_PENDING.store(false, Ordering::SeqCst);
+        critical_section::with(|cs| TIMER_EXECUTOR.borrow(cs).get())();
+        TIMER_EXECUTOR_POLLING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Handles all deadlines that have been reached by `now`.
+///
+/// Returns the next deadline.
+fn on_timer(now: Instant) -> Option<Instant> {
+    let next = SCHEDULER.with_mut(|mut scheduler| {
+        let next = scheduler.wake_expired(now);
+        #[cfg(all(feature = "time-slicing", not(feature = "infini-core")))]
+        let next = earliest(next, scheduler.time_slice_tick(now));
+        next
+    });
+    // The watchdog might reboot or call its hook, so this happens outside of the scheduler.
+    #[cfg(feature = "watchdog")]
+    let next = earliest(next, crate::watchdog::supervise(now));
+    next
+}
+
+/// Returns the earlier of two optional deadlines.
+#[allow(dead_code, reason = "only used with time-slicing or the watchdog")]
+fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timer_queue_ordering() {
+        let mut queue = TimerQueue::new();
+        assert!(queue.insert(ThreadId::new(0), Instant::from_ticks(20)));
+        assert!(queue.insert(ThreadId::new(1), Instant::from_ticks(10)));
+        assert!(!queue.insert(ThreadId::new(2), Instant::from_ticks(10)));
+        assert!(!queue.insert(ThreadId::new(3), Instant::from_ticks(30)));
+        assert_eq!(queue.next_deadline(), Some(Instant::from_ticks(10)));
+
+        assert!(queue.remove(ThreadId::new(2)));
+        assert!(!queue.remove(ThreadId::new(2)));
+        // Re-inserting moves the thread.
+        assert!(!queue.insert(ThreadId::new(3), Instant::from_ticks(15)));
+
+        let now = Instant::from_ticks(20);
+        assert_eq!(queue.pop_expired(now), Some(ThreadId::new(1)));
+        assert_eq!(queue.pop_expired(now), Some(ThreadId::new(3)));
+        assert_eq!(queue.pop_expired(now), Some(ThreadId::new(0)));
+        assert_eq!(queue.pop_expired(now), None);
+        assert_eq!(queue.next_deadline(), None);
+    }
+}
@@ -0,0 +1,225 @@
+//! Time-slicing among threads of the same priority.
+//!
+//! A periodic tick, driven by [`run_timer()`](crate::time::run_timer), checks for how long
+//! the thread on each core has been running.
+//! As the timer runs in interrupt context, threads of any priority are sliced, including
+//! threads with a higher priority than an executor thread.
+//! Once a thread has used up its time slice while other threads of the same priority
+//! are ready, the runqueue of that priority is advanced, as if the thread had called
+//! [`yield_same()`](crate::yield_same).
+//!
+//! The tick only runs while a running thread has a time slice and shares its priority with
+//! another ready thread, so it doesn't keep idle cores from entering deep sleep.
+//! It is started again when such a thread becomes ready or starts running, see
+//! [`start_tick_if_needed()`].
+use embassy_time::{Duration, Instant};
+
+use crate::{CORE_COUNT, RunqueueId, SCHEDULER, Scheduler, THREAD_COUNT, ThreadId, ThreadState};
+
+#[cfg(feature = "multi-core")]
+use crate::{CoreId, schedule_on_core};
+
+/// Default time slice of a thread.
+pub const DEFAULT_TIME_SLICE: Duration = Duration::from_millis(10);
+
+/// Period of the time-slicing tick.
+///
+/// Time slices are effectively rounded up to a multiple of this.
+const TICK: Duration = Duration::from_millis(1);
+
+/// Time-slicing bookkeeping.
+#[derive(Debug)]
+pub(crate) struct TimeSlicer {
+    /// Time slice of each thread, indexed by [`ThreadId`].
+    ///
+    /// `None` if the thread is never preempted by threads of the same priority.
+    slices: [Option<Duration>; THREAD_COUNT],
+    /// Thread that was running on each core at the last tick, and since when.
+    running: [Option<(ThreadId, Instant)>; CORE_COUNT],
+    /// Whether the tick is running.
+    ticking: bool,
+}
+
+impl TimeSlicer {
+    /// Creates a new [`TimeSlicer`].
+    pub const fn new() -> Self {
+        Self {
+            slices: [Some(DEFAULT_TIME_SLICE); THREAD_COUNT],
+            running: [None; CORE_COUNT],
+            ticking: false,
+        }
+    }
+}
+
+/// Sets the time slice of a thread.
+///
+/// When other threads with the same priority are ready, the thread is preempted after
+/// having run for `slice`.
+/// With `None`, the thread is only preempted by threads with a higher priority, and
+/// has to use [`yield_same()`](crate::yield_same) to share the CPU.
+///
+/// Threads start with [`DEFAULT_TIME_SLICE`].
+/// Does nothing if this is not a valid thread.
+pub fn set_time_slice(thread_id: ThreadId, slice: Option<Duration>) {
+    let start = SCHEDULER.with_mut(|mut scheduler| {
+        if scheduler.is_valid_tid(thread_id) {
+            scheduler.time_slicer.slices[usize::from(thread_id)] = slice;
+        }
+        scheduler.start_tick()
+    });
+    if start {
+        crate::time::timer_changed();
+    }
+}
+
+/// Returns the time slice of a thread, see [`set_time_slice()`].
+///
+/// Returns `None` if this is not a valid thread or if it has no time slice.
+pub fn get_time_slice(thread_id: ThreadId) -> Option<Duration> {
+    SCHEDULER.with(|scheduler| {
+        scheduler
+            .is_valid_tid(thread_id)
+            .then(|| scheduler.time_slicer.slices[usize::from(thread_id)])
+            .flatten()
+    })
+}
+
+/// Starts the tick if a running thread needs to be preempted once its time slice is used up.
+///
+/// This is called after each context switch.
+#[allow(dead_code, reason = "used in scheduler implementation")]
+pub(crate) fn start_tick_if_needed() {
+    if SCHEDULER.with_mut(|mut scheduler| scheduler.start_tick()) {
+        crate::time::timer_changed();
+    }
+}
+
+impl Scheduler {
+    /// Resets the time slice of a newly created thread.
+    pub(crate) fn reset_time_slice(&mut self, thread_id: ThreadId) {
+        self.time_slicer.slices[usize::from(thread_id)] = Some(DEFAULT_TIME_SLICE);
+    }
+
+    /// Marks the tick as running if it is needed but isn't running.
+    ///
+    /// Returns `true` if the timer task has to be signaled, which must happen after the
+    /// scheduler has been released.
+    fn start_tick(&mut self) -> bool {
+        if self.time_slicer.ticking || !self.needs_tick() {
+            return false;
+        }
+        self.time_slicer.ticking = true;
+        true
+    }
+
+    /// Schedules if a thread that just became ready requires starting the tick, so that
+    /// it is started after the scheduler has been released, see [`start_tick_if_needed()`].
+    pub(crate) fn schedule_if_tick_needed(&self) {
+        if !self.time_slicer.ticking && self.needs_tick() {
+            crate::schedule();
+        }
+    }
+
+    /// Returns whether a running thread has a time slice and another thread with the same
+    /// priority is ready.
+    fn needs_tick(&self) -> bool {
+        (0..CORE_COUNT).any(|core| {
+            let Some(thread_id) = self.current_on_core(core) else {
+                return false;
+            };
+            let thread = self.get_unchecked(thread_id);
+            thread.state == ThreadState::Running
+                && self.time_slicer.slices[usize::from(thread_id)].is_some()
+                && self.has_other_ready(thread_id, thread.prio)
+        })
+    }
+
+    /// Returns whether a thread other than `thread_id` is ready with priority `prio`.
+    fn has_other_ready(&self, thread_id: ThreadId, prio: RunqueueId) -> bool {
+        // On single-core, the running thread is part of the runqueue.
+        #[cfg(feature = "single-core")]
+        {
+            self.runqueue.peek_head(prio) != Some(thread_id)
+                || self
+                    .runqueue
+                    .iter_from(thread_id, prio)
+                    .next()
+                    .is_some_and(|next| self.get_unchecked(next).prio == prio)
+        }
+        // On multi-core, running threads are not in the runqueue.
+        #[cfg(feature = "multi-core")]
+        {
+            let _ = thread_id;
+            self.runqueue.peek_head(prio).is_some()
+        }
+    }
+
+    /// Advances the runqueues of threads that have used up their time slice.
+    ///
+    /// Returns when the next tick is due, or `None` if the tick isn't needed anymore.
+    pub(crate) fn time_slice_tick(&mut self, now: Instant) -> Option<Instant> {
+        if !self.time_slicer.ticking {
+            return None;
+        }
+        for core in 0..CORE_COUNT {
+            let Some(thread_id) = self.current_on_core(core) else {
+                self.time_slicer.running[core] = None;
+                continue;
+            };
+            match self.time_slicer.running[core] {
+                Some((running, since)) if running == thread_id => {
+                    let Some(slice) = self.time_slicer.slices[usize::from(thread_id)] else {
+                        continue;
+                    };
+                    if now.saturating_duration_since(since) < slice {
+                        continue;
+                    }
+                    // Start a new slice, whether the thread is preempted or not.
+                    self.time_slicer.running[core] = Some((thread_id, now));
+                    let thread = self.get_unchecked(thread_id);
+                    if thread.state == ThreadState::Running {
+                        self.preempt(core, thread.prio);
+                    }
+                }
+                // The thread started running since the last tick.
+                _ => self.time_slicer.running[core] = Some((thread_id, now)),
+            }
+        }
+        if self.needs_tick() {
+            return Some(now + TICK);
+        }
+        // Slices start over once the tick is started again.
+        self.time_slicer.ticking = false;
+        self.time_slicer.running = [None; CORE_COUNT];
+        None
+    }
+
+    /// Returns the thread that is running on `core`.
+    #[allow(unused_variables, reason = "core only used on multi-core")]
+    fn current_on_core(&self, core: usize) -> Option<ThreadId> {
+        #[cfg(feature = "single-core")]
+        {
+            self.current_thread
+        }
+        #[cfg(feature = "multi-core")]
+        {
+            self.current_threads[core]
+        }
+    }
+
+    /// Lets the next thread with priority `prio` run on `core`, if there is one.
+    #[allow(unused_variables, reason = "core only used on multi-core")]
+    fn preempt(&mut self, core: usize, prio: RunqueueId) {
+        #[cfg(feature = "single-core")]
+        if self.runqueue.advance(prio) {
+            crate::schedule();
+        }
+
+        // On multi-core, the running thread is re-added at the tail of the runqueue
+        // when the scheduler is invoked, see `yield_same()`.
+        #[cfg(feature = "multi-core")]
+        if !self.runqueue.is_empty(prio) {
+            schedule_on_core(CoreId(core as u8));
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! Tracing of scheduler events.
+//!
+//! Context switches, thread state and priority changes are recorded into a RAM ring
+//! buffer, together with interrupt handler entries and exits.
+//! When the buffer is full, the oldest records are overwritten.
+//!
+//! The scheduler's own interrupt handler is always traced.
+//! Other interrupt handlers are provided by the HALs, so they only show up if they call
+//! [`isr_enter()`] and [`isr_exit()`] themselves.
+//! On native, threads run concurrently on host threads, so a switch is recorded whenever a
+//! host thread is resumed or stops running, and no interrupts are traced.
+//!
+//! [`print()`] drains the buffer to the debug console in the JSON array format of the
+//! [Trace Event Format], which can be opened with [Perfetto](https://ui.perfetto.dev) or
+//! `chrome://tracing` on the host: copy the printed lines, starting with `[`, into a
+//! `.json` file.
+//! Threads are shown as process 0, with one track per thread; interrupts are shown as
+//! process 1, with one track per core.
+//!
+//! The size of the buffer is [`TRACE_BUFFER_SIZE`] records, which can be configured
+//! using the `CONFIG_THREAD_TRACE_BUFFER_SIZE` environment variable.
+//!
+//! [Trace Event Format]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+use core::{cell::RefCell, fmt};
+
+use critical_section::Mutex;
+use embassy_time::Instant;
+
+use crate::{RunqueueId, ThreadId, ThreadState, core_id};
+
+/// Number of records the trace buffer holds.
+pub const TRACE_BUFFER_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_THREAD_TRACE_BUFFER_SIZE",
+    64,
+    "number of records in the scheduler trace buffer"
+);
+
+const _: () = assert!(
+    TRACE_BUFFER_SIZE > 0,
+    "`CONFIG_THREAD_TRACE_BUFFER_SIZE` must not be 0"
+);
+
+/// Scheduler event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TraceEvent {
+    /// The core switched from running thread `from` (if any) to thread `to` (if any).
+    ///
+    /// On native, each switch either resumes or stops a single thread.
+    Switch {
+        /// Thread that was running before.
+        from: Option<ThreadId>,
+        /// Thread that runs now.
+        to: Option<ThreadId>,
+    },
+    /// The state of a thread changed.
+    State {
+        /// Id of the thread.
+        thread_id: ThreadId,
+        /// New state of the thread.
+        state: ThreadState,
+    },
+    /// The priority of a thread changed.
+    Priority {
+        /// Id of the thread.
+        thread_id: ThreadId,
+        /// New priority of the thread.
+        prio: RunqueueId,
+    },
+    /// An interrupt handler was entered.
+    IsrEnter {
+        /// Number of the interrupt.
+        irq: u16,
+    },
+    /// An interrupt handler was left.
+    IsrExit,
+}
+
+/// Recorded scheduler event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TraceRecord {
+    /// Time at which the event happened.
+    pub timestamp: Instant,
+    /// Core on which the event happened.
+    pub core: u8,
+    /// The event.
+    pub event: TraceEvent,
+}
+
+struct TraceBuffer {
+    records: [Option<TraceRecord>; TRACE_BUFFER_SIZE],
+    /// Index of the oldest record.
+    head: usize,
+    len: usize,
+    /// Number of records that have been overwritten before being drained.
+    overwritten: usize,
+}
+
+impl TraceBuffer {
+    const fn new() -> Self {
+        Self {
+            records: [None; TRACE_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+            overwritten: 0,
+        }
+    }
+
+    fn push(&mut self, record: TraceRecord) {
+        let tail = (self.head + self.len) % TRACE_BUFFER_SIZE;
+        self.records[tail] = Some(record);
+        if self.len == TRACE_BUFFER_SIZE {
+            self.head = (self.head + 1) % TRACE_BUFFER_SIZE;
+            self.overwritten += 1;
+        } else {
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<TraceRecord> {
+        if self.len == 0 {
+            return None;
+        }
+        let record = self.records[self.head].take();
+        self.head = (self.head + 1) % TRACE_BUFFER_SIZE;
+        self.len -= 1;
+        record
+    }
+}
+
+static TRACE_BUFFER: Mutex<RefCell<TraceBuffer>> = Mutex::new(RefCell::new(TraceBuffer::new()));
+
+/// Records an event.
+pub(crate) fn record(event: TraceEvent) {
+    let record = TraceRecord {
+        timestamp: Instant::now(),
+        core: core_id().0,
+        event,
+    };
+    critical_section::with(|cs| TRACE_BUFFER.borrow_ref_mut(cs).push(record));
+}
+
+/// Records the entry into the handler of interrupt `irq`.
+///
+/// This is meant to be called at the start of interrupt handlers that should show up
+/// in the trace, as only the scheduler's own interrupt handler is traced automatically.
+pub fn isr_enter(irq: u16) {
+    record(TraceEvent::IsrEnter { irq });
+}
+
+/// Records the exit from the interrupt handler entered last, see [`isr_enter()`].
+pub fn isr_exit() {
+    record(TraceEvent::IsrExit);
+}
+
+/// Removes and returns the oldest record from the trace buffer.
+#[must_use]
+pub fn pop() -> Option<TraceRecord> {
+    critical_section::with(|cs| TRACE_BUFFER.borrow_ref_mut(cs).pop())
+}
+
+/// Returns the number of records that were overwritten before they could be drained,
+/// and resets it.
+#[must_use]
+pub fn take_overwritten() -> usize {
+    critical_section::with(|cs| core::mem::take(&mut TRACE_BUFFER.borrow_ref_mut(cs).overwritten))
+}
+
+/// Drains the trace buffer to the debug console, in the JSON array format of the Trace
+/// Event Format.
+///
+/// Each trace event is printed on its own line.
+/// Thread names are included as metadata.
+pub fn print() {
+    ariel_os_debug::println!("[");
+    for info in crate::threads() {
+        if let Some(name) = info.name {
+            print_line(format_args!(
+                r#"{{"name":"thread_name","ph":"M","pid":0,"tid":{},"args":{{"name":"{}"}}}},"#,
+                usize::from(info.tid),
+                JsonStr(name),
+            ));
+        }
+    }
+    while let Some(record) = pop() {
+        print_line(format_args!("{},", ChromeJson(&record)));
+    }
+}
+
+fn print_line(args: fmt::Arguments<'_>) {
+    #[cfg(feature = "defmt")]
+    let args = defmt::Display2Format(&args);
+    ariel_os_debug::println!("{}", args);
+}
+
+/// Formats a [`TraceRecord`] as one or more comma-separated events of the Trace Event
+/// Format.
+#[derive(Debug)]
+pub struct ChromeJson<'a>(pub &'a TraceRecord);
+
+impl fmt::Display for ChromeJson<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let TraceRecord {
+            timestamp,
+            core,
+            event,
+        } = self.0;
+        let ts = timestamp.as_micros();
+        match event {
+            TraceEvent::Switch { from, to } => {
+                if let Some(from) = from {
+                    write!(
+                        f,
+                        r#"{{"name":"running","ph":"E","ts":{ts},"pid":0,"tid":{}}}"#,
+                        usize::from(*from)
+                    )?;
+                }
+                if let Some(to) = to {
+                    if from.is_some() {
+                        f.write_str(",")?;
+                    }
+                    write!(
+                        f,
+                        r#"{{"name":"running","ph":"B","ts":{ts},"pid":0,"tid":{},"args":{{"core":{core}}}}}"#,
+                        usize::from(*to)
+                    )?;
+                }
+                Ok(())
+            }
+            TraceEvent::State { thread_id, state } => write!(
+                f,
+                r#"{{"name":"{}","ph":"i","s":"t","ts":{ts},"pid":0,"tid":{}}}"#,
+                JsonStr(&StateName(*state)),
+                usize::from(*thread_id)
+            ),
+            TraceEvent::Priority { thread_id, prio } => write!(
+                f,
+                r#"{{"name":"prio {tid}","ph":"C","ts":{ts},"pid":0,"tid":{tid},"args":{{"prio":{}}}}}"#,
+                usize::from(*prio),
+                tid = usize::from(*thread_id),
+            ),
+            TraceEvent::IsrEnter { irq } => write!(
+                f,
+                r#"{{"name":"irq {irq}","ph":"B","ts":{ts},"pid":1,"tid":{core}}}"#
+            ),
+            TraceEvent::IsrExit => write!(f, r#"{{"ph":"E","ts":{ts},"pid":1,"tid":{core}}}"#),
+        }
+    }
+}
+
+/// Formats a [`ThreadState`] using its [`Debug`](fmt::Debug) implementation.
+struct StateName(ThreadState);
+
+impl fmt::Display for StateName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+/// Formats a string escaped for use inside a JSON string.
+struct JsonStr<T>(T);
+
+impl<T: fmt::Display> fmt::Display for JsonStr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write;
+
+        struct Escaper<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+        impl Write for Escaper<'_, '_> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                for c in s.chars() {
+                    match c {
+                        '"' | '\\' => {
+                            self.0.write_char('\\')?;
+                            self.0.write_char(c)?;
+                        }
+                        c if c.is_control() => write!(self.0, "\\u{:04x}", u32::from(c))?,
+                        c => self.0.write_char(c)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        write!(Escaper(f), "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn chrome_json() {
+        let record = TraceRecord {
+            timestamp: Instant::from_micros(42),
+            core: 0,
+            event: TraceEvent::Switch {
+                from: Some(ThreadId::new(1)),
+                to: Some(ThreadId::new(2)),
+            },
+        };
+        assert_eq!(
+            ChromeJson(&record).to_string(),
+            r#"{"name":"running","ph":"E","ts":42,"pid":0,"tid":1},{"name":"running","ph":"B","ts":42,"pid":0,"tid":2,"args":{"core":0}}"#
+        );
+        let record = TraceRecord {
+            event: TraceEvent::Switch {
+                from: Some(ThreadId::new(1)),
+                to: None,
+            },
+            ..record
+        };
+        assert_eq!(
+            ChromeJson(&record).to_string(),
+            r#"{"name":"running","ph":"E","ts":42,"pid":0,"tid":1}"#
+        );
+        assert_eq!(JsonStr("a\"b\\c\n").to_string(), r#"a\"b\\c\u000a"#);
+    }
+}
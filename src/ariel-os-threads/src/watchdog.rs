@@ -0,0 +1,254 @@
+//! Software watchdog supervising threads.
+//!
+//! A thread [`register()`]s with a timeout and then has to [`checkin()`] at least once per
+//! timeout.
+//! The supervisor is driven by the timer task of the scheduler, see
+//! [`run_timer()`](crate::time::run_timer), which threads cannot delay, so even a thread
+//! that busy-loops at the highest priority is caught: when a registered thread misses its
+//! check-in, its [`ThreadId`] and state, which includes what it is blocked on, are logged,
+//! and the [`Policy`] set with [`set_policy()`] is applied.
+//! A thread that missed its check-in is only supervised again after its next check-in.
+//!
+//! The registration of a thread ends when it calls [`unregister()`] or finishes.
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embassy_time::{Duration, Instant};
+
+use crate::{SCHEDULER, THREAD_COUNT, ThreadId};
+
+/// Function that is called with the [`ThreadId`] of a thread that missed its check-in,
+/// see [`Policy::Callback`].
+pub type WatchdogHook = fn(ThreadId);
+
+/// What happens when a thread misses its check-in.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Policy {
+    /// Reboots the MCU using [`ariel_os_power::reboot()`].
+    #[default]
+    Reboot,
+    /// Calls the hook, e.g., to restart the thread or to notify the application.
+    ///
+    /// The hook is called from the timer task of the scheduler, which usually runs in the
+    /// context-switching interrupt, so it must not block.
+    Callback(WatchdogHook),
+}
+
+/// Registration of a thread.
+#[derive(Clone, Copy, Debug)]
+struct Registration {
+    timeout: Duration,
+    /// `None` after the thread missed its check-in, until it checks in again.
+    deadline: Option<Instant>,
+}
+
+/// Outcome of checking a thread, see [`Watchdog::check()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Check {
+    /// The thread isn't registered, or has missed its check-in before and hasn't checked in
+    /// since.
+    Unsupervised,
+    /// The thread has to check in by this deadline.
+    Pending(Instant),
+    /// The thread has just missed its check-in.
+    Missed,
+}
+
+/// Watchdog bookkeeping.
+struct Watchdog {
+    /// Registration of each thread, indexed by [`ThreadId`].
+    threads: [Option<Registration>; THREAD_COUNT],
+    policy: Policy,
+}
+
+impl Watchdog {
+    /// Creates a new [`Watchdog`] without registrations.
+    const fn new() -> Self {
+        Self {
+            threads: [None; THREAD_COUNT],
+            policy: Policy::Reboot,
+        }
+    }
+
+    /// Registers a thread that has to check in by `now + timeout`.
+    fn register(&mut self, thread_id: ThreadId, timeout: Duration, now: Instant) {
+        self.threads[usize::from(thread_id)] = Some(Registration {
+            timeout,
+            deadline: Some(now + timeout),
+        });
+    }
+
+    /// Ends the registration of a thread.
+    fn unregister(&mut self, thread_id: ThreadId) {
+        self.threads[usize::from(thread_id)] = None;
+    }
+
+    /// Moves the deadline of a registered thread to `now` plus its timeout.
+    ///
+    /// Returns `true` if the thread had missed its check-in before.
+    fn checkin(&mut self, thread_id: ThreadId, now: Instant) -> bool {
+        let Some(registration) = self.threads[usize::from(thread_id)].as_mut() else {
+            return false;
+        };
+        let was_missed = registration.deadline.is_none();
+        registration.deadline = Some(now + registration.timeout);
+        was_missed
+    }
+
+    /// Checks whether a thread has missed its check-in at `now`.
+    ///
+    /// A missed check-in is only reported once, until the thread checks in again.
+    fn check(&mut self, thread_id: ThreadId, now: Instant) -> Check {
+        let Some(registration) = self.threads[usize::from(thread_id)].as_mut() else {
+            return Check::Unsupervised;
+        };
+        match registration.deadline {
+            None => Check::Unsupervised,
+            Some(deadline) if deadline > now => Check::Pending(deadline),
+            Some(_) => {
+                registration.deadline = None;
+                Check::Missed
+            }
+        }
+    }
+}
+
+static WATCHDOG: Mutex<RefCell<Watchdog>> = Mutex::new(RefCell::new(Watchdog::new()));
+
+/// Sets what happens when a thread misses its check-in.
+///
+/// Defaults to [`Policy::Reboot`].
+pub fn set_policy(policy: Policy) {
+    critical_section::with(|cs| WATCHDOG.borrow_ref_mut(cs).policy = policy);
+}
+
+/// Registers the current thread with the watchdog, which expects it to [`checkin()`] at
+/// least once every `timeout`, starting now.
+///
+/// Registering again changes the timeout.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn register(timeout: Duration) {
+    let thread_id = current_tid();
+    let now = Instant::now();
+    critical_section::with(|cs| {
+        WATCHDOG
+            .borrow_ref_mut(cs)
+            .register(thread_id, timeout, now);
+    });
+    // The deadline might be earlier than the one the timer task waits for.
+    crate::time::timer_changed();
+}
+
+/// Ends the registration of the current thread.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn unregister() {
+    let thread_id = current_tid();
+    critical_section::with(|cs| WATCHDOG.borrow_ref_mut(cs).unregister(thread_id));
+}
+
+/// Signals the watchdog that the current thread is still making progress.
+///
+/// The thread has to check in again within its timeout.
+/// Does nothing if the current thread isn't registered.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn checkin() {
+    let thread_id = current_tid();
+    let now = Instant::now();
+    let was_missed =
+        critical_section::with(|cs| WATCHDOG.borrow_ref_mut(cs).checkin(thread_id, now));
+    // Deadlines otherwise only move later, while the timer task waits for an earlier one
+    // already.
+    if was_missed {
+        crate::time::timer_changed();
+    }
+}
+
+/// Drops the registration of a newly created thread.
+pub(crate) fn reset_registration(thread_id: ThreadId) {
+    critical_section::with(|cs| WATCHDOG.borrow_ref_mut(cs).unregister(thread_id));
+}
+
+/// Returns the ID of the current thread.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+fn current_tid() -> ThreadId {
+    crate::current_tid().expect("Function should be called inside a thread context.")
+}
+
+/// Checks all registered threads and applies the policy to those that missed their check-in.
+///
+/// Returns the next check-in deadline.
+pub(crate) fn supervise(now: Instant) -> Option<Instant> {
+    let mut next: Option<Instant> = None;
+    for i in 0..THREAD_COUNT {
+        let thread_id = ThreadId::new(i as u8);
+        let state = SCHEDULER.with(|scheduler| scheduler.get_state(thread_id));
+        let (check, policy) = critical_section::with(|cs| {
+            let mut watchdog = WATCHDOG.borrow_ref_mut(cs);
+            if state.is_none() {
+                // The thread has finished.
+                watchdog.unregister(thread_id);
+            }
+            (watchdog.check(thread_id, now), watchdog.policy)
+        });
+        match check {
+            Check::Unsupervised => {}
+            Check::Pending(deadline) => {
+                next = Some(next.map_or(deadline, |next| next.min(deadline)));
+            }
+            Check::Missed => {
+                ariel_os_debug::log::error!(
+                    "watchdog: thread {} missed its check-in, state: {:?}",
+                    i,
+                    state
+                );
+                match policy {
+                    Policy::Reboot => ariel_os_power::reboot(),
+                    Policy::Callback(hook) => hook(thread_id),
+                }
+            }
+        }
+    }
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkin_and_miss() {
+        let mut watchdog = Watchdog::new();
+        let thread_id = ThreadId::new(1);
+        let at = Instant::from_millis;
+
+        assert_eq!(watchdog.check(thread_id, at(0)), Check::Unsupervised);
+        watchdog.register(thread_id, Duration::from_millis(50), at(0));
+        assert_eq!(watchdog.check(thread_id, at(40)), Check::Pending(at(50)));
+        assert!(!watchdog.checkin(thread_id, at(40)));
+        assert_eq!(watchdog.check(thread_id, at(60)), Check::Pending(at(90)));
+
+        // A missed check-in is only reported once.
+        assert_eq!(watchdog.check(thread_id, at(90)), Check::Missed);
+        assert_eq!(watchdog.check(thread_id, at(200)), Check::Unsupervised);
+
+        // Checking in again resumes the supervision.
+        assert!(watchdog.checkin(thread_id, at(200)));
+        assert_eq!(watchdog.check(thread_id, at(200)), Check::Pending(at(250)));
+
+        watchdog.unregister(thread_id);
+        assert!(!watchdog.checkin(thread_id, at(300)));
+        assert_eq!(watchdog.check(thread_id, at(400)), Check::Unsupervised);
+    }
+}
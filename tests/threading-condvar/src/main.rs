@@ -0,0 +1,77 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{
+        self, RunqueueId, ThreadId, ThreadState,
+        sync::{Condvar, Mutex},
+        thread_flags,
+    },
+};
+
+static MUTEX: Mutex<usize> = Mutex::new(0);
+static CONDVAR: Condvar = Condvar::new();
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    let tid = thread::current_tid().unwrap();
+
+    // Both other threads have higher priorities and are waiting on the condvar by now.
+    for thread_id in [ThreadId::new(1), ThreadId::new(2)] {
+        assert_eq!(state(thread_id), Some(ThreadState::CondvarBlocked));
+    }
+    let mut value = MUTEX.lock();
+    *value = 1;
+
+    CONDVAR.notify_all();
+    // thread2 has run and is blocked on the mutex, so its priority is inherited.
+    assert_eq!(
+        thread::get_priority(tid),
+        thread::get_priority(ThreadId::new(2))
+    );
+
+    // Unlocking hands the mutex to thread2, which runs before thread1.
+    drop(value);
+    assert_eq!(thread::get_priority(tid), Some(RunqueueId::new(1)));
+
+    // Wait for other threads to complete.
+    thread_flags::wait_all(0b11);
+    assert_eq!(*MUTEX.lock(), 3);
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
+
+fn state(thread_id: ThreadId) -> Option<ThreadState> {
+    thread::threads()
+        .find(|info| info.tid == thread_id)
+        .map(|info| info.state)
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn thread1() {
+    let mut value = MUTEX.lock();
+    while *value == 0 {
+        value = CONDVAR.wait(value);
+    }
+    // thread2 has a higher priority and got the mutex first.
+    assert_eq!(*value, 2);
+    *value = 3;
+    drop(value);
+
+    thread_flags::set(ThreadId::new(0), 0b1);
+}
+
+#[ariel_os::thread(autostart, priority = 3)]
+fn thread2() {
+    let mut value = MUTEX.lock();
+    while *value == 0 {
+        value = CONDVAR.wait(value);
+    }
+    assert_eq!(*value, 1);
+    *value = 2;
+    drop(value);
+
+    thread_flags::set(ThreadId::new(0), 0b10);
+}
@@ -0,0 +1,38 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{self, CoreAffinity},
+};
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    let tid = thread::current_tid().unwrap();
+    assert_eq!(
+        thread::get_core_affinity(tid),
+        Some(CoreAffinity::no_affinity())
+    );
+
+    let affinity = CoreAffinity::one(thread::core_id());
+    thread::set_core_affinity(tid, affinity);
+    assert_eq!(thread::get_core_affinity(tid), Some(affinity));
+
+    // The thread is moved to a matching core before it continues.
+    #[cfg(feature = "core-affinity")]
+    for core in [1, 0, 1] {
+        let affinity = CoreAffinity::one(thread::CoreId::new(core));
+        thread::set_core_affinity(tid, affinity);
+        assert_eq!(thread::get_core_affinity(tid), Some(affinity));
+        assert_eq!(thread::core_id(), thread::CoreId::new(core));
+    }
+
+    thread::set_core_affinity(tid, CoreAffinity::no_affinity());
+    assert_eq!(
+        thread::get_core_affinity(tid),
+        Some(CoreAffinity::no_affinity())
+    );
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
@@ -0,0 +1,48 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{self, ThreadId, thread_flags},
+    time::{Duration, Instant},
+};
+
+fn busy_wait(duration: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < duration {}
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    let tid = thread::current_tid().unwrap();
+    thread::reset_cpu_times();
+
+    busy_wait(Duration::from_millis(50));
+    let cpu_time = thread::cpu_time(tid).unwrap();
+    assert!(cpu_time.runtime >= Duration::from_millis(50));
+    assert!(cpu_time.runtime <= thread::cpu_time_elapsed());
+
+    // While this thread sleeps, thread1 runs.
+    thread_flags::set(ThreadId::new(1), 0b1);
+    thread::sleep(Duration::from_millis(50));
+    let thread1 = thread::cpu_time(ThreadId::new(1)).unwrap();
+    assert!(thread1.runtime >= Duration::from_millis(20));
+    assert!(thread1.switches >= 1);
+
+    // Sleeping isn't accounted to this thread.
+    assert!(thread::cpu_time(tid).unwrap().runtime < Duration::from_millis(90));
+
+    thread::reset_cpu_times();
+    assert!(thread::cpu_time(tid).unwrap().runtime < Duration::from_millis(1));
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread1() {
+    thread_flags::wait_one(0b1);
+    busy_wait(Duration::from_millis(20));
+    // Stay alive so that the CPU time can still be read.
+    thread::park();
+}
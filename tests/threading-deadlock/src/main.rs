@@ -0,0 +1,54 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{
+        ThreadId,
+        sync::{DeadlockError, Lock, Mutex},
+        thread_flags,
+    },
+};
+
+static MUTEX_A: Mutex<()> = Mutex::new(());
+static MUTEX_B: Mutex<()> = Mutex::new(());
+static LOCK: Lock = Lock::new();
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn thread0() {
+    let guard_a = MUTEX_A.lock();
+    // Let thread1 lock `MUTEX_B`.
+    thread_flags::wait_one(0b1);
+
+    // thread1 isn't waiting for anything yet, so this waits until thread1 has detected
+    // the deadlock and released `MUTEX_B`.
+    let guard_b = MUTEX_B.lock_checked().unwrap();
+    drop(guard_b);
+    drop(guard_a);
+
+    // Waiting for a lock that the current thread acquired last is fine as long as another
+    // thread releases it, so this must not panic.
+    LOCK.acquire();
+    thread_flags::set(ThreadId::new(1), 0b10);
+    LOCK.acquire();
+    LOCK.release();
+    assert_eq!(LOCK.acquire_checked(), Ok(()));
+    LOCK.release();
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread1() {
+    let guard_b = MUTEX_B.lock();
+    thread_flags::set(ThreadId::new(0), 0b1);
+
+    // thread0 owns `MUTEX_A` and waits for `MUTEX_B`, which this thread owns.
+    assert!(matches!(MUTEX_A.lock_checked(), Err(DeadlockError)));
+    drop(guard_b);
+
+    // thread0 waits for `LOCK`, which it acquired itself before.
+    thread_flags::wait_one(0b10);
+    LOCK.release();
+}
@@ -0,0 +1,58 @@
+#![no_main]
+#![no_std]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{self, RunqueueId, ThreadId, thread_flags},
+    time::{Duration, Instant},
+};
+
+static STEP: AtomicUsize = AtomicUsize::new(0);
+
+fn busy_wait(duration: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < duration {}
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn thread0() {
+    let tid = thread::current_tid().unwrap();
+    thread::edf_start(Duration::from_millis(100), Duration::from_millis(100));
+    assert_eq!(thread::get_priority(tid), Some(thread::EDF_PRIORITY));
+    assert_eq!(thread::deadline_misses(tid), Some(0));
+
+    // Lets thread1 become an EDF thread.
+    thread_flags::wait_one(0b1);
+    // thread1 has the earlier deadline, so it kept running after waking this thread.
+    assert_eq!(STEP.load(Ordering::Relaxed), 1);
+
+    // Overrun the job's deadline. thread1 is released again meanwhile, but its deadline
+    // is later than this job's.
+    busy_wait(Duration::from_millis(150));
+    assert_eq!(STEP.load(Ordering::Relaxed), 1);
+
+    // The next job starts right away, with a deadline later than thread1's.
+    thread::edf_wait_next_period();
+    assert_eq!(STEP.load(Ordering::Relaxed), 2);
+    assert_eq!(thread::deadline_misses(tid), Some(1));
+
+    thread::edf_stop();
+    assert_eq!(thread::get_priority(tid), Some(RunqueueId::new(2)));
+    assert_eq!(thread::deadline_misses(tid), None);
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread1() {
+    thread::edf_start(Duration::from_millis(100), Duration::from_millis(10));
+    thread_flags::set(ThreadId::new(0), 0b1);
+    STEP.store(1, Ordering::Relaxed);
+
+    thread::edf_wait_next_period();
+    STEP.store(2, Ordering::Relaxed);
+    thread::edf_stop();
+}
@@ -0,0 +1,88 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{
+        self, ThreadId,
+        msg::{self, MSG_QUEUE_SIZE, MsgError},
+    },
+    time::Duration,
+};
+
+const MSG_PLAIN: u16 = 1;
+const MSG_REQUEST: u16 = 2;
+const MSG_REPLY: u16 = 3;
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    let own_id = thread::current_tid().unwrap();
+
+    // thread1 has a higher priority and handles each message right away.
+    msg::send(ThreadId::new(1), MSG_PLAIN, 42).unwrap();
+    let reply = msg::send_receive(ThreadId::new(1), MSG_REQUEST, 41).unwrap();
+    assert_eq!(reply.sender, ThreadId::new(1));
+    assert_eq!(reply.msg_type, MSG_REPLY);
+    assert_eq!(reply.content, 42);
+
+    // Messages are queued until the queue is full.
+    assert_eq!(msg::try_receive(), None);
+    for i in 0..MSG_QUEUE_SIZE {
+        msg::try_send(own_id, MSG_PLAIN, i).unwrap();
+    }
+    assert_eq!(
+        msg::try_send(own_id, MSG_PLAIN, MSG_QUEUE_SIZE),
+        Err(MsgError::WouldBlock)
+    );
+    assert_eq!(msg::available(), MSG_QUEUE_SIZE);
+    for i in 0..MSG_QUEUE_SIZE {
+        let msg = msg::receive();
+        assert_eq!(msg.sender, own_id);
+        assert_eq!(msg.content, i);
+    }
+
+    // This thread doesn't wait for a reply to its own message.
+    msg::try_send(own_id, MSG_PLAIN, 0).unwrap();
+    let msg = msg::try_receive().unwrap();
+    assert_eq!(
+        msg::reply(&msg, MSG_REPLY, 0),
+        Err(MsgError::NotWaitingForReply)
+    );
+
+    // thread2 finishes while this thread is blocked sending to it, without taking any
+    // message.
+    for i in 0..MSG_QUEUE_SIZE {
+        msg::try_send(ThreadId::new(2), MSG_PLAIN, i).unwrap();
+    }
+    assert_eq!(
+        msg::send(ThreadId::new(2), MSG_PLAIN, MSG_QUEUE_SIZE),
+        Err(MsgError::ReceiverGone)
+    );
+    assert_eq!(
+        msg::try_send(ThreadId::new(2), MSG_PLAIN, 0),
+        Err(MsgError::InvalidThread)
+    );
+
+    assert!(msg::receive_timeout(Duration::from_millis(10)).is_err());
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn thread1() {
+    loop {
+        let msg = msg::receive();
+        assert_eq!(msg.sender, ThreadId::new(0));
+        match msg.msg_type {
+            MSG_PLAIN => assert_eq!(msg.content, 42),
+            MSG_REQUEST => msg::reply(&msg, MSG_REPLY, msg.content + 1).unwrap(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[ariel_os::thread(autostart)]
+fn thread2() {
+    thread::sleep(Duration::from_millis(100));
+}
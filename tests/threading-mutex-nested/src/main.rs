@@ -0,0 +1,101 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::thread::{self, RunqueueId, ThreadId, sync::Mutex, thread_flags};
+
+static MUTEX_A: Mutex<()> = Mutex::new(());
+static MUTEX_B: Mutex<()> = Mutex::new(());
+
+fn prio(thread_id: u8) -> Option<RunqueueId> {
+    thread::get_priority(ThreadId::new(thread_id))
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    // Priority inheritance propagates down a chain of mutexes.
+    let a = MUTEX_A.lock();
+
+    // thread1 locks B and waits for A.
+    thread_flags::set(ThreadId::new(1), 0b1);
+    assert_eq!(prio(0), Some(RunqueueId::new(2)));
+
+    // thread2 waits for B, which is owned by thread1, which waits for A.
+    thread_flags::set(ThreadId::new(2), 0b1);
+    assert_eq!(prio(1), Some(RunqueueId::new(3)));
+    assert_eq!(prio(0), Some(RunqueueId::new(3)));
+
+    drop(a);
+    assert_eq!(prio(0), Some(RunqueueId::new(1)));
+
+    // Inherited priorities are unwound correctly when releasing out of order.
+    let a = MUTEX_A.lock();
+    let b = MUTEX_B.lock();
+
+    // thread3 waits for A.
+    thread_flags::set(ThreadId::new(3), 0b1);
+    assert_eq!(prio(0), Some(RunqueueId::new(2)));
+
+    // thread4 waits for B.
+    thread_flags::set(ThreadId::new(4), 0b1);
+    assert_eq!(prio(0), Some(RunqueueId::new(3)));
+
+    // thread4 still waits for B.
+    drop(a);
+    assert_eq!(prio(0), Some(RunqueueId::new(3)));
+
+    drop(b);
+    assert_eq!(prio(0), Some(RunqueueId::new(1)));
+
+    // Wait for other threads to complete.
+    thread_flags::wait_all(0b1111);
+    ariel_os::debug::log::info!("Test passed!");
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn thread1() {
+    thread_flags::wait_one(0b1);
+
+    let b = MUTEX_B.lock();
+    let a = MUTEX_A.lock();
+    // Inherited from thread2, which waits for B.
+    assert_eq!(prio(1), Some(RunqueueId::new(3)));
+
+    drop(a);
+    assert_eq!(prio(1), Some(RunqueueId::new(3)));
+
+    drop(b);
+    assert_eq!(prio(1), Some(RunqueueId::new(2)));
+
+    thread_flags::set(ThreadId::new(0), 0b1);
+}
+
+#[ariel_os::thread(autostart, priority = 3)]
+fn thread2() {
+    thread_flags::wait_one(0b1);
+
+    let b = MUTEX_B.lock();
+    assert_eq!(prio(2), Some(RunqueueId::new(3)));
+    drop(b);
+
+    thread_flags::set(ThreadId::new(0), 0b10);
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn thread3() {
+    thread_flags::wait_one(0b1);
+
+    let a = MUTEX_A.lock();
+    drop(a);
+
+    thread_flags::set(ThreadId::new(0), 0b100);
+}
+
+#[ariel_os::thread(autostart, priority = 3)]
+fn thread4() {
+    thread_flags::wait_one(0b1);
+
+    let b = MUTEX_B.lock();
+    drop(b);
+
+    thread_flags::set(ThreadId::new(0), 0b1000);
+}
@@ -0,0 +1,48 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{ThreadId, sync::Queue, thread_flags},
+};
+
+const COUNT: usize = 12;
+
+static QUEUE: Queue<usize, 4> = Queue::new();
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn thread0() {
+    assert!(QUEUE.is_empty());
+    assert_eq!(QUEUE.try_recv(), None);
+
+    // Fill the queue without blocking.
+    for i in 0..QUEUE.capacity() {
+        assert_eq!(QUEUE.try_send(i), Ok(()));
+    }
+    assert!(QUEUE.is_full());
+    assert_eq!(QUEUE.try_send(COUNT), Err(COUNT));
+
+    // The queue is full, so each send blocks until the lower priority
+    // receiver made space.
+    for i in QUEUE.capacity()..COUNT {
+        QUEUE.send(i);
+        assert!(QUEUE.is_full());
+    }
+
+    // Wait for the receiver to drain the queue.
+    thread_flags::wait_one(0b1);
+    assert!(QUEUE.is_empty());
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread1() {
+    for i in 0..COUNT {
+        assert_eq!(QUEUE.recv(), i);
+    }
+    assert_eq!(QUEUE.try_recv(), None);
+
+    thread_flags::set(ThreadId::new(0), 0b1);
+}
@@ -0,0 +1,59 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{self, ThreadId, ThreadState, sync::Semaphore, thread_flags},
+};
+
+use portable_atomic::{AtomicUsize, Ordering};
+
+static SEMAPHORE: Semaphore = Semaphore::new(1);
+static RUN_ORDER: AtomicUsize = AtomicUsize::new(0);
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    // thread1 has taken the initial permit and is waiting for the next one.
+    assert_eq!(RUN_ORDER.fetch_add(1, Ordering::AcqRel), 1);
+    assert_eq!(SEMAPHORE.count(), 0);
+    let thread1 = thread::threads().find(|info| info.tid == ThreadId::new(1));
+    assert_eq!(
+        thread1.map(|info| info.state),
+        Some(ThreadState::SemaphoreBlocked)
+    );
+
+    // The permit is handed to the higher priority waiter, which runs right away.
+    SEMAPHORE.post();
+    assert_eq!(RUN_ORDER.fetch_add(1, Ordering::AcqRel), 3);
+
+    // Without waiters, permits are counted.
+    SEMAPHORE.post();
+    SEMAPHORE.post();
+    assert_eq!(SEMAPHORE.count(), 2);
+
+    // Let thread1 take one of them.
+    thread_flags::set(ThreadId::new(1), 0b1);
+    thread_flags::wait_one(0b1);
+    assert_eq!(SEMAPHORE.count(), 1);
+    assert!(SEMAPHORE.try_wait());
+    assert!(!SEMAPHORE.try_wait());
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn thread1() {
+    assert_eq!(RUN_ORDER.fetch_add(1, Ordering::AcqRel), 0);
+
+    SEMAPHORE.wait();
+    assert!(!SEMAPHORE.try_wait());
+
+    SEMAPHORE.wait();
+    assert_eq!(RUN_ORDER.fetch_add(1, Ordering::AcqRel), 2);
+
+    // Wait for thread0 to post more permits.
+    thread_flags::wait_one(0b1);
+    SEMAPHORE.wait();
+    thread_flags::set(ThreadId::new(0), 0b1);
+}
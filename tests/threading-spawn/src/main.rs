@@ -0,0 +1,49 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{self, JoinHandle, SpawnError, THREAD_COUNT},
+};
+
+fn worker(value: usize) {
+    thread::exit(value * 2);
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    // Spawn more short-lived threads than there are thread slots, which only works if
+    // finished threads are cleaned up.
+    for i in 0..THREAD_COUNT * 4 {
+        let handle = thread::spawn(worker, i, 1024, 2).unwrap();
+        assert_eq!(handle.join(), Some(i * 2));
+    }
+
+    // Lower priority workers only run once this thread waits for them.
+    let handles = [
+        thread::spawn(worker, 1, 1024, 0).unwrap(),
+        thread::spawn(worker, 2, 2048, 0).unwrap(),
+    ];
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.join(), Some((i + 1) * 2));
+    }
+
+    // Once all thread slots are in use, spawning fails without leaking the stack.
+    let mut handles: [Option<JoinHandle>; THREAD_COUNT] = [const { None }; THREAD_COUNT];
+    let mut spawned = 0;
+    for (i, slot) in handles.iter_mut().enumerate() {
+        match thread::spawn(worker, i, 512, 0) {
+            Ok(handle) => *slot = Some(handle),
+            Err(SpawnError) => break,
+        }
+        spawned += 1;
+    }
+    assert!(spawned > 0 && spawned < THREAD_COUNT);
+    for (i, handle) in handles.into_iter().flatten().enumerate() {
+        assert_eq!(handle.join(), Some(i * 2));
+    }
+    assert_eq!(thread::spawn(worker, 3, 512, 2).unwrap().join(), Some(6));
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
@@ -0,0 +1,46 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    reexports::static_cell::ConstStaticCell,
+    thread::{self, ThreadId},
+};
+
+use portable_atomic::{AtomicUsize, Ordering};
+
+static STACK: ConstStaticCell<[u8; 2048]> = ConstStaticCell::new([0; 2048]);
+static OVERFLOWING_THREAD: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+fn on_stack_overflow(thread_id: ThreadId) {
+    assert_eq!(
+        usize::from(thread_id),
+        OVERFLOWING_THREAD.load(Ordering::Acquire)
+    );
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
+
+fn overflowing_thread(canary: usize) {
+    let thread_id = thread::current_tid().unwrap();
+    OVERFLOWING_THREAD.store(usize::from(thread_id), Ordering::Release);
+    // Simulate a stack overflow by overwriting the canary at the lowest end of the stack.
+    // SAFETY: the canary is part of this thread's stack, which is otherwise unused there.
+    unsafe { core::ptr::write_volatile(canary as *mut u8, 0) };
+    // The overflow is detected when this thread is switched out.
+    thread::yield_same();
+    thread::park();
+    unreachable!();
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    thread::set_stack_overflow_hook(on_stack_overflow);
+
+    let stack = STACK.take();
+    let canary = stack.as_ptr() as usize;
+    // The thread has a higher priority, so it runs right away.
+    thread::create(overflowing_thread, canary, stack, 2, None);
+
+    unreachable!();
+}
@@ -0,0 +1,47 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    reexports::static_cell::ConstStaticCell,
+    thread,
+};
+
+use portable_atomic::{AtomicUsize, Ordering};
+
+static STACKS: [ConstStaticCell<[u8; 2048]>; 2] = [const { ConstStaticCell::new([0; 2048]) }; 2];
+
+ariel_os::thread::thread_local! {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+}
+
+/// Increments the current thread's counter and returns the new value.
+fn increment() -> usize {
+    COUNTER.with(|counter| counter.fetch_add(1, Ordering::Relaxed) + 1)
+}
+
+fn worker(increments: usize) {
+    for _ in 1..increments {
+        increment();
+    }
+    thread::exit(increment());
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    assert_eq!(increment(), 1);
+
+    // Each thread starts with a fresh value, and doesn't affect other threads' values.
+    let first = thread::create_joinable(worker, 3, STACKS[0].take(), 2, None);
+    let first_id = first.thread_id();
+    assert_eq!(first.join(), Some(3));
+    assert_eq!(increment(), 2);
+
+    // The value is reset when the slot of a finished thread is reused.
+    let second = thread::create_joinable(worker, 2, STACKS[1].take(), 2, None);
+    assert_eq!(second.thread_id(), first_id);
+    assert_eq!(second.join(), Some(2));
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
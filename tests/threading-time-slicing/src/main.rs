@@ -0,0 +1,51 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use portable_atomic::{AtomicUsize, Ordering};
+
+static COUNTERS: [AtomicUsize; 2] = [const { AtomicUsize::new(0) }; 2];
+
+fn busy_loop(index: usize) -> ! {
+    // Never yields, so the other thread only gets to run through time-slicing.
+    loop {
+        COUNTERS[index].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// The busy threads have a higher priority than the executor thread, which they starve, so
+// the tick must not depend on it.
+#[ariel_os::thread(autostart, priority = 10)]
+fn thread0() {
+    busy_loop(0);
+}
+
+#[ariel_os::thread(autostart, priority = 10)]
+fn thread1() {
+    busy_loop(1);
+}
+
+#[ariel_os::thread(autostart, priority = 11)]
+fn thread2() {
+    assert_eq!(
+        thread::get_time_slice(ThreadId::new(0)),
+        Some(thread::DEFAULT_TIME_SLICE)
+    );
+
+    thread::sleep(Duration::from_millis(100));
+
+    // Both same-priority threads made progress.
+    assert!(COUNTERS[0].load(Ordering::Relaxed) > 0);
+    assert!(COUNTERS[1].load(Ordering::Relaxed) > 0);
+
+    thread::set_time_slice(ThreadId::new(0), None);
+    assert_eq!(thread::get_time_slice(ThreadId::new(0)), None);
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}
@@ -0,0 +1,69 @@
+#![no_main]
+#![no_std]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    thread::{
+        self, ThreadId, thread_flags,
+        watchdog::{self, Policy},
+    },
+    time::{Duration, Instant},
+};
+
+static MISSED: AtomicUsize = AtomicUsize::new(0);
+
+fn on_missed(thread_id: ThreadId) {
+    MISSED.store(MISSED.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    thread_flags::set(thread_id, 0b1);
+}
+
+#[ariel_os::thread(autostart)]
+fn thread0() {
+    watchdog::set_policy(Policy::Callback(on_missed));
+    watchdog::register(Duration::from_millis(50));
+
+    for _ in 0..5 {
+        thread::sleep(Duration::from_millis(20));
+        watchdog::checkin();
+    }
+    assert_eq!(MISSED.load(Ordering::Relaxed), 0);
+
+    // Without checking in, the callback wakes this thread once the timeout has passed.
+    let start = Instant::now();
+    thread_flags::wait_one(0b1);
+    assert!(start.elapsed() >= Duration::from_millis(40));
+    assert_eq!(MISSED.load(Ordering::Relaxed), 1);
+
+    // A missed check-in is only reported once.
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(MISSED.load(Ordering::Relaxed), 1);
+
+    watchdog::checkin();
+    watchdog::unregister();
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(MISSED.load(Ordering::Relaxed), 1);
+
+    // thread1 ends the test.
+    thread_flags::set(ThreadId::new(1), 0b10);
+    loop {
+        thread::park();
+    }
+}
+
+// The busy thread has a higher priority than the executor thread, which it starves, so the
+// watchdog must not depend on it.
+#[ariel_os::thread(autostart, priority = 10)]
+fn thread1() {
+    thread_flags::wait_one(0b10);
+    watchdog::register(Duration::from_millis(50));
+
+    // Busy-loops without checking in, until the callback sets the flag.
+    while thread_flags::get() & 0b1 == 0 {}
+    assert_eq!(MISSED.load(Ordering::Relaxed), 2);
+    watchdog::unregister();
+
+    ariel_os::debug::log::info!("Test passed!");
+    exit(ExitCode::Success);
+}